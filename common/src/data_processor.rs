@@ -20,14 +20,17 @@ pub fn create_dataset_with_sorted_features<'a>(
     MultiTargetDataSetSortedFeatures {
         labels: label_refs,
         sorted_feature_columns,
+        weights: original_dataset.weights.clone(),
     }
 }
 
+// all_labels and all_weights hold every row of the original data set
 pub fn new_partition<'a>(
     dataset: &MultiTargetDataSetSortedFeatures,
     split_column: usize,
     split_value: f64,
     all_labels: &Vec<&'a Vec<f64>>,
+    all_weights: Option<&[f64]>,
 ) -> (
     MultiTargetDataSetSortedFeatures<'a>,
     MultiTargetDataSetSortedFeatures<'a>,
@@ -41,6 +44,8 @@ pub fn new_partition<'a>(
     let partitioned_labels = collect_partitioned_labels(&true_indices, &false_indices, all_labels);
     let true_labels = partitioned_labels.0;
     let false_labels = partitioned_labels.1;
+    let (true_weights, false_weights) =
+        collect_partitioned_weights(&true_indices, &false_indices, all_weights);
 
     let partitioned_feature_colummns = collect_partitioned_feature_columns(&dataset, &true_indices);
     let feature_columns_with_true_values = partitioned_feature_colummns.0;
@@ -49,10 +54,12 @@ pub fn new_partition<'a>(
     let true_dataset = MultiTargetDataSetSortedFeatures {
         labels: true_labels,
         sorted_feature_columns: feature_columns_with_true_values,
+        weights: true_weights,
     };
     let false_dataset = MultiTargetDataSetSortedFeatures {
         labels: false_labels,
         sorted_feature_columns: feature_columns_with_false_values,
+        weights: false_weights,
     };
 
     (true_dataset, false_dataset)
@@ -104,6 +111,27 @@ fn collect_partitioned_labels<'a>(
     (true_labels, false_labels)
 }
 
+fn collect_partitioned_weights(
+    true_indices: &[u8],
+    false_indices: &[u8],
+    all_weights: Option<&[f64]>,
+) -> (Option<Vec<f64>>, Option<Vec<f64>>) {
+    let all_weights = match all_weights {
+        Some(all_weights) => all_weights,
+        None => return (None, None),
+    };
+    let mut true_weights = Vec::new();
+    let mut false_weights = Vec::new();
+    for (i, weight) in all_weights.iter().enumerate() {
+        if true_indices[i] == 1 {
+            true_weights.push(*weight);
+        } else if false_indices[i] == 1 {
+            false_weights.push(*weight);
+        }
+    }
+    (Some(true_weights), Some(false_weights))
+}
+
 fn collect_partitioned_feature_columns(
     dataset: &MultiTargetDataSetSortedFeatures,
     true_indices: &Vec<u8>,
@@ -15,54 +15,81 @@ pub fn read_csv_data_one_hot_multi_target(
     file_path: &str,
     number_of_targets: usize,
 ) -> MultiTargetDataSet {
-    let data_set_read = read_data(file_path).unwrap();
-    let dataset = parse_data_into_features_and_labels(data_set_read);
-    let multi_target_labels = create_multi_target_labels(dataset.labels, number_of_targets);
-
-    let mut columns = vec![];
-    for col in 0..dataset.features[0].len() {
-        let mut column = vec![];
-        for row in 0..dataset.features.len() {
-            column.push(dataset.features[row][col]);
-        }
-        columns.push(column);
-    }
-
-    MultiTargetDataSet {
-        feature_rows: dataset.features,
-        feature_columns: columns,
-        labels: multi_target_labels,
-    }
+    try_read_csv_data_one_hot_multi_target(file_path, number_of_targets).unwrap()
 }
 
 pub fn read_csv_data_multi_target(
     file_path_to_features: &str,
     file_path_to_labels: &str,
 ) -> MultiTargetDataSet {
-    let data_set_features = read_data(file_path_to_features).unwrap();
-    let data_set_labels = read_data(file_path_to_labels).unwrap();
+    try_read_csv_data_multi_target(file_path_to_features, file_path_to_labels).unwrap()
+}
 
-    let columns = create_feature_columns(&data_set_features);
+pub fn get_feature_names(file_path: &str) -> Vec<String> {
+    try_get_feature_names(file_path).unwrap()
+}
 
-    MultiTargetDataSet {
-        feature_rows: data_set_features,
-        feature_columns: columns,
-        labels: data_set_labels,
+// The try_ readers return an error for a missing file, a non-numeric value, a class label
+// outside 0..number_of_targets or mismatched shapes, instead of panicking
+pub fn try_read_csv_data_one_hot_multi_target(
+    file_path: &str,
+    number_of_targets: usize,
+) -> Result<MultiTargetDataSet, Box<dyn Error>> {
+    let dataset = parse_data_into_features_and_labels(read_data(file_path)?);
+    if let Some(label) = dataset
+        .labels
+        .iter()
+        .find(|label| label.fract() != 0. || **label < 0. || **label >= number_of_targets as f64)
+    {
+        return Err(format!(
+            "{}: class label {} is not one of 0..{}",
+            file_path, label, number_of_targets
+        )
+        .into());
     }
+    let multi_target_labels = create_multi_target_labels(dataset.labels, number_of_targets);
+    build_data_set(dataset.features, multi_target_labels)
 }
 
-pub fn get_feature_names(file_path: &str) -> Vec<String> {
-    let feature_names = get_header_record(file_path).unwrap();
-    let mut names_vec = vec![];
-    for i in 0..feature_names.len() {
-        names_vec.push(feature_names.get(i).unwrap().to_owned());
+pub fn try_read_csv_data_multi_target(
+    file_path_to_features: &str,
+    file_path_to_labels: &str,
+) -> Result<MultiTargetDataSet, Box<dyn Error>> {
+    let data_set_features = read_data(file_path_to_features)?;
+    let data_set_labels = read_data(file_path_to_labels)?;
+    build_data_set(data_set_features, data_set_labels)
+}
+
+// Every column is a feature, i.e there is no label column
+pub fn try_read_csv_features(file_path: &str) -> Result<Vec<Vec<f64>>, Box<dyn Error>> {
+    read_data(file_path)
+}
+
+pub fn try_get_feature_names(file_path: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let feature_names = get_header_record(file_path)?;
+    Ok(feature_names.iter().map(|name| name.to_owned()).collect())
+}
+
+fn build_data_set(
+    feature_rows: Vec<Vec<f64>>,
+    labels: Vec<Vec<f64>>,
+) -> Result<MultiTargetDataSet, Box<dyn Error>> {
+    if feature_rows.len() != labels.len() {
+        return Err(format!(
+            "{} feature rows but {} label rows",
+            feature_rows.len(),
+            labels.len()
+        )
+        .into());
     }
-    names_vec
+    // the csv reader already rejects records of different lengths within a file
+    MultiTargetDataSet::try_from_rows(feature_rows, labels)
 }
 
 fn get_header_record(file_path: &str) -> Result<StringRecord, Box<dyn Error>> {
     //feature names should be in the header of the csv file
-    let file = File::open(file_path)?;
+    let file = File::open(file_path)
+        .map_err(|error| format!("Failed to open {}: {}", file_path, error))?;
     let mut reader = csv::Reader::from_reader(file);
     let headers = reader.headers()?;
     Ok(headers.to_owned())
@@ -70,12 +97,12 @@ fn get_header_record(file_path: &str) -> Result<StringRecord, Box<dyn Error>> {
 
 //reading in data from csv, presume header included and label is at the end of each record
 fn read_data(file_path: &str) -> Result<Vec<Vec<f64>>, Box<dyn Error>> {
-    let error_msg = "Failed to open ".to_owned() + file_path;
-    let file = File::open(file_path).expect(&error_msg);
+    let file = File::open(file_path)
+        .map_err(|error| format!("Failed to open {}: {}", file_path, error))?;
     let mut data = vec![];
     let mut reader = csv::Reader::from_reader(file);
     for result in reader.deserialize() {
-        let record: Vec<f64> = result?;
+        let record: Vec<f64> = result.map_err(|error| format!("{}: {}", file_path, error))?;
         data.push(record);
     }
     Ok(data)
@@ -125,13 +152,7 @@ mod tests {
         let mt_labels = create_multi_target_labels(data_set.labels, 3);
         assert_eq!(*mt_labels.get(0).unwrap(), vec![1., 0., 0.]);
 
-        let columns = create_feature_columns(&data_set.features);
-
-        let multi_target_dataset = MultiTargetDataSet {
-            feature_rows: data_set.features.clone(),
-            feature_columns: columns,
-            labels: mt_labels,
-        };
+        let multi_target_dataset = MultiTargetDataSet::from_rows(data_set.features, mt_labels);
         println!("{:?}", multi_target_dataset);
     }
 
@@ -0,0 +1,157 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::Write;
+
+use crate::datasets::SparseBinaryMatrix;
+
+pub fn write_csv_data(
+    file_path: &str,
+    header: &[String],
+    rows: &[Vec<f64>],
+) -> Result<(), Box<dyn Error>> {
+    let file = File::create(file_path)?;
+    write_csv_data_to_writer(file, header, rows)
+}
+
+pub fn write_csv_data_to_writer<W: Write>(
+    writer: W,
+    header: &[String],
+    rows: &[Vec<f64>],
+) -> Result<(), Box<dyn Error>> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    csv_writer.write_record(header)?;
+    for row in rows {
+        csv_writer.serialize(row)?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+// Rows of already formatted fields, fields containing commas or quotes are quoted
+pub fn write_csv_records_to_writer<W: Write>(
+    writer: W,
+    header: &[String],
+    records: &[Vec<String>],
+) -> Result<(), Box<dyn Error>> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    csv_writer.write_record(header)?;
+    for record in records {
+        csv_writer.write_record(record)?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+// Markdown table with the same layout as write_csv_records_to_writer, pipes are escaped
+pub fn write_markdown_table<W: Write>(
+    mut writer: W,
+    header: &[String],
+    records: &[Vec<String>],
+) -> Result<(), Box<dyn Error>> {
+    let format_row = |fields: &[String]| {
+        let escaped_fields: Vec<String> = fields
+            .iter()
+            .map(|field| field.replace('|', "\\|"))
+            .collect();
+        format!("| {} |", escaped_fields.join(" | "))
+    };
+    writeln!(writer, "{}", format_row(header))?;
+    writeln!(writer, "|{}", " --- |".repeat(header.len()))?;
+    for record in records {
+        writeln!(writer, "{}", format_row(record))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// Dense 0/1 csv, one column per sparse matrix column
+pub fn write_sparse_binary_matrix_as_csv<W: Write>(
+    writer: W,
+    header: &[String],
+    matrix: &SparseBinaryMatrix,
+) -> Result<(), Box<dyn Error>> {
+    assert_eq!(header.len(), matrix.number_of_columns);
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    csv_writer.write_record(header)?;
+    let mut dense_row = vec![0u8; matrix.number_of_columns];
+    for row in matrix.rows.iter() {
+        dense_row.iter_mut().for_each(|value| *value = 0);
+        row.iter().for_each(|&column| dense_row[column] = 1);
+        csv_writer.serialize(&dense_row)?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+// LIBSVM format with 1-based feature indices, e.g "0.5,1.2 3:1 17:1".
+// Multi-target label vectors are written comma separated in front of each row.
+pub fn write_sparse_binary_matrix_as_libsvm<W: Write>(
+    mut writer: W,
+    matrix: &SparseBinaryMatrix,
+    labels: &[Vec<f64>],
+) -> Result<(), Box<dyn Error>> {
+    assert_eq!(matrix.rows.len(), labels.len());
+    for (row, label_vector) in matrix.rows.iter().zip(labels) {
+        let label_string = label_vector
+            .iter()
+            .map(|label| label.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        write!(writer, "{}", label_string)?;
+        for column in row {
+            write!(writer, " {}:1", column + 1)?;
+        }
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_sparse_binary_matrix() {
+        let matrix = SparseBinaryMatrix {
+            number_of_columns: 3,
+            rows: vec![vec![0, 2], vec![1]],
+        };
+        let labels = vec![vec![1., 0.], vec![0.5, 2.]];
+
+        let mut libsvm_output = vec![];
+        write_sparse_binary_matrix_as_libsvm(&mut libsvm_output, &matrix, &labels).unwrap();
+        assert_eq!(
+            String::from_utf8(libsvm_output).unwrap(),
+            "1,0 1:1 3:1\n0.5,2 2:1\n"
+        );
+
+        let header = vec!["a".to_owned(), "b".to_owned(), "c".to_owned()];
+        let mut csv_output = vec![];
+        write_sparse_binary_matrix_as_csv(&mut csv_output, &header, &matrix).unwrap();
+        assert_eq!(
+            String::from_utf8(csv_output).unwrap(),
+            "a,b,c\n1,0,1\n0,1,0\n"
+        );
+    }
+
+    #[test]
+    fn test_write_string_records() {
+        let header = vec!["rule".to_owned(), "support".to_owned()];
+        let records = vec![vec!["x >= 1, y < 2".to_owned(), "0.5".to_owned()]];
+
+        let mut csv_output = vec![];
+        write_csv_records_to_writer(&mut csv_output, &header, &records).unwrap();
+        assert_eq!(
+            String::from_utf8(csv_output).unwrap(),
+            "rule,support\n\"x >= 1, y < 2\",0.5\n"
+        );
+
+        let mut markdown_output = vec![];
+        write_markdown_table(&mut markdown_output, &header, &records).unwrap();
+        assert_eq!(
+            String::from_utf8(markdown_output).unwrap(),
+            "| rule | support |\n| --- | --- |\n| x >= 1, y < 2 | 0.5 |\n"
+        );
+    }
+}
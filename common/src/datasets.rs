@@ -1,3 +1,15 @@
+use std::error::Error;
+
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    seq::SliceRandom,
+    Rng, SeedableRng,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::metrics::classification::class_index;
+
 // Used to read in single-target datasets that are to have their labels one-hot encoded
 #[derive(Debug, Clone)]
 pub struct SingleTargetDataSet {
@@ -5,15 +17,426 @@ pub struct SingleTargetDataSet {
     pub labels: Vec<f64>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MultiTargetDataSet {
     pub feature_rows: Vec<Vec<f64>>,
     pub labels: Vec<Vec<f64>>,
     pub feature_columns: Vec<Vec<f64>>,
+    // One non-negative weight per row, every row weighs 1 when there are none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weights: Option<Vec<f64>>,
+}
+
+// feature_rows and feature_columns hold the same values, the methods below keep them in sync
+impl MultiTargetDataSet {
+    // Panics when the shape is invalid, see try_from_rows for an error instead
+    pub fn from_rows(feature_rows: Vec<Vec<f64>>, labels: Vec<Vec<f64>>) -> Self {
+        Self::try_from_rows(feature_rows, labels).unwrap()
+    }
+
+    pub fn try_from_rows(
+        feature_rows: Vec<Vec<f64>>,
+        labels: Vec<Vec<f64>>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let data = Self {
+            feature_columns: transpose(&feature_rows),
+            feature_rows,
+            labels,
+            weights: None,
+        };
+        data.validate()?;
+        Ok(data)
+    }
+
+    // Panics when the shape is invalid, see try_from_columns for an error instead
+    pub fn from_columns(feature_columns: Vec<Vec<f64>>, labels: Vec<Vec<f64>>) -> Self {
+        Self::try_from_columns(feature_columns, labels).unwrap()
+    }
+
+    pub fn try_from_columns(
+        feature_columns: Vec<Vec<f64>>,
+        labels: Vec<Vec<f64>>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut feature_rows = transpose(&feature_columns);
+        // without columns there is still one (empty) row per label
+        if feature_columns.is_empty() {
+            feature_rows = vec![vec![]; labels.len()];
+        }
+        let data = Self {
+            feature_rows,
+            feature_columns,
+            labels,
+            weights: None,
+        };
+        data.validate()?;
+        Ok(data)
+    }
+
+    // Panics when the weights are invalid, see validate
+    pub fn with_weights(self, weights: Vec<f64>) -> Self {
+        let data = Self {
+            weights: Some(weights),
+            ..self
+        };
+        data.validate().unwrap();
+        data
+    }
+
+    pub fn weight(&self, row: usize) -> f64 {
+        self.weights.as_ref().map_or(1., |weights| weights[row])
+    }
+
+    pub fn weights(&self) -> Option<&[f64]> {
+        self.weights.as_deref()
+    }
+
+    // The number of rows when unweighted
+    pub fn total_weight(&self) -> f64 {
+        match &self.weights {
+            Some(weights) => weights.iter().sum(),
+            None => self.number_of_rows() as f64,
+        }
+    }
+
+    // Every row, column and label must have the same length as the others of its kind, and
+    // the columns must be the transpose of the rows. Weights must be finite and non-negative.
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        let number_of_rows = self.number_of_rows();
+        if self.feature_rows.len() != number_of_rows {
+            return Err(format!(
+                "{} feature rows but {} labels",
+                self.feature_rows.len(),
+                number_of_rows
+            )
+            .into());
+        }
+        let number_of_features = self.number_of_features();
+        if let Some(row) = self
+            .feature_rows
+            .iter()
+            .position(|feature_row| feature_row.len() != number_of_features)
+        {
+            return Err(format!(
+                "feature row {} has {} features, expected {}",
+                row,
+                self.feature_rows[row].len(),
+                number_of_features
+            )
+            .into());
+        }
+        let number_of_targets = self.number_of_targets();
+        if let Some(row) = self
+            .labels
+            .iter()
+            .position(|label| label.len() != number_of_targets)
+        {
+            return Err(format!(
+                "label {} has {} targets, expected {}",
+                row,
+                self.labels[row].len(),
+                number_of_targets
+            )
+            .into());
+        }
+        if number_of_rows > 0 && !same_values(&self.feature_columns, &transpose(&self.feature_rows))
+        {
+            return Err("feature columns do not match the feature rows".into());
+        }
+        if let Some(weights) = &self.weights {
+            if weights.len() != number_of_rows {
+                return Err(
+                    format!("{} weights but {} labels", weights.len(), number_of_rows).into(),
+                );
+            }
+            if let Some(row) = weights
+                .iter()
+                .position(|weight| !weight.is_finite() || *weight < 0.)
+            {
+                return Err(format!("weight {} is {}", row, weights[row]).into());
+            }
+            if number_of_rows > 0 && weights.iter().all(|weight| *weight == 0.) {
+                return Err("all weights are 0".into());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn number_of_rows(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn number_of_features(&self) -> usize {
+        self.feature_rows
+            .first()
+            .map_or(0, |feature_row| feature_row.len())
+    }
+
+    pub fn number_of_targets(&self) -> usize {
+        self.labels.first().map_or(0, |label| label.len())
+    }
+
+    // Rows in the order of indices, an index may appear more than once
+    pub fn subset(&self, indices: &[usize]) -> Self {
+        let feature_rows: Vec<Vec<f64>> = indices
+            .iter()
+            .map(|index| self.feature_rows[*index].clone())
+            .collect();
+        Self {
+            feature_columns: match indices.is_empty() {
+                true => vec![vec![]; self.feature_columns.len()],
+                false => transpose(&feature_rows),
+            },
+            labels: indices
+                .iter()
+                .map(|index| self.labels[*index].clone())
+                .collect(),
+            weights: self
+                .weights
+                .as_ref()
+                .map(|weights| indices.iter().map(|index| weights[*index]).collect()),
+            feature_rows,
+        }
+    }
+
+    pub fn shuffle(&self, seed: u64) -> Self {
+        let mut indices: Vec<usize> = (0..self.number_of_rows()).collect();
+        indices.shuffle(&mut StdRng::seed_from_u64(seed));
+        self.subset(&indices)
+    }
+
+    // Returns (train, test) with about test_fraction of the rows in test. When stratified each
+    // class, the argmax of the label, is split separately so both sets keep the class shares.
+    pub fn train_test_split(&self, test_fraction: f64, stratify: bool, seed: u64) -> (Self, Self) {
+        assert!((0. ..=1.).contains(&test_fraction));
+        let mut rng = StdRng::seed_from_u64(seed);
+        let groups: Vec<Vec<usize>> = match stratify {
+            true => {
+                let mut class_indices = vec![vec![]; self.number_of_targets()];
+                for (index, label) in self.labels.iter().enumerate() {
+                    class_indices[class_index(label)].push(index);
+                }
+                class_indices
+            }
+            false => vec![(0..self.number_of_rows()).collect()],
+        };
+        let mut train_indices = vec![];
+        let mut test_indices = vec![];
+        for mut indices in groups {
+            indices.shuffle(&mut rng);
+            let test_size = (indices.len() as f64 * test_fraction).round() as usize;
+            test_indices.extend_from_slice(&indices[..test_size]);
+            train_indices.extend_from_slice(&indices[test_size..]);
+        }
+        train_indices.sort_unstable();
+        test_indices.sort_unstable();
+        (self.subset(&train_indices), self.subset(&test_indices))
+    }
+
+    pub fn select_features(&self, features: &[usize]) -> Self {
+        let feature_columns: Vec<Vec<f64>> = features
+            .iter()
+            .map(|feature| self.feature_columns[*feature].clone())
+            .collect();
+        Self {
+            weights: self.weights.clone(),
+            ..Self::from_columns(feature_columns, self.labels.clone())
+        }
+    }
+
+    pub fn select_targets(&self, targets: &[usize]) -> Self {
+        Self {
+            feature_rows: self.feature_rows.clone(),
+            feature_columns: self.feature_columns.clone(),
+            labels: self
+                .labels
+                .iter()
+                .map(|label| targets.iter().map(|target| label[*target]).collect())
+                .collect(),
+            weights: self.weights.clone(),
+        }
+    }
+
+    // Rows of self followed by the rows of other, unweighted rows weigh 1 if only one is weighted
+    pub fn concat(&self, other: &Self) -> Self {
+        assert_eq!(self.number_of_features(), other.number_of_features());
+        assert_eq!(self.number_of_targets(), other.number_of_targets());
+        let mut feature_rows = self.feature_rows.clone();
+        feature_rows.extend_from_slice(&other.feature_rows);
+        let mut labels = self.labels.clone();
+        labels.extend_from_slice(&other.labels);
+        let data = Self::from_rows(feature_rows, labels);
+        if self.weights.is_none() && other.weights.is_none() {
+            return data;
+        }
+        let weights = (0..self.number_of_rows())
+            .map(|row| self.weight(row))
+            .chain((0..other.number_of_rows()).map(|row| other.weight(row)))
+            .collect();
+        data.with_weights(weights)
+    }
+
+    // As many rows as the data set, drawn with replacement. Rows are drawn in proportion to
+    // their weights, so the sample itself is unweighted.
+    pub fn bootstrap_sample(&self, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let number_of_rows = self.number_of_rows();
+        let indices: Vec<usize> = match &self.weights {
+            Some(weights) => {
+                let distribution = WeightedIndex::new(weights)
+                    .expect("bootstrap sampling needs a positive weight");
+                (0..number_of_rows)
+                    .map(|_| distribution.sample(&mut rng))
+                    .collect()
+            }
+            None => (0..number_of_rows)
+                .map(|_| rng.gen_range(0..number_of_rows))
+                .collect(),
+        };
+        Self {
+            weights: None,
+            ..self.subset(&indices)
+        }
+    }
+}
+
+// Equal, counting missing values (NaN) as equal to each other
+fn same_values(first: &[Vec<f64>], second: &[Vec<f64>]) -> bool {
+    first.len() == second.len()
+        && first
+            .iter()
+            .zip(second)
+            .all(|(first_vector, second_vector)| {
+                first_vector.len() == second_vector.len()
+                    && first_vector
+                        .iter()
+                        .zip(second_vector)
+                        .all(|(first_value, second_value)| {
+                            first_value == second_value
+                                || (first_value.is_nan() && second_value.is_nan())
+                        })
+            })
+}
+
+fn transpose(vectors: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let length = vectors.first().map_or(0, |vector| vector.len());
+    (0..length)
+        .map(|index| vectors.iter().map(|vector| vector[index]).collect())
+        .collect()
 }
 
 #[derive(Debug, Clone)]
 pub struct MultiTargetDataSetSortedFeatures<'a> {
     pub labels: Vec<&'a Vec<f64>>,
     pub sorted_feature_columns: Vec<Vec<(f64, usize)>>,
+    // Weights of the rows of labels, as for MultiTargetDataSet
+    pub weights: Option<Vec<f64>>,
+}
+
+// Sparse matrix where every stored entry is 1, each row holds the (sorted) column indices of its ones
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparseBinaryMatrix {
+    pub number_of_columns: usize,
+    pub rows: Vec<Vec<usize>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_data_set() -> MultiTargetDataSet {
+        let feature_rows = (0..10).map(|i| vec![i as f64, (i * 10) as f64]).collect();
+        let labels = (0..10)
+            .map(|i| match i < 6 {
+                true => vec![1., 0.],
+                false => vec![0., 1.],
+            })
+            .collect();
+        MultiTargetDataSet::from_rows(feature_rows, labels)
+    }
+
+    #[test]
+    fn test_constructors_and_validation() {
+        let data = build_data_set();
+        assert_eq!(data.feature_columns[1][3], 30.);
+        let from_columns =
+            MultiTargetDataSet::from_columns(data.feature_columns.clone(), data.labels.clone());
+        assert_eq!(from_columns.feature_rows, data.feature_rows);
+
+        let mut broken = data.clone();
+        broken.feature_rows[2][0] = -1.;
+        assert!(broken.validate().is_err());
+        broken.feature_rows.pop();
+        assert!(broken.validate().is_err());
+        assert!(
+            MultiTargetDataSet::try_from_rows(vec![vec![1.], vec![2., 3.]], vec![vec![0.]; 2])
+                .is_err()
+        );
+        assert!(MultiTargetDataSet::try_from_columns(vec![vec![1.]], vec![vec![0.]; 2]).is_err());
+
+        // missing values are kept as NaN
+        let mut feature_rows = data.feature_rows.clone();
+        feature_rows[4][1] = f64::NAN;
+        let with_missing_values = MultiTargetDataSet::from_rows(feature_rows, data.labels);
+        assert!(with_missing_values.feature_columns[1][4].is_nan());
+        assert!(with_missing_values.validate().is_ok());
+    }
+
+    #[test]
+    fn test_subset_split_and_selection() {
+        let data = build_data_set();
+        let subset = data.subset(&[7, 2, 2]);
+        assert_eq!(
+            subset.feature_columns,
+            vec![vec![7., 2., 2.], vec![70., 20., 20.]]
+        );
+        assert!(subset.validate().is_ok());
+        assert!(data.subset(&[]).validate().is_ok());
+
+        let (train, test) = data.train_test_split(0.5, true, 4);
+        assert_eq!((train.number_of_rows(), test.number_of_rows()), (5, 5));
+        let class_one_count = test.labels.iter().filter(|label| label[1] == 1.).count();
+        assert_eq!(class_one_count, 2);
+        assert_eq!(train.concat(&test).number_of_rows(), 10);
+
+        let selected = data.select_features(&[1]).select_targets(&[1, 0]);
+        assert_eq!(selected.feature_rows[4], vec![40.]);
+        assert_eq!(selected.labels[0], vec![0., 1.]);
+
+        let shuffled = data.shuffle(1);
+        assert!(shuffled.validate().is_ok());
+        assert_ne!(shuffled.feature_rows, data.feature_rows);
+        let bootstrap_sample = data.bootstrap_sample(1);
+        assert_eq!(bootstrap_sample.number_of_rows(), 10);
+        assert!(bootstrap_sample.validate().is_ok());
+    }
+
+    #[test]
+    fn test_weights_follow_the_rows() {
+        let mut weights = vec![0.; 10];
+        weights[3] = 2.;
+        weights[8] = 1.;
+        let data = build_data_set().with_weights(weights);
+        assert_eq!(data.total_weight(), 3.);
+        assert_eq!(data.subset(&[8, 3]).weights(), Some(&[1., 2.][..]));
+        assert_eq!(data.select_features(&[0]).weight(3), 2.);
+        let (train, test) = data.train_test_split(0.3, false, 2);
+        assert_eq!(train.total_weight() + test.total_weight(), 3.);
+        assert_eq!(data.concat(&build_data_set()).total_weight(), 13.);
+
+        // only rows with a weight can be drawn
+        let bootstrap_sample = data.bootstrap_sample(1);
+        assert!(bootstrap_sample.weights.is_none());
+        assert!(bootstrap_sample
+            .feature_rows
+            .iter()
+            .all(|row| row[0] == 3. || row[0] == 8.));
+
+        let mut broken = data;
+        broken.weights = Some(vec![-1.; 10]);
+        assert!(broken.validate().is_err());
+        broken.weights = Some(vec![1.; 9]);
+        assert!(broken.validate().is_err());
+        broken.weights = Some(vec![0.; 10]);
+        assert!(broken.validate().is_err());
+    }
 }
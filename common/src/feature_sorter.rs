@@ -9,7 +9,7 @@ pub fn get_sorted_feature_tuple_vector(feature_column: &[f64]) -> Vec<(f64, usiz
 
 #[cfg(test)]
 mod tests {
-    use crate::{data_reader::create_feature_columns, datasets::MultiTargetDataSet};
+    use crate::datasets::MultiTargetDataSet;
 
     use super::*;
 
@@ -17,13 +17,7 @@ mod tests {
     fn test_get_sorted_feature_tuple_vector() {
         let features = vec![vec![10., 2., 1.], vec![6., 2., 2.], vec![-1., 2., 3.]];
         let labels = vec![vec![0.], vec![0.], vec![0.]];
-        let columns = create_feature_columns(&features);
-
-        let data = MultiTargetDataSet {
-            feature_rows: features,
-            feature_columns: columns,
-            labels,
-        };
+        let data = MultiTargetDataSet::from_rows(features, labels);
         let column = 0;
         let sorted_feature_tuple_vector =
             get_sorted_feature_tuple_vector(&data.feature_columns[column]);
@@ -1,7 +1,10 @@
 pub mod data_processor;
 pub mod data_reader;
+pub mod data_writer;
 pub mod datasets;
 pub mod feature_sorter;
+pub mod metrics;
 pub mod numerical_calculations;
 pub mod question;
 pub mod results;
+pub mod statistics;
@@ -0,0 +1,523 @@
+// Metrics computed from predictions and label vectors, shared by the trees and the ensembles.
+// The weighted versions take one weight per row, without weights every row weighs 1.
+
+pub mod regression {
+    // Per target metrics and the usual multi-target aggregates. Relative RMSE and R² compare
+    // against predicting the mean of the given labels, so they are NaN for a constant target.
+    #[derive(Debug, Clone)]
+    pub struct RegressionReport {
+        pub mean_squared_error: Vec<f64>,
+        pub root_mean_squared_error: Vec<f64>,
+        pub mean_absolute_error: Vec<f64>,
+        pub r_squared: Vec<f64>,
+        pub explained_variance: Vec<f64>,
+        pub relative_root_mean_squared_error: Vec<f64>,
+        pub correlation_coefficient: Vec<f64>,
+        // Mean over targets of mean_squared_error
+        pub overall_mean_squared_error: f64,
+        // aRRMSE
+        pub average_relative_root_mean_squared_error: f64,
+        pub average_correlation_coefficient: f64,
+    }
+
+    impl RegressionReport {
+        pub fn new(predictions: &[Vec<f64>], labels: &[Vec<f64>]) -> Self {
+            Self::new_weighted(predictions, labels, None)
+        }
+
+        pub fn new_weighted(
+            predictions: &[Vec<f64>],
+            labels: &[Vec<f64>],
+            weights: Option<&[f64]>,
+        ) -> Self {
+            assert_eq!(predictions.len(), labels.len());
+            let weights = weights_or_ones(weights, labels.len());
+            let number_of_targets = labels[0].len();
+            let mut mean_squared_error = vec![];
+            let mut mean_absolute_error = vec![];
+            let mut r_squared = vec![];
+            let mut explained_variance = vec![];
+            let mut relative_root_mean_squared_error = vec![];
+            let mut correlation_coefficient = vec![];
+            for target in 0..number_of_targets {
+                let predicted: Vec<f64> = predictions.iter().map(|p| p[target]).collect();
+                let actual: Vec<f64> = labels.iter().map(|l| l[target]).collect();
+                let errors: Vec<f64> = actual.iter().zip(&predicted).map(|(a, p)| a - p).collect();
+
+                let target_mean_squared_error = calculate_mean(&squares(&errors), &weights);
+                let label_variance = calculate_variance(&actual, &weights);
+                mean_squared_error.push(target_mean_squared_error);
+                mean_absolute_error.push(calculate_mean(
+                    &errors.iter().map(|error| error.abs()).collect::<Vec<_>>(),
+                    &weights,
+                ));
+                r_squared.push(1. - target_mean_squared_error / label_variance);
+                explained_variance
+                    .push(1. - calculate_variance(&errors, &weights) / label_variance);
+                relative_root_mean_squared_error
+                    .push((target_mean_squared_error / label_variance).sqrt());
+                correlation_coefficient.push(calculate_correlation(&predicted, &actual, &weights));
+            }
+
+            Self {
+                root_mean_squared_error: mean_squared_error.iter().map(|mse| mse.sqrt()).collect(),
+                overall_mean_squared_error: average_over_targets(&mean_squared_error),
+                average_relative_root_mean_squared_error: average_over_targets(
+                    &relative_root_mean_squared_error,
+                ),
+                average_correlation_coefficient: average_over_targets(&correlation_coefficient),
+                mean_squared_error,
+                mean_absolute_error,
+                r_squared,
+                explained_variance,
+                relative_root_mean_squared_error,
+                correlation_coefficient,
+            }
+        }
+
+        // One line per target followed by the aggregates, target_{index} is used for missing names
+        pub fn to_string(&self, target_names: &[String]) -> String {
+            let mut lines = vec![format!(
+                "{:<20} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}",
+                "target", "mse", "rmse", "mae", "r2", "expl_var", "rrmse", "corr"
+            )];
+            for target in 0..self.mean_squared_error.len() {
+                let target_name = match target_names.get(target) {
+                    Some(target_name) => target_name.clone(),
+                    None => format!("target_{}", target),
+                };
+                lines.push(format!(
+                    "{:<20} {:>10.4} {:>10.4} {:>10.4} {:>10.4} {:>10.4} {:>10.4} {:>10.4}",
+                    target_name,
+                    self.mean_squared_error[target],
+                    self.root_mean_squared_error[target],
+                    self.mean_absolute_error[target],
+                    self.r_squared[target],
+                    self.explained_variance[target],
+                    self.relative_root_mean_squared_error[target],
+                    self.correlation_coefficient[target]
+                ));
+            }
+            lines.push(format!(
+                "overall mse {:.4}, aRRMSE {:.4}, average correlation {:.4}",
+                self.overall_mean_squared_error,
+                self.average_relative_root_mean_squared_error,
+                self.average_correlation_coefficient
+            ));
+            lines.join("\n")
+        }
+    }
+
+    // Averaged over rows and targets, i.e what the regression models' calculate_score reports
+    pub fn calculate_mean_squared_error(predictions: &[Vec<f64>], labels: &[Vec<f64>]) -> f64 {
+        calculate_weighted_mean_squared_error(predictions, labels, None)
+    }
+
+    pub fn calculate_weighted_mean_squared_error(
+        predictions: &[Vec<f64>],
+        labels: &[Vec<f64>],
+        weights: Option<&[f64]>,
+    ) -> f64 {
+        average_over_targets(&calculate_weighted_mean_squared_error_per_target(
+            predictions,
+            labels,
+            weights,
+        ))
+    }
+
+    pub fn calculate_mean_squared_error_per_target(
+        predictions: &[Vec<f64>],
+        labels: &[Vec<f64>],
+    ) -> Vec<f64> {
+        calculate_weighted_mean_squared_error_per_target(predictions, labels, None)
+    }
+
+    pub fn calculate_weighted_mean_squared_error_per_target(
+        predictions: &[Vec<f64>],
+        labels: &[Vec<f64>],
+        weights: Option<&[f64]>,
+    ) -> Vec<f64> {
+        assert_eq!(predictions.len(), labels.len());
+        let weights = weights_or_ones(weights, labels.len());
+        let number_of_targets = labels.first().map_or(0, |label| label.len());
+        let mut errors = vec![0.; number_of_targets];
+        for ((prediction, label), weight) in predictions.iter().zip(labels).zip(&weights) {
+            for (target, error) in errors.iter_mut().enumerate() {
+                let difference = prediction[target] - label[target];
+                *error += weight * difference * difference;
+            }
+        }
+        let total_weight: f64 = weights.iter().sum();
+        errors.iter().map(|error| error / total_weight).collect()
+    }
+
+    fn weights_or_ones(weights: Option<&[f64]>, number_of_rows: usize) -> Vec<f64> {
+        match weights {
+            Some(weights) => {
+                assert_eq!(weights.len(), number_of_rows);
+                weights.to_vec()
+            }
+            None => vec![1.; number_of_rows],
+        }
+    }
+
+    fn squares(values: &[f64]) -> Vec<f64> {
+        values.iter().map(|value| value * value).collect()
+    }
+
+    fn average_over_targets(values: &[f64]) -> f64 {
+        values.iter().sum::<f64>() / values.len() as f64
+    }
+
+    fn calculate_mean(values: &[f64], weights: &[f64]) -> f64 {
+        values
+            .iter()
+            .zip(weights)
+            .map(|(value, weight)| value * weight)
+            .sum::<f64>()
+            / weights.iter().sum::<f64>()
+    }
+
+    // Population variance
+    fn calculate_variance(values: &[f64], weights: &[f64]) -> f64 {
+        let mean = calculate_mean(values, weights);
+        calculate_mean(
+            &squares(&values.iter().map(|value| value - mean).collect::<Vec<_>>()),
+            weights,
+        )
+    }
+
+    // Pearson correlation coefficient
+    fn calculate_correlation(first: &[f64], second: &[f64], weights: &[f64]) -> f64 {
+        let first_mean = calculate_mean(first, weights);
+        let second_mean = calculate_mean(second, weights);
+        let products: Vec<f64> = first
+            .iter()
+            .zip(second)
+            .map(|(a, b)| (a - first_mean) * (b - second_mean))
+            .collect();
+        let covariance = calculate_mean(&products, weights);
+        covariance
+            / (calculate_variance(first, weights) * calculate_variance(second, weights)).sqrt()
+    }
+}
+
+pub mod classification {
+    // Class of a one-hot label or of a score vector, the first largest entry wins
+    pub fn class_index(vector: &[f64]) -> usize {
+        let mut max_index = 0;
+        for (index, value) in vector.iter().enumerate() {
+            if *value > vector[max_index] {
+                max_index = index;
+            }
+        }
+        max_index
+    }
+
+    // counts[actual][predicted], the sum of the weights of those rows
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct ConfusionMatrix {
+        pub counts: Vec<Vec<f64>>,
+    }
+
+    impl ConfusionMatrix {
+        pub fn new(predictions: &[Vec<f64>], labels: &[Vec<f64>]) -> Self {
+            Self::new_weighted(predictions, labels, None)
+        }
+
+        pub fn new_weighted(
+            predictions: &[Vec<f64>],
+            labels: &[Vec<f64>],
+            weights: Option<&[f64]>,
+        ) -> Self {
+            assert_eq!(predictions.len(), labels.len());
+            let number_of_classes = labels[0].len();
+            let mut counts = vec![vec![0.; number_of_classes]; number_of_classes];
+            for (row, (prediction, label)) in predictions.iter().zip(labels).enumerate() {
+                let weight = weights.map_or(1., |weights| weights[row]);
+                counts[class_index(label)][class_index(prediction)] += weight;
+            }
+            Self { counts }
+        }
+
+        // The number of samples when unweighted
+        pub fn total_weight(&self) -> f64 {
+            self.counts.iter().map(|row| row.iter().sum::<f64>()).sum()
+        }
+
+        pub fn to_string(&self, class_names: &[String]) -> String {
+            let names: Vec<String> = (0..self.counts.len())
+                .map(|class| class_name(class_names, class))
+                .collect();
+            let mut lines = vec![format!(
+                "{:<20} {}",
+                "actual \\ predicted",
+                names
+                    .iter()
+                    .map(|name| format!("{:>10}", name))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            )];
+            for (name, row) in names.iter().zip(&self.counts) {
+                lines.push(format!(
+                    "{:<20} {}",
+                    name,
+                    row.iter()
+                        .map(|count| format!("{:>10}", count))
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                ));
+            }
+            lines.join("\n")
+        }
+    }
+
+    // Per class scores use 0 when their denominator is 0, e.g precision of a never predicted class.
+    // Weighted averages are weighted by support, the total weight of each actual class.
+    #[derive(Debug, Clone)]
+    pub struct ClassificationReport {
+        pub confusion_matrix: ConfusionMatrix,
+        pub precision: Vec<f64>,
+        pub recall: Vec<f64>,
+        pub f1: Vec<f64>,
+        pub support: Vec<f64>,
+        pub accuracy: f64,
+        pub macro_precision: f64,
+        pub macro_recall: f64,
+        pub macro_f1: f64,
+        pub weighted_precision: f64,
+        pub weighted_recall: f64,
+        pub weighted_f1: f64,
+        // Macro recall
+        pub balanced_accuracy: f64,
+        pub cohen_kappa: f64,
+    }
+
+    impl ClassificationReport {
+        pub fn new(predictions: &[Vec<f64>], labels: &[Vec<f64>]) -> Self {
+            Self::new_weighted(predictions, labels, None)
+        }
+
+        pub fn new_weighted(
+            predictions: &[Vec<f64>],
+            labels: &[Vec<f64>],
+            weights: Option<&[f64]>,
+        ) -> Self {
+            let confusion_matrix = ConfusionMatrix::new_weighted(predictions, labels, weights);
+            let counts = &confusion_matrix.counts;
+            let number_of_classes = counts.len();
+            let number_of_samples = confusion_matrix.total_weight();
+            let support: Vec<f64> = counts.iter().map(|row| row.iter().sum()).collect();
+            let predicted_counts: Vec<f64> = (0..number_of_classes)
+                .map(|class| counts.iter().map(|row| row[class]).sum())
+                .collect();
+
+            let mut precision = vec![];
+            let mut recall = vec![];
+            let mut f1 = vec![];
+            for class in 0..number_of_classes {
+                let true_positives = counts[class][class];
+                let class_precision = safe_divide(true_positives, predicted_counts[class]);
+                let class_recall = safe_divide(true_positives, support[class]);
+                precision.push(class_precision);
+                recall.push(class_recall);
+                f1.push(safe_divide(
+                    2. * class_precision * class_recall,
+                    class_precision + class_recall,
+                ));
+            }
+
+            let correct: f64 = (0..number_of_classes)
+                .map(|class| counts[class][class])
+                .sum();
+            let accuracy = correct / number_of_samples;
+            let expected_agreement = support
+                .iter()
+                .zip(&predicted_counts)
+                .map(|(actual, predicted)| actual * predicted)
+                .sum::<f64>()
+                / (number_of_samples * number_of_samples);
+            let weighted_average = |values: &[f64]| {
+                values
+                    .iter()
+                    .zip(&support)
+                    .map(|(value, class_support)| value * class_support)
+                    .sum::<f64>()
+                    / number_of_samples
+            };
+            let macro_average =
+                |values: &[f64]| values.iter().sum::<f64>() / number_of_classes as f64;
+
+            Self {
+                accuracy,
+                macro_precision: macro_average(&precision),
+                macro_recall: macro_average(&recall),
+                macro_f1: macro_average(&f1),
+                weighted_precision: weighted_average(&precision),
+                weighted_recall: weighted_average(&recall),
+                weighted_f1: weighted_average(&f1),
+                balanced_accuracy: macro_average(&recall),
+                cohen_kappa: safe_divide(accuracy - expected_agreement, 1. - expected_agreement),
+                precision,
+                recall,
+                f1,
+                support,
+                confusion_matrix,
+            }
+        }
+
+        // Per class table, the averages and the confusion matrix, class_{index} for missing names
+        pub fn to_string(&self, class_names: &[String]) -> String {
+            let mut lines = vec![format!(
+                "{:<20} {:>10} {:>10} {:>10} {:>10}",
+                "class", "precision", "recall", "f1", "support"
+            )];
+            for class in 0..self.support.len() {
+                lines.push(format!(
+                    "{:<20} {:>10.4} {:>10.4} {:>10.4} {:>10}",
+                    class_name(class_names, class),
+                    self.precision[class],
+                    self.recall[class],
+                    self.f1[class],
+                    self.support[class]
+                ));
+            }
+            let number_of_samples = self.confusion_matrix.total_weight();
+            lines.push(format!(
+                "{:<20} {:>10.4} {:>10.4} {:>10.4} {:>10}",
+                "macro avg",
+                self.macro_precision,
+                self.macro_recall,
+                self.macro_f1,
+                number_of_samples
+            ));
+            lines.push(format!(
+                "{:<20} {:>10.4} {:>10.4} {:>10.4} {:>10}",
+                "weighted avg",
+                self.weighted_precision,
+                self.weighted_recall,
+                self.weighted_f1,
+                number_of_samples
+            ));
+            lines.push(format!(
+                "accuracy {:.4}, balanced accuracy {:.4}, cohen kappa {:.4}",
+                self.accuracy, self.balanced_accuracy, self.cohen_kappa
+            ));
+            lines.push(String::new());
+            lines.push(self.confusion_matrix.to_string(class_names));
+            lines.join("\n")
+        }
+    }
+
+    fn safe_divide(numerator: f64, denominator: f64) -> f64 {
+        match denominator == 0. {
+            true => 0.,
+            false => numerator / denominator,
+        }
+    }
+
+    fn class_name(class_names: &[String], class: usize) -> String {
+        match class_names.get(class) {
+            Some(class_name) => class_name.clone(),
+            None => format!("class_{}", class),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classification::*, regression::*};
+
+    #[test]
+    fn test_regression_report() {
+        let labels = vec![vec![1., 10.], vec![2., 20.], vec![3., 30.], vec![4., 40.]];
+        let predictions = vec![vec![1., 12.], vec![2., 22.], vec![3., 32.], vec![5., 42.]];
+        let report = RegressionReport::new(&predictions, &labels);
+
+        assert_eq!(report.mean_squared_error, vec![0.25, 4.]);
+        assert_eq!(report.root_mean_squared_error, vec![0.5, 2.]);
+        assert_eq!(report.mean_absolute_error, vec![0.25, 2.]);
+        // label variances are 1.25 and 125
+        assert!((report.r_squared[0] - 0.8).abs() < 1e-10);
+        assert!((report.r_squared[1] - (1. - 4. / 125.)).abs() < 1e-10);
+        // a constant offset is fully explained
+        assert!((report.explained_variance[1] - 1.).abs() < 1e-10);
+        assert!((report.correlation_coefficient[1] - 1.).abs() < 1e-10);
+        assert!((report.relative_root_mean_squared_error[0] - 0.2f64.sqrt()).abs() < 1e-10);
+        assert_eq!(report.overall_mean_squared_error, 2.125);
+        assert_eq!(
+            calculate_mean_squared_error(&predictions, &labels),
+            report.overall_mean_squared_error
+        );
+        assert_eq!(report.to_string(&[]).lines().count(), 4);
+    }
+
+    #[test]
+    fn test_classification_report() {
+        let one_hot = |class: usize| {
+            let mut label = vec![0.; 3];
+            label[class] = 1.;
+            label
+        };
+        let actual_classes = [0, 0, 0, 0, 1, 1, 1, 2, 2, 2];
+        let predicted_classes = [0, 0, 0, 1, 1, 1, 2, 2, 2, 0];
+        let labels: Vec<Vec<f64>> = actual_classes.iter().map(|c| one_hot(*c)).collect();
+        let predictions: Vec<Vec<f64>> = predicted_classes.iter().map(|c| one_hot(*c)).collect();
+        let report = ClassificationReport::new(&predictions, &labels);
+
+        assert_eq!(
+            report.confusion_matrix.counts,
+            vec![vec![3., 1., 0.], vec![0., 2., 1.], vec![1., 0., 2.]]
+        );
+        assert_eq!(report.support, vec![4., 3., 3.]);
+        assert_eq!(report.accuracy, 0.7);
+        assert_eq!(report.precision, vec![0.75, 2. / 3., 2. / 3.]);
+        assert_eq!(report.recall, vec![0.75, 2. / 3., 2. / 3.]);
+        assert!((report.balanced_accuracy - (0.75 + 4. / 3.) / 3.).abs() < 1e-10);
+        // expected agreement (4*4 + 3*3 + 3*3) / 100 = 0.34
+        assert!((report.cohen_kappa - (0.7 - 0.34) / 0.66).abs() < 1e-10);
+        assert!((report.weighted_f1 - 0.7).abs() < 1e-10);
+        assert_eq!(class_index(&[0.2, 0.5, 0.5]), 1);
+
+        let text = report.to_string(&[]);
+        assert!(text.contains("class_2"));
+        assert!(text.contains("weighted avg"));
+    }
+
+    #[test]
+    fn test_weighted_metrics() {
+        let labels = vec![vec![1., 0.], vec![1., 0.], vec![0., 1.]];
+        let predictions = vec![vec![1., 0.], vec![0., 1.], vec![0., 1.]];
+        let report = ClassificationReport::new_weighted(&predictions, &labels, Some(&[3., 1., 1.]));
+        assert_eq!(report.accuracy, 0.8);
+        assert_eq!(report.support, vec![4., 1.]);
+        assert_eq!(
+            report.confusion_matrix.counts,
+            vec![vec![3., 1.], vec![0., 1.]]
+        );
+
+        // an integer weight counts like repeating the row
+        let repeated_labels = vec![vec![1.], vec![2.], vec![2.], vec![4.]];
+        let repeated_predictions = vec![vec![1.5], vec![2.], vec![2.], vec![3.]];
+        let weighted = RegressionReport::new_weighted(
+            &[vec![1.5], vec![2.], vec![3.]],
+            &[vec![1.], vec![2.], vec![4.]],
+            Some(&[1., 2., 1.]),
+        );
+        let repeated = RegressionReport::new(&repeated_predictions, &repeated_labels);
+        assert_eq!(weighted.mean_squared_error, repeated.mean_squared_error);
+        assert!((weighted.r_squared[0] - repeated.r_squared[0]).abs() < 1e-12);
+        assert!(
+            (weighted.correlation_coefficient[0] - repeated.correlation_coefficient[0]).abs()
+                < 1e-12
+        );
+        assert_eq!(
+            calculate_weighted_mean_squared_error(
+                &repeated_predictions,
+                &repeated_labels,
+                Some(&[0., 0., 0., 1.])
+            ),
+            1.
+        );
+    }
+}
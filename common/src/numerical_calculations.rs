@@ -30,6 +30,27 @@ pub fn calculate_average_f64_vector(vector_of_vectors: &Vec<Vec<f64>>) -> Vec<f6
     average_vector
 }
 
+// The unweighted average when there are no weights
+pub fn calculate_weighted_average_f64_vector(
+    vector_of_vectors: &[Vec<f64>],
+    weights: Option<&[f64]>,
+) -> Vec<f64> {
+    let mut average_vector = vec![0.; vector_of_vectors[0].len()];
+    let mut total_weight = 0.;
+    for (i, vector) in vector_of_vectors.iter().enumerate() {
+        let weight = weights.map_or(1., |weights| weights[i]);
+        total_weight += weight;
+        average_vector
+            .iter_mut()
+            .zip(vector)
+            .for_each(|(average, element)| *average += weight * element);
+    }
+    average_vector
+        .iter_mut()
+        .for_each(|average| *average /= total_weight);
+    average_vector
+}
+
 pub fn mean_sum_of_squared_differences_between_f64_slices(first: &[f64], second: &[f64]) -> f64 {
     assert_eq!(first.len(), second.len());
     let squared_differences: Vec<f64> = first
@@ -98,6 +119,10 @@ mod tests {
 
         let vector_of_vectors = vec![vec_one.clone(), vec_two.clone()];
         assert_eq!(calculate_average_f64_vector(&vector_of_vectors), vec_one);
+        assert_eq!(
+            calculate_weighted_average_f64_vector(&[vec![1., 4.], vec![4., 1.]], Some(&[2., 1.])),
+            vec![2., 3.]
+        );
         assert_eq!(
             mean_sum_of_squared_differences_between_f64_slices(&vec_one, &vec_two),
             0.
@@ -1,6 +1,8 @@
+use serde::{Deserialize, Serialize};
+
 // Question struct purpose:
 // Given a data row, is this rows value at question.column >= question.value?
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Question {
     pub column: u32,
     pub value: f64,
@@ -0,0 +1,290 @@
+// Two-sided 95% quantiles of Student's t distribution for 1 to 30 degrees of freedom
+const T_QUANTILES_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+// Critical values of the Nemenyi test at alpha = 0.05 for 2 to 20 models (Demšar, 2006)
+const NEMENYI_Q_05: [f64; 19] = [
+    1.960, 2.343, 2.569, 2.728, 2.850, 2.949, 3.031, 3.102, 3.164, 3.219, 3.268, 3.313, 3.354,
+    3.391, 3.426, 3.458, 3.489, 3.517, 3.544,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeanWithInterval {
+    pub mean: f64,
+    // Half the width of the 95% confidence interval, 0 for a single value
+    pub half_width: f64,
+}
+
+// Mean with a t-based 95% confidence interval, using the normal quantile beyond 30 degrees of freedom
+pub fn mean_with_confidence_interval(values: &[f64]) -> MeanWithInterval {
+    assert!(!values.is_empty());
+    let number_of_values = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / number_of_values;
+    if values.len() == 1 {
+        return MeanWithInterval {
+            mean,
+            half_width: 0.,
+        };
+    }
+    let sample_variance = values
+        .iter()
+        .map(|value| (value - mean) * (value - mean))
+        .sum::<f64>()
+        / (number_of_values - 1.);
+    let degrees_of_freedom = values.len() - 1;
+    let t_quantile = T_QUANTILES_95
+        .get(degrees_of_freedom - 1)
+        .copied()
+        .unwrap_or(1.96);
+    MeanWithInterval {
+        mean,
+        half_width: t_quantile * (sample_variance / number_of_values).sqrt(),
+    }
+}
+
+// Ranks starting at 1 for the best score, tied scores share their average rank
+pub fn rank_scores(scores: &[f64], higher_is_better: bool) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|first, second| {
+        let ordering = scores[*first].partial_cmp(&scores[*second]).unwrap();
+        if higher_is_better {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+    let mut ranks = vec![0.; scores.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && scores[order[end]] == scores[order[start]] {
+            end += 1;
+        }
+        // positions start..end hold ranks start + 1..=end
+        let average_rank = (start + end + 1) as f64 / 2.;
+        order[start..end]
+            .iter()
+            .for_each(|index| ranks[*index] = average_rank);
+        start = end;
+    }
+    ranks
+}
+
+#[derive(Clone, Debug)]
+pub struct FriedmanTest {
+    // One per model, lower is better
+    pub average_ranks: Vec<f64>,
+    pub chi_squared: f64,
+    pub degrees_of_freedom: usize,
+    pub p_value: f64,
+    // Iman and Davenport's less conservative F statistic
+    pub f_statistic: f64,
+    // Nemenyi critical difference of average ranks at alpha = 0.05, None for more than 20 models
+    pub critical_difference: Option<f64>,
+}
+
+impl FriedmanTest {
+    // Pairs of models whose average ranks differ by more than the critical difference
+    pub fn significantly_different_pairs(&self) -> Vec<(usize, usize)> {
+        let critical_difference = match self.critical_difference {
+            Some(critical_difference) => critical_difference,
+            None => return vec![],
+        };
+        let mut pairs = vec![];
+        for first in 0..self.average_ranks.len() {
+            for second in first + 1..self.average_ranks.len() {
+                if (self.average_ranks[first] - self.average_ranks[second]).abs()
+                    > critical_difference
+                {
+                    pairs.push((first, second));
+                }
+            }
+        }
+        pairs
+    }
+}
+
+// scores[dataset][model], needs at least two data sets and two models
+pub fn friedman_test(scores: &[Vec<f64>], higher_is_better: bool) -> FriedmanTest {
+    let number_of_datasets = scores.len();
+    let number_of_models = scores[0].len();
+    assert!(number_of_datasets >= 2 && number_of_models >= 2);
+    let mut average_ranks = vec![0.; number_of_models];
+    for dataset_scores in scores {
+        assert_eq!(dataset_scores.len(), number_of_models);
+        let ranks = rank_scores(dataset_scores, higher_is_better);
+        average_ranks
+            .iter_mut()
+            .zip(ranks)
+            .for_each(|(average_rank, rank)| *average_rank += rank / number_of_datasets as f64);
+    }
+
+    let n = number_of_datasets as f64;
+    let k = number_of_models as f64;
+    let chi_squared = 12. * n / (k * (k + 1.))
+        * (average_ranks.iter().map(|rank| rank * rank).sum::<f64>() - k * (k + 1.).powi(2) / 4.);
+    let degrees_of_freedom = number_of_models - 1;
+    // every data set ranking the models the same way gives an infinite statistic
+    let f_statistic = if n * (k - 1.) - chi_squared > 0. {
+        (n - 1.) * chi_squared / (n * (k - 1.) - chi_squared)
+    } else {
+        f64::INFINITY
+    };
+    let critical_difference = NEMENYI_Q_05
+        .get(number_of_models - 2)
+        .map(|q| q * (k * (k + 1.) / (6. * n)).sqrt());
+    FriedmanTest {
+        average_ranks,
+        chi_squared,
+        degrees_of_freedom,
+        p_value: chi_squared_survival(chi_squared, degrees_of_freedom),
+        f_statistic,
+        critical_difference,
+    }
+}
+
+// P(X > x) for X chi squared distributed
+pub fn chi_squared_survival(x: f64, degrees_of_freedom: usize) -> f64 {
+    if x <= 0. {
+        return 1.;
+    }
+    regularized_upper_gamma(degrees_of_freedom as f64 / 2., x / 2.)
+}
+
+// Q(a, x), by its series below a + 1 and its continued fraction above (Numerical Recipes 6.2)
+fn regularized_upper_gamma(a: f64, x: f64) -> f64 {
+    let log_prefactor = a * x.ln() - x - ln_gamma(a);
+    if x < a + 1. {
+        let mut term = 1. / a;
+        let mut sum = term;
+        let mut denominator = a;
+        for _ in 0..500 {
+            denominator += 1.;
+            term *= x / denominator;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        1. - sum * log_prefactor.exp()
+    } else {
+        let tiny = 1e-300;
+        let mut b = x + 1. - a;
+        let mut c = 1. / tiny;
+        let mut d = 1. / b;
+        let mut fraction = d;
+        for i in 1..500 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1. / d;
+            let delta = d * c;
+            fraction *= delta;
+            if (delta - 1.).abs() < 1e-15 {
+                break;
+            }
+        }
+        fraction * log_prefactor.exp()
+    }
+}
+
+// Lanczos approximation, g = 7
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1. - x);
+    }
+    let x = x - 1.;
+    let mut sum = COEFFICIENTS[0];
+    for (i, coefficient) in COEFFICIENTS.iter().enumerate().skip(1) {
+        sum += coefficient / (x + i as f64);
+    }
+    let t = x + 7.5;
+    0.5 * (2. * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() < tolerance,
+            "{} is not close to {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_mean_with_confidence_interval() {
+        let interval = mean_with_confidence_interval(&[1., 2., 3., 4.]);
+        assert_close(interval.mean, 2.5, 1e-12);
+        // 3.182 * sqrt(5 / 3 / 4)
+        assert_close(interval.half_width, 2.0540, 1e-3);
+        assert_eq!(mean_with_confidence_interval(&[3.]).half_width, 0.);
+    }
+
+    #[test]
+    fn test_rank_scores_with_ties() {
+        assert_eq!(
+            rank_scores(&[0.9, 0.7, 0.9, 0.5], true),
+            vec![1.5, 3., 1.5, 4.]
+        );
+        assert_eq!(
+            rank_scores(&[0.9, 0.7, 0.9, 0.5], false),
+            vec![3.5, 2., 3.5, 1.]
+        );
+    }
+
+    #[test]
+    fn test_chi_squared_survival() {
+        assert_close(chi_squared_survival(3.841_458_8, 1), 0.05, 1e-6);
+        assert_close(chi_squared_survival(5.991_464_5, 2), 0.05, 1e-6);
+        assert_close(chi_squared_survival(1., 10), 0.999_827_8, 1e-6);
+        assert_close(ln_gamma(5.), 24_f64.ln(), 1e-12);
+    }
+
+    #[test]
+    fn test_friedman_test() {
+        // the first model always wins, the others tie on average
+        let scores = vec![
+            vec![0.9, 0.8, 0.7],
+            vec![0.9, 0.7, 0.8],
+            vec![0.9, 0.8, 0.7],
+            vec![0.9, 0.7, 0.8],
+        ];
+        let test = friedman_test(&scores, true);
+        assert_eq!(test.average_ranks, vec![1., 2.5, 2.5]);
+        // 12 * 4 / 12 * (1 + 6.25 + 6.25 - 12)
+        assert_close(test.chi_squared, 6., 1e-12);
+        assert_eq!(test.degrees_of_freedom, 2);
+        assert_close(test.p_value, (-3_f64).exp(), 1e-9);
+        // 3 * 6 / (4 * 2 - 6)
+        assert_close(test.f_statistic, 9., 1e-12);
+        // 2.343 * sqrt(3 * 4 / 24)
+        assert_close(test.critical_difference.unwrap(), 1.6568, 1e-3);
+        assert!(test.significantly_different_pairs().is_empty());
+    }
+}
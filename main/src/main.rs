@@ -1,46 +1,650 @@
-use std::time::Instant;
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, Write},
+    process,
+    time::Instant,
+};
 
-use common::data_reader::read_csv_data_multi_target;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use common::{
+    data_reader::{
+        try_get_feature_names, try_read_csv_data_multi_target,
+        try_read_csv_data_one_hot_multi_target, try_read_csv_features,
+    },
+    data_writer::write_csv_records_to_writer,
+    datasets::MultiTargetDataSet,
+    metrics::{classification::ClassificationReport, regression::RegressionReport},
+};
 use multi_target_decision_tree::{
     decision_trees::TreeConfig,
+    printer::TreeDump,
     split_finder::{SplitFinder, SplitMetric},
 };
-use multi_target_grad_boost::boosting_ensemble::{
-    boosting_types::RegressionBoostModel, GradientBoostedEnsemble,
+use multi_target_grad_boost::{
+    benchmark::summarise_benchmark,
+    boosting_ensemble::{
+        boosting_types::{ClassWeight, MultiClassObjective},
+        learning_rate_schedule::LearningRateSchedule,
+    },
+    checkpoint::{read_checkpoint_header, CheckpointConfig},
+    experiment::{run_experiment, write_results_csv, write_results_markdown, ExperimentConfig},
+    saved_model::{BoostingOptions, Model, ModelType, SavedModel},
 };
 
-// Execute cargo run from base dir
+// Exit codes: 0 on success, 1 when a command fails (e.g unreadable data or model files),
+// 2 for invalid command line arguments
+#[derive(Parser)]
+#[command(about = "Train, predict with, evaluate and inspect multi-target trees and ensembles")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Train a model and save it as JSON
+    Train(TrainArgs),
+    /// Add trees to a saved boosting ensemble, trained on the given data
+    ContinueTraining(ContinueTrainingArgs),
+    /// Write the predictions of a saved model as CSV
+    Predict(PredictArgs),
+    /// Print regression metrics, and classification metrics for class labels
+    Evaluate(EvaluateArgs),
+    /// Print a saved model's summary, tree dump or feature importances
+    Inspect(InspectArgs),
+    /// Run every combination of an experiment file and write a results table with timings
+    Experiment(ExperimentArgs),
+    /// Run an experiment over its seeds and print means, confidence intervals and a Friedman test
+    Benchmark(BenchmarkArgs),
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ModelTypeArg {
+    Tree,
+    RegressionBoost,
+    MultiClassBoost,
+    AmgBoost,
+}
+
+impl From<ModelTypeArg> for ModelType {
+    fn from(model_type: ModelTypeArg) -> Self {
+        match model_type {
+            ModelTypeArg::Tree => ModelType::Tree,
+            ModelTypeArg::RegressionBoost => ModelType::RegressionBoost,
+            ModelTypeArg::MultiClassBoost => ModelType::MultiClassBoost,
+            ModelTypeArg::AmgBoost => ModelType::AmgBoost,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LearningRateScheduleArg {
+    Constant,
+    StepDecay,
+    Exponential,
+    Cosine,
+}
+
+#[derive(Args)]
+struct LabelledDataArgs {
+    /// Data CSV with a header
+    #[arg(long)]
+    data: String,
+    /// Label CSV with one column per target, row by row with the data
+    #[arg(long, required_unless_present = "number_of_classes")]
+    labels: Option<String>,
+    /// The last column of the data is a class in 0..number_of_classes, one-hot encoded
+    #[arg(long, conflicts_with = "labels")]
+    number_of_classes: Option<usize>,
+}
+
+#[derive(Args)]
+struct TrainArgs {
+    #[arg(long, value_enum)]
+    model_type: ModelTypeArg,
+    #[command(flatten)]
+    data: LabelledDataArgs,
+    #[arg(long, default_value_t = 3)]
+    max_levels: u32,
+    #[arg(long, default_value_t = 100)]
+    number_of_iterations: u32,
+    /// The first learning rate of the schedule
+    #[arg(long, default_value_t = 0.1)]
+    learning_rate: f64,
+    #[arg(long, value_enum, default_value_t = LearningRateScheduleArg::Constant)]
+    learning_rate_schedule: LearningRateScheduleArg,
+    /// What step-decay and exponential schedules multiply the learning rate by
+    #[arg(long, required_if_eq_any = [
+        ("learning_rate_schedule", "step-decay"),
+        ("learning_rate_schedule", "exponential"),
+    ])]
+    decay: Option<f64>,
+    /// Iterations between the decays of a step-decay schedule
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    decay_every: u32,
+    /// The learning rate a cosine schedule ends at
+    #[arg(long, default_value_t = 0.)]
+    minimum_learning_rate: f64,
+    /// Raise the learning rate linearly over this many iterations before the schedule starts
+    #[arg(long, default_value_t = 0)]
+    warmup_iterations: u32,
+    /// Comma separated factors, one per target, the learning rate is multiplied by,
+    /// regression-boost only
+    #[arg(long, value_delimiter = ',')]
+    target_shrinkage: Option<Vec<f64>>,
+    #[arg(long)]
+    multi_threading: bool,
+    /// "balanced" or one comma separated weight per class, multiclass models only
+    #[arg(long, value_parser = parse_class_weight)]
+    class_weight: Option<ClassWeight>,
+    /// Train on the focal loss with this gamma, multiclass models only
+    #[arg(long)]
+    focal_gamma: Option<f64>,
+    /// Write a checkpoint of a boosting ensemble to this directory every --checkpoint-every
+    /// iterations
+    #[arg(long)]
+    checkpoint_directory: Option<String>,
+    #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+    checkpoint_every: u32,
+    /// Finish the run of a checkpoint file, with the data and options it was started with
+    #[arg(long, conflicts_with_all = [
+        "class_weight",
+        "focal_gamma",
+        "learning_rate_schedule",
+        "warmup_iterations",
+        "target_shrinkage",
+    ])]
+    resume_from: Option<String>,
+    #[arg(long)]
+    output: String,
+}
+
+fn learning_rate_schedule(args: &TrainArgs) -> LearningRateSchedule {
+    let initial_rate = args.learning_rate;
+    let schedule = match args.learning_rate_schedule {
+        LearningRateScheduleArg::Constant => LearningRateSchedule::Constant(initial_rate),
+        LearningRateScheduleArg::StepDecay => LearningRateSchedule::StepDecay {
+            initial_rate,
+            factor: args.decay.unwrap(),
+            step_size: args.decay_every,
+        },
+        LearningRateScheduleArg::Exponential => LearningRateSchedule::Exponential {
+            initial_rate,
+            decay: args.decay.unwrap(),
+        },
+        LearningRateScheduleArg::Cosine => LearningRateSchedule::Cosine {
+            initial_rate,
+            minimum_rate: args.minimum_learning_rate,
+        },
+    };
+    if args.warmup_iterations == 0 {
+        return schedule;
+    }
+    LearningRateSchedule::Warmup {
+        warmup_iterations: args.warmup_iterations,
+        schedule: Box::new(schedule),
+    }
+}
+
+fn parse_class_weight(value: &str) -> Result<ClassWeight, String> {
+    if value == "balanced" {
+        return Ok(ClassWeight::Balanced);
+    }
+    let class_weights = value
+        .split(',')
+        .map(|weight| weight.trim().parse::<f64>())
+        .collect::<Result<Vec<f64>, _>>()
+        .map_err(|_| {
+            format!(
+                "expected \"balanced\" or comma separated numbers, got {}",
+                value
+            )
+        })?;
+    if class_weights
+        .iter()
+        .any(|weight| !weight.is_finite() || *weight < 0.)
+    {
+        return Err("class weights must be finite and non-negative".to_string());
+    }
+    Ok(ClassWeight::Explicit(class_weights))
+}
+
+#[derive(Args)]
+struct ContinueTrainingArgs {
+    #[arg(long)]
+    model: String,
+    #[command(flatten)]
+    data: LabelledDataArgs,
+    /// Of the new trees
+    #[arg(long, default_value_t = 3)]
+    max_levels: u32,
+    /// The number of trees to add
+    #[arg(long)]
+    number_of_iterations: u32,
+    /// Of the new trees, defaults to that of the model's last tree
+    #[arg(long)]
+    learning_rate: Option<f64>,
+    #[arg(long)]
+    multi_threading: bool,
+    /// Defaults to overwriting the model
+    #[arg(long)]
+    output: Option<String>,
+}
+
+#[derive(Args)]
+struct PredictArgs {
+    #[arg(long)]
+    model: String,
+    /// Features only, unless --with-class-column is set
+    #[arg(long)]
+    data: String,
+    /// Ignore the last column of the data, e.g to predict for a labelled data set
+    #[arg(long)]
+    with_class_column: bool,
+    /// Defaults to stdout
+    #[arg(long)]
+    output: Option<String>,
+}
+
+#[derive(Args)]
+struct EvaluateArgs {
+    #[arg(long)]
+    model: String,
+    #[command(flatten)]
+    data: LabelledDataArgs,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DumpFormat {
+    Text,
+    Json,
+}
+
+#[derive(Args)]
+struct InspectArgs {
+    #[arg(long)]
+    model: String,
+    /// Write every tree instead of the summary
+    #[arg(long, value_enum)]
+    dump: Option<DumpFormat>,
+    /// Print the normalised total gain of each feature instead of the summary
+    #[arg(long, conflicts_with = "dump")]
+    importance: bool,
+}
+
+#[derive(Args)]
+struct ExperimentArgs {
+    /// Experiment TOML, or JSON with a .json extension
+    #[arg(long)]
+    config: String,
+    /// Results CSV, a markdown table is printed when not set
+    #[arg(long)]
+    output: Option<String>,
+}
+
+#[derive(Args)]
+struct BenchmarkArgs {
+    /// Experiment file, see experiments/benchmark_classification.toml
+    #[arg(long)]
+    config: String,
+    /// Also write every run as CSV
+    #[arg(long)]
+    results: Option<String>,
+}
+
 fn main() {
-    let true_data = read_csv_data_multi_target(
-        "./common/data-files/multi-target/features_train_mt.csv",
-        "./common/data-files/multi-target/labels_train_mt.csv",
-    );
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Train(args) => train(args),
+        Command::ContinueTraining(args) => continue_training(args),
+        Command::Predict(args) => predict(args),
+        Command::Evaluate(args) => evaluate(args),
+        Command::Inspect(args) => inspect(args),
+        Command::Experiment(args) => experiment(args),
+        Command::Benchmark(args) => benchmark(args),
+    };
+    if let Err(error) = result {
+        eprintln!("error: {}", error);
+        process::exit(1);
+    }
+}
 
-    let split_finder = SplitFinder::new(SplitMetric::Variance);
-    println!("{:?}", true_data.labels[10]);
+fn train(args: TrainArgs) -> Result<(), Box<dyn Error>> {
+    let LabelledData {
+        data_set,
+        feature_names,
+        target_names,
+    } = read_labelled_data(&args.data)?;
+    let tree_config = TreeConfig {
+        split_finder: SplitFinder::new(SplitMetric::Variance),
+        use_multi_threading: args.multi_threading,
+        number_of_classes: data_set.number_of_targets() as u32,
+        max_levels: args.max_levels,
+    };
+
+    let model_type: ModelType = args.model_type.into();
+    let learning_rate_schedule = learning_rate_schedule(&args);
+    learning_rate_schedule.validate()?;
+    let objective = MultiClassObjective {
+        class_weight: args.class_weight.unwrap_or(ClassWeight::Uniform),
+        focal_gamma: args.focal_gamma,
+    };
+    if objective != MultiClassObjective::default() {
+        if !matches!(model_type, ModelType::MultiClassBoost | ModelType::AmgBoost) {
+            return Err("class weights and the focal loss need a multiclass model".into());
+        }
+        if let ClassWeight::Explicit(class_weights) = &objective.class_weight {
+            if class_weights.len() != data_set.number_of_targets() {
+                return Err(format!(
+                    "{} class weights for {} classes",
+                    class_weights.len(),
+                    data_set.number_of_targets()
+                )
+                .into());
+            }
+        }
+        if objective
+            .focal_gamma
+            .is_some_and(|focal_gamma| focal_gamma < 0.)
+        {
+            return Err("the focal gamma must be non-negative".into());
+        }
+    }
+
+    if let Some(target_shrinkage) = &args.target_shrinkage {
+        if model_type != ModelType::RegressionBoost {
+            return Err("the target shrinkage needs a regression-boost model".into());
+        }
+        if target_shrinkage.len() != data_set.number_of_targets() {
+            return Err(format!(
+                "{} target shrinkage factors for {} targets",
+                target_shrinkage.len(),
+                data_set.number_of_targets()
+            )
+            .into());
+        }
+        if target_shrinkage
+            .iter()
+            .any(|shrinkage| !shrinkage.is_finite() || *shrinkage < 0.)
+        {
+            return Err("target shrinkage factors must be finite and non-negative".into());
+        }
+    }
+    let options = BoostingOptions {
+        number_of_iterations: args.number_of_iterations,
+        learning_rate_schedule,
+        objective: objective.clone(),
+        target_shrinkage: args.target_shrinkage,
+    };
+
+    let checkpoint_every = args.checkpoint_every;
+    let checkpoint_config =
+        args.checkpoint_directory
+            .map(|checkpoint_directory| CheckpointConfig {
+                directory: checkpoint_directory,
+                every_n_iterations: checkpoint_every,
+            });
 
+    // a resumed run keeps the objective of its checkpoint
+    let objective = match &args.resume_from {
+        Some(checkpoint_path) => read_checkpoint_header(checkpoint_path)?
+            .objective
+            .unwrap_or_default(),
+        None => objective,
+    };
+
+    let before = Instant::now();
+    let model = match (&args.resume_from, &checkpoint_config) {
+        (Some(checkpoint_path), _) => Model::resume_from(
+            model_type,
+            checkpoint_path,
+            data_set,
+            tree_config,
+            checkpoint_config.as_ref(),
+        )?,
+        (None, Some(checkpoint_config)) => Model::train_with_checkpoints(
+            model_type,
+            data_set,
+            tree_config,
+            options,
+            checkpoint_config,
+        )?,
+        (None, None) => Model::train_with_options(model_type, data_set, tree_config, options),
+    };
+    eprintln!(
+        "Trained {} with {} trees in {:.2?}",
+        model.type_name(),
+        model.number_of_trees(),
+        before.elapsed()
+    );
+    SavedModel::new(model, feature_names, target_names)
+        .with_objective(objective)
+        .save(&args.output)
+}
+
+fn continue_training(args: ContinueTrainingArgs) -> Result<(), Box<dyn Error>> {
+    let mut saved_model = SavedModel::load(&args.model)?;
+    let data_set = read_labelled_data(&args.data)?.data_set;
+    check_number_of_features(&saved_model, &data_set.feature_rows)?;
+    check_number_of_targets(&saved_model, &data_set)?;
+    if args
+        .learning_rate
+        .is_some_and(|learning_rate| learning_rate <= 0.)
+    {
+        return Err("the learning rate must be positive".into());
+    }
     let tree_config = TreeConfig {
-        split_finder,
-        use_multi_threading: true,
-        number_of_classes: 10,
-        max_levels: 3,
+        split_finder: SplitFinder::new(SplitMetric::Variance),
+        use_multi_threading: args.multi_threading,
+        number_of_classes: data_set.number_of_targets() as u32,
+        max_levels: args.max_levels,
     };
 
     let before = Instant::now();
-    let grad_boost_ensemble = RegressionBoostModel::train(true_data, tree_config, 300, 0.1);
-    println!("Elapsed time: {:.2?}", before.elapsed());
+    let number_of_trees = saved_model.model.number_of_trees();
+    saved_model.model.continue_training(
+        data_set,
+        tree_config,
+        args.number_of_iterations,
+        args.learning_rate,
+        saved_model.objective.clone(),
+    )?;
+    eprintln!(
+        "Added {} trees to {} in {:.2?}",
+        saved_model.model.number_of_trees() - number_of_trees,
+        saved_model.model.type_name(),
+        before.elapsed()
+    );
+    saved_model.save(args.output.as_ref().unwrap_or(&args.model))
+}
+
+fn predict(args: PredictArgs) -> Result<(), Box<dyn Error>> {
+    let saved_model = SavedModel::load(&args.model)?;
+    let mut feature_rows = try_read_csv_features(&args.data)?;
+    if args.with_class_column {
+        feature_rows.iter_mut().for_each(|feature_row| {
+            feature_row.pop();
+        });
+    }
+    check_number_of_features(&saved_model, &feature_rows)?;
+
+    let predictions = saved_model.model.predict_batch(&feature_rows);
+    let records: Vec<Vec<String>> = predictions
+        .iter()
+        .map(|prediction| prediction.iter().map(|value| value.to_string()).collect())
+        .collect();
+    match &args.output {
+        Some(output) => {
+            let file = File::create(output)
+                .map_err(|error| format!("Failed to create {}: {}", output, error))?;
+            write_csv_records_to_writer(file, &saved_model.target_names, &records)
+        }
+        None => write_csv_records_to_writer(io::stdout(), &saved_model.target_names, &records),
+    }
+}
 
-    let test_set = read_csv_data_multi_target(
-        "./common/data-files/multi-target/features_test_mt.csv",
-        "./common/data-files/multi-target/labels_test_mt.csv",
+fn evaluate(args: EvaluateArgs) -> Result<(), Box<dyn Error>> {
+    let saved_model = SavedModel::load(&args.model)?;
+    let data_set = read_labelled_data(&args.data)?.data_set;
+    check_number_of_features(&saved_model, &data_set.feature_rows)?;
+    check_number_of_targets(&saved_model, &data_set)?;
+
+    let predictions = saved_model.model.predict_batch(&data_set.feature_rows);
+    let target_names = &saved_model.target_names;
+    println!(
+        "{}",
+        RegressionReport::new_weighted(&predictions, &data_set.labels, data_set.weights())
+            .to_string(target_names)
     );
+    if saved_model.model.is_classifier() || args.data.number_of_classes.is_some() {
+        println!();
+        println!(
+            "{}",
+            ClassificationReport::new_weighted(&predictions, &data_set.labels, data_set.weights())
+                .to_string(target_names)
+        );
+    }
+    Ok(())
+}
+
+fn inspect(args: InspectArgs) -> Result<(), Box<dyn Error>> {
+    let saved_model = SavedModel::load(&args.model)?;
+    let model = &saved_model.model;
+    let tree_dump = TreeDump {
+        feature_names: &saved_model.feature_names,
+        target_names: &saved_model.target_names,
+    };
+    let mut stdout = io::stdout();
+    match args.dump {
+        Some(DumpFormat::Text) => model.write_text(&mut stdout, &tree_dump)?,
+        Some(DumpFormat::Json) => model.write_json(&mut stdout, &tree_dump)?,
+        None if args.importance => {
+            let feature_importance = model.feature_importance(saved_model.feature_names.len());
+            let normalised_total_gain = feature_importance.normalised_total_gain();
+            let mut features: Vec<usize> = (0..normalised_total_gain.len()).collect();
+            features.sort_by(|first, second| {
+                normalised_total_gain[*second]
+                    .partial_cmp(&normalised_total_gain[*first])
+                    .unwrap()
+            });
+            writeln!(stdout, "feature,normalised_total_gain,split_count")?;
+            for feature in features {
+                writeln!(
+                    stdout,
+                    "{},{:.4},{}",
+                    saved_model.feature_names[feature],
+                    normalised_total_gain[feature],
+                    feature_importance.split_count[feature]
+                )?;
+            }
+        }
+        None => {
+            writeln!(stdout, "model type: {}", model.type_name())?;
+            writeln!(stdout, "trees: {}", model.number_of_trees())?;
+            writeln!(stdout, "features: {}", saved_model.feature_names.join(", "))?;
+            writeln!(stdout, "targets: {}", saved_model.target_names.join(", "))?;
+        }
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+fn experiment(args: ExperimentArgs) -> Result<(), Box<dyn Error>> {
+    let config = ExperimentConfig::from_file(&args.config)?;
+    let before = Instant::now();
+    let results = run_experiment(&config)?;
+    eprintln!("Ran {} runs in {:.2?}", results.len(), before.elapsed());
+    match &args.output {
+        Some(output) => {
+            let file = File::create(output)
+                .map_err(|error| format!("Failed to create {}: {}", output, error))?;
+            write_results_csv(file, &config.metrics, &results)
+        }
+        None => write_results_markdown(io::stdout(), &config.metrics, &results),
+    }
+}
+
+fn benchmark(args: BenchmarkArgs) -> Result<(), Box<dyn Error>> {
+    let config = ExperimentConfig::from_file(&args.config)?;
+    let before = Instant::now();
+    let results = run_experiment(&config)?;
+    eprintln!("Ran {} runs in {:.2?}", results.len(), before.elapsed());
+    if let Some(output) = &args.results {
+        let file = File::create(output)
+            .map_err(|error| format!("Failed to create {}: {}", output, error))?;
+        write_results_csv(file, &config.metrics, &results)?;
+    }
+    summarise_benchmark(&config.metrics, &results).write_markdown(io::stdout())
+}
 
-    let mean_squared_error = grad_boost_ensemble.calculate_score(&test_set);
-    let root_mean_squared_error = f64::sqrt(mean_squared_error);
-    println!("{:?}", mean_squared_error);
-    println!("{:?}", root_mean_squared_error);
+// Names are taken from the CSV headers, or are class_{index} for class labels
+struct LabelledData {
+    data_set: MultiTargetDataSet,
+    feature_names: Vec<String>,
+    target_names: Vec<String>,
+}
+
+fn read_labelled_data(args: &LabelledDataArgs) -> Result<LabelledData, Box<dyn Error>> {
+    let labelled_data = read_labelled_data_with_names(args)?;
+    if labelled_data.data_set.number_of_rows() == 0 {
+        return Err(format!("{} has no data rows", args.data).into());
+    }
+    Ok(labelled_data)
+}
+
+fn read_labelled_data_with_names(args: &LabelledDataArgs) -> Result<LabelledData, Box<dyn Error>> {
+    let mut feature_names = try_get_feature_names(&args.data)?;
+    match (&args.labels, args.number_of_classes) {
+        (Some(labels), _) => Ok(LabelledData {
+            data_set: try_read_csv_data_multi_target(&args.data, labels)?,
+            feature_names,
+            target_names: try_get_feature_names(labels)?,
+        }),
+        (None, Some(number_of_classes)) => {
+            let data_set = try_read_csv_data_one_hot_multi_target(&args.data, number_of_classes)?;
+            // the last column holds the class
+            feature_names.pop();
+            let target_names = (0..number_of_classes)
+                .map(|class| format!("class_{}", class))
+                .collect();
+            Ok(LabelledData {
+                data_set,
+                feature_names,
+                target_names,
+            })
+        }
+        (None, None) => Err("either --labels or --number-of-classes is required".into()),
+    }
+}
+
+fn check_number_of_targets(
+    saved_model: &SavedModel,
+    data_set: &MultiTargetDataSet,
+) -> Result<(), Box<dyn Error>> {
+    if data_set.number_of_targets() != saved_model.target_names.len() {
+        return Err(format!(
+            "the data has {} targets but the model was trained with {}",
+            data_set.number_of_targets(),
+            saved_model.target_names.len()
+        )
+        .into());
+    }
+    Ok(())
+}
 
-    let prediction = grad_boost_ensemble.predict(&test_set.feature_rows[0]);
-    println!("{:?}", test_set.labels[0]);
-    println!("{:?}", prediction);
+fn check_number_of_features(
+    saved_model: &SavedModel,
+    feature_rows: &[Vec<f64>],
+) -> Result<(), Box<dyn Error>> {
+    let number_of_features = feature_rows.first().map_or(0, |row| row.len());
+    if number_of_features != saved_model.feature_names.len() {
+        return Err(format!(
+            "the data has {} features but the model was trained with {}",
+            number_of_features,
+            saved_model.feature_names.len()
+        )
+        .into());
+    }
+    Ok(())
 }
@@ -29,7 +29,7 @@ fn bench_partition_new(c: &mut Criterion) {
         label_refs.push(*label);
     }
     c.bench_function("new partitioning", |b| {
-        b.iter(|| data_processor::new_partition(&processed_dataset, 0, 1., &label_refs))
+        b.iter(|| data_processor::new_partition(&processed_dataset, 0, 1., &label_refs, None))
     });
 }
 
@@ -7,15 +7,27 @@ mod class_counter;
 pub mod data_partitioner;
 #[path = "tree/decision_trees.rs"]
 pub mod decision_trees;
+#[path = "tree/dot_export.rs"]
+pub mod dot_export;
+#[path = "tree/feature_importance.rs"]
+pub mod feature_importance;
 #[path = "tree/grad_boost_decision_trees.rs"]
 pub mod grad_boost_decision_trees;
 #[path = "tree/leaf.rs"]
 pub mod leaf;
+#[path = "utils/leaf_encoder.rs"]
+pub mod leaf_encoder;
 #[path = "tree/node.rs"]
 pub mod node;
 #[path = "tree/tree_print.rs"]
 pub mod printer;
+#[path = "tree/rule_extraction.rs"]
+pub mod rule_extraction;
 #[path = "utils/scorer.rs"]
 pub mod scorer;
 #[path = "split_finders/split_finder.rs"]
 pub mod split_finder;
+#[path = "tree/tree_shap.rs"]
+pub mod tree_shap;
+#[path = "tree/tree_traverse.rs"]
+pub mod tree_traverse;
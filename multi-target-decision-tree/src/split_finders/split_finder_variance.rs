@@ -10,17 +10,18 @@ use common::{
 use super::*;
 use crate::calculations::*;
 
+// Variances and the loss are weighted by the data's weights, if any
 pub fn find_best_split(data: &MultiTargetDataSet, number_of_targets: u32) -> BestSplitResult {
     let mut best_gain = 0.0;
     let mut best_question = Question::new(0, 0.);
-    let number_of_labels = data.labels.len();
+    let total_weight = data.total_weight();
     let number_of_targets = number_of_targets as usize;
 
     let total_multi_target_label_metrics =
-        get_multi_target_label_metrics(&data.labels, number_of_targets);
+        get_weighted_multi_target_label_metrics(&data.labels, data.weights(), number_of_targets);
     let total_variance_sum = get_total_variance_sum(
         &total_multi_target_label_metrics,
-        number_of_labels as f64,
+        total_weight,
         number_of_targets,
     );
 
@@ -31,8 +32,9 @@ pub fn find_best_split(data: &MultiTargetDataSet, number_of_targets: u32) -> Bes
         .par_iter()
         .map(|feature_column| {
             threshold_finder_variance::determine_best_threshold(
-                number_of_labels,
+                total_weight,
                 &data.labels,
+                data.weights(),
                 feature_column,
                 &total_multi_target_label_metrics,
                 number_of_targets,
@@ -61,19 +63,21 @@ pub fn find_best_split(data: &MultiTargetDataSet, number_of_targets: u32) -> Bes
 pub(crate) fn find_best_split_new_partition(
     data: &MultiTargetDataSetSortedFeatures,
     all_labels: &Vec<&Vec<f64>>,
+    all_weights: Option<&[f64]>,
     number_of_targets: u32,
     number_of_cols: usize,
 ) -> BestSplitResult {
     let mut best_gain = 0.0;
     let mut best_question = Question::new(0, 0.);
-    let number_of_labels_in_subset = data.labels.len();
+    let subset_weights = data.weights.as_deref();
+    let total_weight_of_subset = calculate_total_weight(subset_weights, data.labels.len());
     let number_of_targets = number_of_targets as usize;
 
     let total_multi_target_label_metrics =
-        get_multi_target_label_metrics_new_partition(&data.labels, number_of_targets);
+        get_weighted_multi_target_label_metrics(&data.labels, subset_weights, number_of_targets);
     let total_variance_sum = get_total_variance_sum(
         &total_multi_target_label_metrics,
-        number_of_labels_in_subset as f64,
+        total_weight_of_subset,
         number_of_targets,
     );
 
@@ -83,8 +87,9 @@ pub(crate) fn find_best_split_new_partition(
         .par_iter()
         .map(|feature_column| {
             threshold_finder_variance::determine_best_threshold_new(
-                number_of_labels_in_subset,
+                total_weight_of_subset,
                 all_labels,
+                all_weights,
                 feature_column,
                 &total_multi_target_label_metrics,
                 number_of_targets,
@@ -126,8 +131,7 @@ fn get_total_variance_sum(
 #[cfg(test)]
 mod tests {
     use common::{
-        data_processor,
-        data_reader::{create_feature_columns, read_csv_data_one_hot_multi_target},
+        data_processor, data_reader::read_csv_data_one_hot_multi_target,
         datasets::MultiTargetDataSet,
     };
 
@@ -136,12 +140,7 @@ mod tests {
         let features = vec![vec![10., 2., 0.], vec![6., 2., 0.], vec![1., 2., 1.]];
         let labels = vec![vec![1., 0.], vec![1., 0.], vec![1., 1.]];
 
-        let columns = create_feature_columns(&features);
-        let data = MultiTargetDataSet {
-            feature_rows: features,
-            feature_columns: columns,
-            labels,
-        };
+        let data = MultiTargetDataSet::from_rows(features, labels);
 
         let result = super::find_best_split(&data, 2);
         println!("{:?}", result);
@@ -157,19 +156,14 @@ mod tests {
         let label2 = labels[2].clone();
         let all_labels_refs = vec![&label0, &label1, &label2];
 
-        let columns = create_feature_columns(&features);
-        let number_of_cols = columns.len();
-        let data = MultiTargetDataSet {
-            feature_rows: features,
-            feature_columns: columns,
-            labels,
-        };
+        let data = MultiTargetDataSet::from_rows(features, labels);
+        let number_of_cols = data.number_of_features();
 
-        let data_with_sorted_features =
-            data_processor::create_dataset_with_sorted_features(&data);
+        let data_with_sorted_features = data_processor::create_dataset_with_sorted_features(&data);
         let result = super::find_best_split_new_partition(
             &data_with_sorted_features,
             &all_labels_refs,
+            None,
             2,
             number_of_cols,
         );
@@ -184,4 +178,16 @@ mod tests {
         assert_eq!(result.question.column, 2);
         assert_eq!(result.question.value, 30.);
     }
+
+    #[test]
+    fn test_find_best_split_follows_the_weights() {
+        let features = vec![vec![1.], vec![2.], vec![3.], vec![4.]];
+        let labels = vec![vec![0.], vec![0.], vec![1.], vec![3.]];
+        let data = MultiTargetDataSet::from_rows(features, labels);
+        assert_eq!(super::find_best_split(&data, 1).question.value, 4.);
+
+        // the outlier no longer counts, separating the zeros is best
+        let weighted = data.with_weights(vec![1., 1., 1., 0.]);
+        assert_eq!(super::find_best_split(&weighted, 1).question.value, 3.);
+    }
 }
@@ -4,14 +4,17 @@ use crate::calculations::MultiTargetLabelMetrics;
 use crate::calculations::{calculate_loss_vector, calculate_variance_vector};
 use common::feature_sorter::get_sorted_feature_tuple_vector;
 
+// number_of_labels is the total weight of the labels on the tracker's side of the threshold
 struct VarianceValueTrackerMultiTarget {
     number_of_labels: f64,
     multi_target_label_metrics: MultiTargetLabelMetrics,
 }
 
+// total_weight is the number of labels when there are no weights
 pub(super) fn determine_best_threshold(
-    number_of_labels: usize,
+    total_weight: f64,
     labels: &Vec<Vec<f64>>,
+    weights: Option<&[f64]>,
     feature_column: &[f64],
     total_multi_target_label_metrics: &MultiTargetLabelMetrics,
     number_of_targets: usize,
@@ -31,7 +34,7 @@ pub(super) fn determine_best_threshold(
     };
 
     let mut right_value_tracker = VarianceValueTrackerMultiTarget {
-        number_of_labels: number_of_labels as f64,
+        number_of_labels: total_weight,
         multi_target_label_metrics: MultiTargetLabelMetrics {
             sum_of_squared_labels_vector: total_multi_target_label_metrics
                 .sum_of_squared_labels_vector
@@ -80,15 +83,28 @@ pub(super) fn determine_best_threshold(
 
         let real_row_index = tuple.1;
         let label_vector = &labels[real_row_index];
-        update_left_value_tracker(&mut left_value_tracker, label_vector, number_of_targets);
-        update_right_value_tracker(&mut right_value_tracker, label_vector, number_of_targets);
+        let weight = weights.map_or(1., |weights| weights[real_row_index]);
+        update_left_value_tracker(
+            &mut left_value_tracker,
+            label_vector,
+            weight,
+            number_of_targets,
+        );
+        update_right_value_tracker(
+            &mut right_value_tracker,
+            label_vector,
+            weight,
+            number_of_targets,
+        );
     });
     best_result_container
 }
 
+// all_labels and all_weights are indexed by the row indices in feature_column
 pub(super) fn determine_best_threshold_new(
-    number_of_labels_in_subset: usize,
+    total_weight_of_subset: f64,
     all_labels: &Vec<&Vec<f64>>,
+    all_weights: Option<&[f64]>,
     feature_column: &[(f64, usize)],
     total_multi_target_label_metrics: &MultiTargetLabelMetrics,
     number_of_targets: usize,
@@ -108,7 +124,7 @@ pub(super) fn determine_best_threshold_new(
     };
 
     let mut right_value_tracker = VarianceValueTrackerMultiTarget {
-        number_of_labels: number_of_labels_in_subset as f64,
+        number_of_labels: total_weight_of_subset,
         multi_target_label_metrics: MultiTargetLabelMetrics {
             sum_of_squared_labels_vector: total_multi_target_label_metrics
                 .sum_of_squared_labels_vector
@@ -157,8 +173,19 @@ pub(super) fn determine_best_threshold_new(
 
         let real_row_index = tuple.1;
         let label_vector = &all_labels[real_row_index];
-        update_left_value_tracker(&mut left_value_tracker, label_vector, number_of_targets);
-        update_right_value_tracker(&mut right_value_tracker, label_vector, number_of_targets);
+        let weight = all_weights.map_or(1., |weights| weights[real_row_index]);
+        update_left_value_tracker(
+            &mut left_value_tracker,
+            label_vector,
+            weight,
+            number_of_targets,
+        );
+        update_right_value_tracker(
+            &mut right_value_tracker,
+            label_vector,
+            weight,
+            number_of_targets,
+        );
     });
     best_result_container
 }
@@ -166,14 +193,16 @@ pub(super) fn determine_best_threshold_new(
 fn update_left_value_tracker(
     left_value_tracker: &mut VarianceValueTrackerMultiTarget,
     label_vector: &[f64],
+    weight: f64,
     number_of_targets: usize,
 ) {
-    left_value_tracker.number_of_labels += 1.0;
+    left_value_tracker.number_of_labels += weight;
     let left_multi_label_metrics = &mut left_value_tracker.multi_target_label_metrics;
     for i in 0..number_of_targets {
         let label_value = label_vector[i];
-        left_multi_label_metrics.sum_of_squared_labels_vector[i] += label_value * label_value;
-        left_multi_label_metrics.sum_of_labels_vector[i] += label_value;
+        left_multi_label_metrics.sum_of_squared_labels_vector[i] +=
+            weight * label_value * label_value;
+        left_multi_label_metrics.sum_of_labels_vector[i] += weight * label_value;
         left_multi_label_metrics.mean_of_labels_vector[i] =
             left_multi_label_metrics.sum_of_labels_vector[i] / left_value_tracker.number_of_labels
     }
@@ -182,44 +211,39 @@ fn update_left_value_tracker(
 fn update_right_value_tracker(
     right_value_tracker: &mut VarianceValueTrackerMultiTarget,
     label_vector: &[f64],
+    weight: f64,
     number_of_targets: usize,
 ) {
-    right_value_tracker.number_of_labels -= 1.0;
+    right_value_tracker.number_of_labels -= weight;
     let right_multi_label_metrics = &mut right_value_tracker.multi_target_label_metrics;
 
     for i in 0..number_of_targets {
         let label_value = label_vector[i];
-        right_multi_label_metrics.sum_of_squared_labels_vector[i] -= label_value * label_value;
-        right_multi_label_metrics.sum_of_labels_vector[i] -= label_value;
+        right_multi_label_metrics.sum_of_squared_labels_vector[i] -=
+            weight * label_value * label_value;
+        right_multi_label_metrics.sum_of_labels_vector[i] -= weight * label_value;
         right_multi_label_metrics.mean_of_labels_vector[i] =
             right_multi_label_metrics.sum_of_labels_vector[i] / right_value_tracker.number_of_labels
     }
 }
 
 mod tests {
-    use common::{
-        data_reader::{create_feature_columns, read_csv_data_one_hot_multi_target},
-        datasets::MultiTargetDataSet,
-    };
+    use common::{data_reader::read_csv_data_one_hot_multi_target, datasets::MultiTargetDataSet};
 
-    use crate::calculations::get_multi_target_label_metrics;
+    use crate::calculations::get_weighted_multi_target_label_metrics;
 
     #[test]
     fn test_best_threshold_for_particular_feature() {
         let features = vec![vec![10., 2., 0.], vec![6., 2., 0.], vec![1., 2., 1.]];
         let labels = vec![vec![1., 0.], vec![1., 0.], vec![0., 1.]];
-        let total_mt_label_metrics = get_multi_target_label_metrics(&labels, 2);
-        let columns = create_feature_columns(&features);
-        let data = MultiTargetDataSet {
-            feature_rows: features,
-            feature_columns: columns,
-            labels,
-        };
+        let total_mt_label_metrics = get_weighted_multi_target_label_metrics(&labels, None, 2);
+        let data = MultiTargetDataSet::from_rows(features, labels);
         let column = 0;
         let number_labels = data.labels.len();
         let best = super::determine_best_threshold(
-            number_labels,
+            number_labels as f64,
             &data.labels,
+            None,
             &data.feature_columns[column],
             &total_mt_label_metrics,
             2,
@@ -233,11 +257,12 @@ mod tests {
     fn test_best_threshold_for_particular_feature_in_iris() {
         let iris = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
         let column = 2;
-        let total_mt_label_metrics = get_multi_target_label_metrics(&iris.labels, 3);
+        let total_mt_label_metrics = get_weighted_multi_target_label_metrics(&iris.labels, None, 3);
         let number_labels = iris.labels.len();
         let best = super::determine_best_threshold(
-            number_labels,
+            number_labels as f64,
             &iris.labels,
+            None,
             &iris.feature_columns[column],
             &total_mt_label_metrics,
             2,
@@ -245,4 +270,33 @@ mod tests {
         println!("{:?}", best);
         assert_eq!(best.threshold_value, 30.0);
     }
+
+    #[test]
+    fn test_zero_weight_removes_a_row_from_the_loss() {
+        let feature_column = vec![1., 2., 3., 4.];
+        let labels = vec![vec![0.], vec![5.], vec![1.], vec![1.]];
+        let total_mt_label_metrics = get_weighted_multi_target_label_metrics(&labels, None, 1);
+        let unweighted = super::determine_best_threshold(
+            4.,
+            &labels,
+            None,
+            &feature_column,
+            &total_mt_label_metrics,
+            1,
+        );
+        assert!(unweighted.loss > 0.);
+
+        let weights = [1., 0., 1., 1.];
+        let total_mt_label_metrics =
+            get_weighted_multi_target_label_metrics(&labels, Some(&weights), 1);
+        let weighted = super::determine_best_threshold(
+            3.,
+            &labels,
+            Some(&weights),
+            &feature_column,
+            &total_mt_label_metrics,
+            1,
+        );
+        assert_eq!(weighted.loss, 0.);
+    }
 }
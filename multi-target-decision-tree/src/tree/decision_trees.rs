@@ -1,12 +1,24 @@
-use common::datasets::{MultiTargetDataSet, MultiTargetDataSetSortedFeatures};
+use common::{
+    datasets::{MultiTargetDataSet, MultiTargetDataSetSortedFeatures},
+    metrics::regression::RegressionReport,
+};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 
 #[path = "tree_builders/regression_tree_builder.rs"]
 mod regression_tree_builder;
 
 use crate::{
+    calculations::calculate_weighted_mean_vector_of_labels,
     leaf::{RegressionLeaf, RegressionLeafNewPartition},
     node::TreeNode,
     split_finder::SplitFinder,
+    tree_shap::{
+        calculate_shap_interaction_values, calculate_shap_values, ShapInteractionValues, ShapValues,
+    },
+    tree_traverse::{
+        find_decision_path, find_leaf_node_for_data, find_leaf_node_id_for_data, DecisionPath,
+    },
 };
 
 #[derive(Copy, Clone)]
@@ -19,6 +31,7 @@ pub struct TreeConfig {
 
 // Multi target decision tree where each label vector, and each label-vector
 // is of the form e.g [1.90, 2.56, 828.1, 0.2828], i.e label vectors contain floating numbers (and also discrete labellings)
+#[derive(Serialize, Deserialize)]
 pub struct RegressionMultiTargetDecisionTree {
     pub root: TreeNode<RegressionLeaf>,
 }
@@ -29,29 +42,78 @@ pub struct RegressionMultiTargetDecisionTreeNewPartition<'a> {
 
 impl RegressionMultiTargetDecisionTree {
     pub fn new(data: MultiTargetDataSet, tree_config: TreeConfig) -> Self {
-        Self {
-            root: match tree_config.use_multi_threading {
-                true => regression_tree_builder::build_regression_tree_using_multiple_threads(
-                    data,
-                    tree_config,
-                    0,
-                ),
-                false => regression_tree_builder::build_regression_tree(data, tree_config, 0),
-            },
-        }
+        let mut root = match tree_config.use_multi_threading {
+            true => regression_tree_builder::build_regression_tree_using_multiple_threads(
+                data,
+                tree_config,
+                0,
+            ),
+            false => regression_tree_builder::build_regression_tree(data, tree_config, 0),
+        };
+        root.assign_node_ids();
+        Self { root }
+    }
+
+    // Prediction is the average label vector of the leaf the row falls into
+    pub fn predict(&self, feature_row: &[f64]) -> Vec<f64> {
+        average_label_of_leaf(find_leaf_node_for_data(feature_row, &self.root))
+    }
+
+    pub fn predict_batch(&self, feature_rows: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        feature_rows
+            .par_iter()
+            .map(|feature_row| self.predict(feature_row))
+            .collect()
+    }
+
+    pub fn regression_report(&self, test_set: &MultiTargetDataSet) -> RegressionReport {
+        RegressionReport::new_weighted(
+            &self.predict_batch(&test_set.feature_rows),
+            &test_set.labels,
+            test_set.weights(),
+        )
+    }
+
+    // Id of the leaf each row ends up in
+    pub fn apply(&self, feature_rows: &[Vec<f64>]) -> Vec<usize> {
+        feature_rows
+            .par_iter()
+            .map(|feature_row| find_leaf_node_id_for_data(feature_row, &self.root))
+            .collect()
+    }
+
+    // Questions answered by the row on the way to its leaf
+    pub fn decision_path(&self, feature_row: &[f64]) -> DecisionPath {
+        find_decision_path(feature_row, &self.root)
+    }
+
+    // Expected value plus the SHAP values of each feature sums to predict
+    pub fn shap_values(&self, feature_row: &[f64]) -> ShapValues {
+        calculate_shap_values(&self.root, feature_row, average_label_of_leaf)
+    }
+
+    pub fn shap_interaction_values(&self, feature_row: &[f64]) -> ShapInteractionValues {
+        calculate_shap_interaction_values(&self.root, feature_row, average_label_of_leaf)
     }
 }
 
+fn average_label_of_leaf(leaf: &RegressionLeaf) -> Vec<f64> {
+    let data = leaf.data.as_ref().unwrap();
+    calculate_weighted_mean_vector_of_labels(&data.labels, data.weights(), data.number_of_targets())
+}
+
 impl<'a> RegressionMultiTargetDecisionTreeNewPartition<'a>{
     pub fn new(data: MultiTargetDataSetSortedFeatures<'a>, tree_config: TreeConfig) -> Self {
         let all_labels = &data.labels.clone();
-        Self {
-            root: regression_tree_builder::build_regression_tree_new_partition(
-                data,
-                all_labels,
-                tree_config,
-                0,
-            ),
-        }
+        let all_weights = data.weights.clone();
+        let mut root = regression_tree_builder::build_regression_tree_new_partition(
+            data,
+            all_labels,
+            all_weights.as_deref(),
+            tree_config,
+            0,
+        );
+        root.assign_node_ids();
+        Self { root }
     }
 }
@@ -0,0 +1,145 @@
+use std::io::{self, Write};
+
+use crate::{leaf::Leaf, node::TreeNode, printer::feature_name};
+
+// Missing feature names fall back to "feature_{column}", missing target names to indices.
+// Nodes deeper than max_depth are collapsed into a single "..." node.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DotExportConfig<'a> {
+    pub feature_names: &'a [String],
+    pub target_names: &'a [String],
+    pub max_depth: Option<usize>,
+}
+
+// Writes the tree as a Graphviz digraph, render with e.g `dot -Tpng tree.dot -o tree.png`.
+// Node ids in the output are the tree's node ids, see TreeNode::assign_node_ids.
+pub fn write_dot<L: Leaf, W: Write>(
+    writer: &mut W,
+    root: &TreeNode<L>,
+    config: &DotExportConfig,
+) -> io::Result<()> {
+    writeln!(writer, "digraph Tree {{")?;
+    writeln!(
+        writer,
+        "node [shape=box, style=\"rounded\", fontname=\"helvetica\"];"
+    )?;
+    writeln!(writer, "edge [fontname=\"helvetica\"];")?;
+    write_node(writer, root, config, 0)?;
+    writeln!(writer, "}}")
+}
+
+fn write_node<L: Leaf, W: Write>(
+    writer: &mut W,
+    node: &TreeNode<L>,
+    config: &DotExportConfig,
+    depth: usize,
+) -> io::Result<()> {
+    if matches!(config.max_depth, Some(max_depth) if depth > max_depth) {
+        return writeln!(writer, "{} [label=\"...\"];", node.id);
+    }
+
+    let statistics = format!(
+        "samples = {}\\nimpurity = {:.4}",
+        node.statistics.number_of_samples, node.statistics.impurity
+    );
+    if node.is_leaf_node() {
+        let leaf_value = node
+            .leaf
+            .as_ref()
+            .unwrap()
+            .format_value(config.target_names);
+        return writeln!(
+            writer,
+            "{} [label=\"{}\\n{}\"];",
+            node.id,
+            statistics,
+            escape(&leaf_value)
+        );
+    }
+
+    let column = node.question.column as usize;
+    let feature_name = feature_name(config.feature_names, column);
+    writeln!(
+        writer,
+        "{} [label=\"{}\\n{}\"];",
+        node.id,
+        escape(&node.question.to_string(&feature_name)),
+        statistics
+    )?;
+    for (branch, answer) in [(&node.true_branch, "True"), (&node.false_branch, "False")] {
+        let child = branch.as_ref().unwrap();
+        write_node(writer, child, config, depth + 1)?;
+        writeln!(
+            writer,
+            "{} -> {} [label=\"{}\"];",
+            node.id, child.id, answer
+        )?;
+    }
+    Ok(())
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use common::data_reader::{get_feature_names, read_csv_data_one_hot_multi_target};
+
+    use super::*;
+    use crate::{
+        decision_trees::{RegressionMultiTargetDecisionTree, TreeConfig},
+        split_finder::{SplitFinder, SplitMetric},
+    };
+
+    #[test]
+    fn test_write_dot_for_regression_tree() {
+        let data_set = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
+        let tree_config = TreeConfig {
+            split_finder: SplitFinder::new(SplitMetric::Variance),
+            use_multi_threading: false,
+            number_of_classes: 3,
+            max_levels: 3,
+        };
+        let tree = RegressionMultiTargetDecisionTree::new(data_set, tree_config);
+        let feature_names = get_feature_names("./../common/data-files/iris.csv");
+        let target_names: Vec<String> = ["setosa", "versicolor", "virginica"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+
+        let mut output = vec![];
+        let config = DotExportConfig {
+            feature_names: &feature_names,
+            target_names: &target_names,
+            max_depth: None,
+        };
+        write_dot(&mut output, &tree.root, &config).unwrap();
+        let dot = String::from_utf8(output).unwrap();
+        assert!(dot.starts_with("digraph Tree {"));
+        assert!(dot.trim_end().ends_with('}'));
+        let root_feature = &feature_names[tree.root.question.column as usize];
+        assert!(dot.contains(&format!("0 [label=\"Is {} >=", root_feature)));
+        assert!(dot.contains("samples = 150"));
+        assert!(dot.contains("setosa: "));
+        let number_of_edges = dot.lines().filter(|line| line.contains("->")).count();
+        let number_of_leaves = tree.root.leaf_ids().len();
+        assert_eq!(number_of_edges, 2 * (number_of_leaves - 1));
+
+        let mut truncated_output = vec![];
+        let truncated_config = DotExportConfig {
+            max_depth: Some(0),
+            ..config
+        };
+        write_dot(&mut truncated_output, &tree.root, &truncated_config).unwrap();
+        let truncated_dot = String::from_utf8(truncated_output).unwrap();
+        assert_eq!(truncated_dot.matches("[label=\"...\"]").count(), 2);
+        assert_eq!(
+            truncated_dot
+                .lines()
+                .filter(|line| line.contains("->"))
+                .count(),
+            2
+        );
+    }
+}
@@ -0,0 +1,167 @@
+use crate::{leaf::Leaf, node::TreeNode};
+
+// Importance of each feature, computed from the splits of one or more trees.
+// A split's gain contribution is its gain (variance decrease per unit of weight) multiplied by
+// its cover, i.e the decrease in the (weighted) sum of squared errors.
+// Cover is the total weight of the training samples reaching the splits on a feature, their
+// number when unweighted.
+#[derive(Debug, Clone)]
+pub struct FeatureImportance {
+    pub total_gain: Vec<f64>,
+    pub average_gain: Vec<f64>,
+    pub split_count: Vec<usize>,
+    pub total_cover: Vec<f64>,
+    pub average_cover: Vec<f64>,
+    // Indexed by feature then target, see NodeStatistics::gain_per_target
+    pub total_gain_per_target: Vec<Vec<f64>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NamedFeatureImportance {
+    pub feature_name: String,
+    pub total_gain: f64,
+    pub average_gain: f64,
+    pub split_count: usize,
+    pub total_cover: f64,
+    pub average_cover: f64,
+    pub total_gain_per_target: Vec<f64>,
+}
+
+impl FeatureImportance {
+    pub fn from_tree<L: Leaf>(root: &TreeNode<L>, number_of_features: usize) -> Self {
+        let mut feature_importance = Self::empty(number_of_features);
+        feature_importance.add_splits_of_tree(root);
+        feature_importance.calculate_averages();
+        feature_importance
+    }
+
+    // Aggregated over all trees of an ensemble
+    pub fn from_trees<L: Leaf>(trees: &[Box<TreeNode<L>>], number_of_features: usize) -> Self {
+        let mut feature_importance = Self::empty(number_of_features);
+        trees
+            .iter()
+            .for_each(|tree| feature_importance.add_splits_of_tree(tree));
+        feature_importance.calculate_averages();
+        feature_importance
+    }
+
+    // Total gain scaled to sum to 1
+    pub fn normalised_total_gain(&self) -> Vec<f64> {
+        let sum_of_total_gain: f64 = self.total_gain.iter().sum();
+        if sum_of_total_gain == 0. {
+            return vec![0.; self.total_gain.len()];
+        }
+        self.total_gain
+            .iter()
+            .map(|gain| gain / sum_of_total_gain)
+            .collect()
+    }
+
+    // Feature names as returned by get_feature_names, any trailing label column name is ignored.
+    // Sorted by total gain, most important first.
+    pub fn with_feature_names(&self, feature_names: &[String]) -> Vec<NamedFeatureImportance> {
+        let number_of_features = self.total_gain.len();
+        assert!(feature_names.len() >= number_of_features);
+        let mut named_feature_importances: Vec<NamedFeatureImportance> = (0..number_of_features)
+            .map(|feature| NamedFeatureImportance {
+                feature_name: feature_names[feature].clone(),
+                total_gain: self.total_gain[feature],
+                average_gain: self.average_gain[feature],
+                split_count: self.split_count[feature],
+                total_cover: self.total_cover[feature],
+                average_cover: self.average_cover[feature],
+                total_gain_per_target: self.total_gain_per_target[feature].clone(),
+            })
+            .collect();
+        named_feature_importances
+            .sort_by(|first, second| second.total_gain.partial_cmp(&first.total_gain).unwrap());
+        named_feature_importances
+    }
+
+    fn empty(number_of_features: usize) -> Self {
+        Self {
+            total_gain: vec![0.; number_of_features],
+            average_gain: vec![0.; number_of_features],
+            split_count: vec![0; number_of_features],
+            total_cover: vec![0.; number_of_features],
+            average_cover: vec![0.; number_of_features],
+            total_gain_per_target: vec![vec![]; number_of_features],
+        }
+    }
+
+    fn add_splits_of_tree<L: Leaf>(&mut self, node: &TreeNode<L>) {
+        if node.is_leaf_node() {
+            return;
+        }
+        let feature = node.question.column as usize;
+        let statistics = &node.statistics;
+        self.total_gain[feature] += statistics.gain * statistics.cover;
+        self.split_count[feature] += 1;
+        self.total_cover[feature] += statistics.cover;
+
+        let total_gain_per_target = &mut self.total_gain_per_target[feature];
+        if total_gain_per_target.is_empty() {
+            total_gain_per_target.resize(statistics.gain_per_target.len(), 0.);
+        }
+        total_gain_per_target
+            .iter_mut()
+            .zip(&statistics.gain_per_target)
+            .for_each(|(total, target_gain)| *total += target_gain * statistics.cover);
+
+        self.add_splits_of_tree(node.true_branch.as_ref().unwrap());
+        self.add_splits_of_tree(node.false_branch.as_ref().unwrap());
+    }
+
+    fn calculate_averages(&mut self) {
+        for feature in 0..self.total_gain.len() {
+            let split_count = self.split_count[feature];
+            if split_count > 0 {
+                self.average_gain[feature] = self.total_gain[feature] / split_count as f64;
+                self.average_cover[feature] = self.total_cover[feature] / split_count as f64;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::data_reader::{get_feature_names, read_csv_data_one_hot_multi_target};
+
+    use super::*;
+    use crate::{
+        decision_trees::{RegressionMultiTargetDecisionTree, TreeConfig},
+        split_finder::{SplitFinder, SplitMetric},
+    };
+
+    #[test]
+    fn test_feature_importance_iris() {
+        let data_set = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
+        let number_of_features = data_set.feature_columns.len();
+        let number_of_samples = data_set.labels.len();
+        let tree_config = TreeConfig {
+            split_finder: SplitFinder::new(SplitMetric::Variance),
+            use_multi_threading: false,
+            number_of_classes: 3,
+            max_levels: 3,
+        };
+        let tree = RegressionMultiTargetDecisionTree::new(data_set, tree_config);
+        let feature_importance = FeatureImportance::from_tree(&tree.root, number_of_features);
+
+        let root_feature = tree.root.question.column as usize;
+        assert!(feature_importance.split_count[root_feature] >= 1);
+        assert!(feature_importance.total_cover[root_feature] >= number_of_samples as f64);
+        let root_gain_over_targets: f64 = tree.root.statistics.gain_per_target.iter().sum();
+        assert!((root_gain_over_targets - tree.root.statistics.gain).abs() < 1e-10);
+        assert!(feature_importance.total_gain_per_target[root_feature].len() == 3);
+        let normalised_sum: f64 = feature_importance.normalised_total_gain().iter().sum();
+        assert!((normalised_sum - 1.).abs() < 1e-10);
+
+        let feature_names = get_feature_names("./../common/data-files/iris.csv");
+        let named_feature_importances = feature_importance.with_feature_names(&feature_names);
+        assert_eq!(named_feature_importances.len(), number_of_features);
+        assert_eq!(
+            named_feature_importances[0].feature_name,
+            feature_names[root_feature]
+        );
+    }
+}
@@ -1,9 +1,16 @@
 use common::datasets::MultiTargetDataSet;
+use rayon::prelude::*;
 
 use crate::{
     decision_trees::TreeConfig,
     leaf::{AMGBoostLeaf, GradBoostLeaf},
     node::TreeNode,
+    tree_shap::{
+        calculate_shap_interaction_values, calculate_shap_values, ShapInteractionValues, ShapValues,
+    },
+    tree_traverse::{
+        find_decision_path, find_leaf_node_for_data, find_leaf_node_id_for_data, DecisionPath,
+    },
 };
 
 use self::grad_boost_leaf_output::LeafOutputCalculator;
@@ -26,25 +33,63 @@ impl GradBoostMultiTargetDecisionTree {
         tree_config: TreeConfig,
         leaf_output_calculator: LeafOutputCalculator,
     ) -> Self {
-        Self {
-            root: match tree_config.use_multi_threading {
-                true => {
-                    grad_boost_tree_builder::build_grad_boost_regression_tree_using_multiple_threads(
-                        data,
-                        tree_config,
-                        leaf_output_calculator,
-                        0,
-                    )
-                }
-                false => grad_boost_tree_builder::build_grad_boost_regression_tree(
+        let mut root = match tree_config.use_multi_threading {
+            true => {
+                grad_boost_tree_builder::build_grad_boost_regression_tree_using_multiple_threads(
                     data,
                     tree_config,
                     leaf_output_calculator,
                     0,
-                ),
-            },
-        }
+                )
+            }
+            false => grad_boost_tree_builder::build_grad_boost_regression_tree(
+                data,
+                tree_config,
+                leaf_output_calculator,
+                0,
+            ),
+        };
+        root.assign_node_ids();
+        Self { root }
     }
+
+    // Prediction is the (unweighted) leaf output of the leaf the row falls into
+    pub fn predict(&self, feature_row: &[f64]) -> Vec<f64> {
+        leaf_output_of_leaf(find_leaf_node_for_data(feature_row, &self.root))
+    }
+
+    pub fn predict_batch(&self, feature_rows: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        feature_rows
+            .par_iter()
+            .map(|feature_row| self.predict(feature_row))
+            .collect()
+    }
+
+    // Id of the leaf each row ends up in
+    pub fn apply(&self, feature_rows: &[Vec<f64>]) -> Vec<usize> {
+        feature_rows
+            .par_iter()
+            .map(|feature_row| find_leaf_node_id_for_data(feature_row, &self.root))
+            .collect()
+    }
+
+    // Questions answered by the row on the way to its leaf
+    pub fn decision_path(&self, feature_row: &[f64]) -> DecisionPath {
+        find_decision_path(feature_row, &self.root)
+    }
+
+    // Expected value plus the SHAP values of each feature sums to predict
+    pub fn shap_values(&self, feature_row: &[f64]) -> ShapValues {
+        calculate_shap_values(&self.root, feature_row, leaf_output_of_leaf)
+    }
+
+    pub fn shap_interaction_values(&self, feature_row: &[f64]) -> ShapInteractionValues {
+        calculate_shap_interaction_values(&self.root, feature_row, leaf_output_of_leaf)
+    }
+}
+
+fn leaf_output_of_leaf(leaf: &GradBoostLeaf) -> Vec<f64> {
+    leaf.leaf_output.as_ref().unwrap().clone()
 }
 
 // Multi target decision tree where each label is a vector, and each label-vector
@@ -59,23 +104,36 @@ impl AMGBoostTree {
         tree_config: TreeConfig,
         leaf_output_calculator: LeafOutputCalculator,
     ) -> Self {
-        Self {
-            root: match tree_config.use_multi_threading {
-                true => {
-                    approximate_grad_boost_tree_builder::build_approximate_grad_boost_regression_tree_using_multiple_threads(
-                        data,
-                        tree_config,
-                        leaf_output_calculator,
-                        0,
-                    )
-                }
-                false => approximate_grad_boost_tree_builder::build_approximate_grad_boost_regression_tree(
+        let mut root = match tree_config.use_multi_threading {
+            true => {
+                approximate_grad_boost_tree_builder::build_approximate_grad_boost_regression_tree_using_multiple_threads(
                     data,
                     tree_config,
                     leaf_output_calculator,
                     0,
-                ),
-            },
-        }
+                )
+            }
+            false => approximate_grad_boost_tree_builder::build_approximate_grad_boost_regression_tree(
+                data,
+                tree_config,
+                leaf_output_calculator,
+                0,
+            ),
+        };
+        root.assign_node_ids();
+        Self { root }
+    }
+
+    // Id of the leaf each row ends up in
+    pub fn apply(&self, feature_rows: &[Vec<f64>]) -> Vec<usize> {
+        feature_rows
+            .par_iter()
+            .map(|feature_row| find_leaf_node_id_for_data(feature_row, &self.root))
+            .collect()
+    }
+
+    // Questions answered by the row on the way to its leaf
+    pub fn decision_path(&self, feature_row: &[f64]) -> DecisionPath {
+        find_decision_path(feature_row, &self.root)
     }
 }
@@ -1,16 +1,107 @@
 use common::datasets::{MultiTargetDataSet, MultiTargetDataSetSortedFeatures};
 
-pub trait Leaf {}
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 
-impl Leaf for RegressionLeaf {}
+use crate::calculations::calculate_weighted_mean_vector_of_labels;
 
-impl Leaf for RegressionLeafNewPartition<'_> {}
+pub trait Leaf {
+    // Short description of the leaf's value for printing and export. Target names label the
+    // entries of output vectors, indices are used when there are none.
+    fn format_value(&self, target_names: &[String]) -> String;
+    // The leaf's value as a JSON object, e.g {"output": [0.1, -0.2]}
+    fn value_to_json(&self) -> Value;
+}
+
+impl Leaf for RegressionLeaf {
+    fn format_value(&self, target_names: &[String]) -> String {
+        let data = self.data.as_ref().unwrap();
+        let mean_label = calculate_weighted_mean_vector_of_labels(
+            &data.labels,
+            data.weights.as_deref(),
+            data.labels[0].len(),
+        );
+        format!("mean {}", format_vector(&mean_label, target_names))
+    }
 
-impl Leaf for GradBoostLeaf {}
+    fn value_to_json(&self) -> Value {
+        let data = self.data.as_ref().unwrap();
+        let mean_label = calculate_weighted_mean_vector_of_labels(
+            &data.labels,
+            data.weights.as_deref(),
+            data.labels[0].len(),
+        );
+        json!({ "mean": mean_label })
+    }
+}
 
-impl Leaf for AMGBoostLeaf {}
+impl Leaf for RegressionLeafNewPartition<'_> {
+    fn format_value(&self, target_names: &[String]) -> String {
+        let data = self.data.as_ref().unwrap();
+        let mean_label = calculate_weighted_mean_vector_of_labels(
+            &data.labels,
+            data.weights.as_deref(),
+            data.labels[0].len(),
+        );
+        format!("mean {}", format_vector(&mean_label, target_names))
+    }
 
-#[derive(Debug, Clone)]
+    fn value_to_json(&self) -> Value {
+        let data = self.data.as_ref().unwrap();
+        let mean_label = calculate_weighted_mean_vector_of_labels(
+            &data.labels,
+            data.weights.as_deref(),
+            data.labels[0].len(),
+        );
+        json!({ "mean": mean_label })
+    }
+}
+
+impl Leaf for GradBoostLeaf {
+    fn format_value(&self, target_names: &[String]) -> String {
+        format!(
+            "output {}",
+            format_vector(self.leaf_output.as_ref().unwrap(), target_names)
+        )
+    }
+
+    fn value_to_json(&self) -> Value {
+        json!({ "output": self.leaf_output })
+    }
+}
+
+impl Leaf for AMGBoostLeaf {
+    fn format_value(&self, target_names: &[String]) -> String {
+        let class = self.class.unwrap();
+        let class_name = target_names
+            .get(class)
+            .cloned()
+            .unwrap_or_else(|| class.to_string());
+        format!(
+            "class {} max value {:.4}",
+            class_name,
+            self.max_value.unwrap()
+        )
+    }
+
+    fn value_to_json(&self) -> Value {
+        json!({ "class": self.class, "max_value": self.max_value })
+    }
+}
+
+fn format_vector(values: &[f64], target_names: &[String]) -> String {
+    let entries: Vec<String> = values
+        .iter()
+        .enumerate()
+        .map(|(target, value)| match target_names.get(target) {
+            Some(target_name) => format!("{}: {:.4}", target_name, value),
+            None => format!("{:.4}", value),
+        })
+        .collect();
+    format!("[{}]", entries.join(", "))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RegressionLeaf {
     pub data: Option<MultiTargetDataSet>,
 }
@@ -20,12 +111,12 @@ pub struct RegressionLeafNewPartition<'a> {
     pub data: Option<MultiTargetDataSetSortedFeatures<'a>>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GradBoostLeaf {
     pub leaf_output: Option<Vec<f64>>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AMGBoostLeaf {
     pub max_value: Option<f64>,
     pub class: Option<usize>,
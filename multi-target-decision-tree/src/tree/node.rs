@@ -1,13 +1,93 @@
 use common::question::Question;
+use serde::{Deserialize, Serialize};
 
-use crate::leaf::Leaf;
+use crate::{
+    calculations::{
+        calculate_total_weight, calculate_weighted_gain_vector,
+        calculate_weighted_mean_vector_of_labels, calculate_weighted_variance_vector_of_labels,
+    },
+    leaf::Leaf,
+};
 
-#[derive(Debug)]
+// Recorded while building a node, from the training data that reached it. The statistics use
+// the sample weights, falling back to unweighted ones where every weight reaching the node is 0.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NodeStatistics {
+    pub number_of_samples: usize,
+    // Total weight of the samples reaching the node, number_of_samples when unweighted
+    pub cover: f64,
+    // The split finder's gain (variance decrease per unit of weight), 0 for leaves
+    pub gain: f64,
+    // Decrease in variance of each target for the partition that was made, empty for leaves.
+    // Sums to gain unless the split finder's threshold fell between tied feature values.
+    pub gain_per_target: Vec<f64>,
+    // Mean of the labels reaching the node, for boosting trees these are the pseudo-residuals
+    pub mean_label: Vec<f64>,
+    // Sum over targets of the variance of the labels reaching the node
+    pub impurity: f64,
+}
+
+impl NodeStatistics {
+    pub(crate) fn leaf<T: AsRef<[f64]>>(labels: &[T], weights: Option<&[f64]>) -> Self {
+        Self {
+            number_of_samples: labels.len(),
+            cover: calculate_total_weight(weights, labels.len()),
+            gain: 0.,
+            gain_per_target: vec![],
+            mean_label: calculate_mean_of_labels(labels, weights),
+            impurity: calculate_impurity_of_labels(labels, weights),
+        }
+    }
+
+    pub(crate) fn split<T: AsRef<[f64]>>(
+        labels: &[T],
+        weights: Option<&[f64]>,
+        true_labels: &[T],
+        true_weights: Option<&[f64]>,
+        false_labels: &[T],
+        false_weights: Option<&[f64]>,
+        gain: f64,
+    ) -> Self {
+        Self {
+            number_of_samples: labels.len(),
+            cover: calculate_total_weight(weights, labels.len()),
+            gain,
+            gain_per_target: calculate_weighted_gain_vector(
+                labels,
+                weights,
+                true_labels,
+                true_weights,
+                false_labels,
+                false_weights,
+            ),
+            mean_label: calculate_mean_of_labels(labels, weights),
+            impurity: calculate_impurity_of_labels(labels, weights),
+        }
+    }
+}
+
+fn calculate_mean_of_labels<T: AsRef<[f64]>>(labels: &[T], weights: Option<&[f64]>) -> Vec<f64> {
+    calculate_weighted_mean_vector_of_labels(labels, weights, number_of_targets(labels))
+}
+
+fn calculate_impurity_of_labels<T: AsRef<[f64]>>(labels: &[T], weights: Option<&[f64]>) -> f64 {
+    calculate_weighted_variance_vector_of_labels(labels, weights, number_of_targets(labels))
+        .iter()
+        .sum()
+}
+
+fn number_of_targets<T: AsRef<[f64]>>(labels: &[T]) -> usize {
+    labels.first().map_or(0, |label| label.as_ref().len())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TreeNode<L: Leaf> {
+    pub id: usize,
     pub question: Question,
     pub true_branch: Option<Box<TreeNode<L>>>,
     pub false_branch: Option<Box<TreeNode<L>>>,
     pub leaf: Option<L>,
+    pub statistics: NodeStatistics,
 }
 
 impl<L: Leaf> TreeNode<L> {
@@ -15,25 +95,68 @@ impl<L: Leaf> TreeNode<L> {
         question: Question,
         true_branch: Box<TreeNode<L>>,
         false_branch: Box<TreeNode<L>>,
+        statistics: NodeStatistics,
     ) -> Self {
         Self {
+            id: 0,
             question,
             true_branch: Some(true_branch),
             false_branch: Some(false_branch),
             leaf: None,
+            statistics,
         }
     }
 
-    pub fn leaf_node(question: Question, leaf: L) -> Self {
+    pub fn leaf_node(question: Question, leaf: L, statistics: NodeStatistics) -> Self {
         Self {
+            id: 0,
             question,
             true_branch: None,
             false_branch: None,
             leaf: Some(leaf),
+            statistics,
         }
     }
 
     pub fn is_leaf_node(&self) -> bool {
         self.true_branch.is_none() && self.false_branch.is_none()
     }
+
+    // Numbers every node in pre-order, true branch before false branch, with the root as 0.
+    // Ids are stable for a given tree so they can be used to refer to nodes and leaves.
+    pub fn assign_node_ids(&mut self) {
+        self.assign_node_ids_starting_from(0);
+    }
+
+    fn assign_node_ids_starting_from(&mut self, id: usize) -> usize {
+        self.id = id;
+        let mut next_id = id + 1;
+        if let Some(true_branch) = self.true_branch.as_mut() {
+            next_id = true_branch.assign_node_ids_starting_from(next_id);
+        }
+        if let Some(false_branch) = self.false_branch.as_mut() {
+            next_id = false_branch.assign_node_ids_starting_from(next_id);
+        }
+        next_id
+    }
+
+    // Ids of the leaf nodes in pre-order
+    pub fn leaf_ids(&self) -> Vec<usize> {
+        let mut leaf_ids = vec![];
+        self.collect_leaf_ids(&mut leaf_ids);
+        leaf_ids
+    }
+
+    fn collect_leaf_ids(&self, leaf_ids: &mut Vec<usize>) {
+        if self.is_leaf_node() {
+            leaf_ids.push(self.id);
+            return;
+        }
+        if let Some(true_branch) = self.true_branch.as_ref() {
+            true_branch.collect_leaf_ids(leaf_ids);
+        }
+        if let Some(false_branch) = self.false_branch.as_ref() {
+            false_branch.collect_leaf_ids(leaf_ids);
+        }
+    }
 }
@@ -0,0 +1,314 @@
+use std::{error::Error, io::Write};
+
+use common::data_writer::{write_csv_records_to_writer, write_markdown_table};
+
+use crate::{leaf::Leaf, node::TreeNode, printer::feature_name};
+
+// Feature values a rule allows, lower_bound <= value < upper_bound.
+// Matches the Question convention, the true branch is value >= threshold.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureInterval {
+    pub feature: usize,
+    pub lower_bound: Option<f64>,
+    pub upper_bound: Option<f64>,
+}
+
+impl FeatureInterval {
+    pub fn contains(&self, value: f64) -> bool {
+        let above_lower_bound = match self.lower_bound {
+            Some(lower_bound) => value >= lower_bound,
+            None => true,
+        };
+        let below_upper_bound = match self.upper_bound {
+            Some(upper_bound) => value < upper_bound,
+            None => true,
+        };
+        above_lower_bound && below_upper_bound
+    }
+
+    pub fn to_string(&self, feature_name: &str) -> String {
+        match (self.lower_bound, self.upper_bound) {
+            (Some(lower_bound), Some(upper_bound)) => {
+                format!("{} <= {} < {}", lower_bound, feature_name, upper_bound)
+            }
+            (Some(lower_bound), None) => format!("{} >= {}", feature_name, lower_bound),
+            (None, Some(upper_bound)) => format!("{} < {}", feature_name, upper_bound),
+            (None, None) => format!("any {}", feature_name),
+        }
+    }
+}
+
+// IF-THEN rule for one leaf. Conditions hold one merged interval per feature on the path,
+// sorted by feature. Support is the fraction of the tree's training samples reaching the leaf.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub tree_index: usize,
+    pub leaf_id: usize,
+    pub conditions: Vec<FeatureInterval>,
+    pub number_of_samples: usize,
+    pub support: f64,
+    pub prediction: Vec<f64>,
+}
+
+impl Rule {
+    pub fn matches(&self, feature_row: &[f64]) -> bool {
+        self.conditions
+            .iter()
+            .all(|condition| condition.contains(feature_row[condition.feature]))
+    }
+
+    // e.g "sepal_width < 3.1 AND 2.45 <= petal_length < 4.75", "TRUE" for a root leaf
+    pub fn condition_to_string(&self, feature_names: &[String]) -> String {
+        if self.conditions.is_empty() {
+            return "TRUE".to_string();
+        }
+        self.conditions
+            .iter()
+            .map(|condition| condition.to_string(&feature_name(feature_names, condition.feature)))
+            .collect::<Vec<_>>()
+            .join(" AND ")
+    }
+
+    pub fn to_string(&self, feature_names: &[String]) -> String {
+        format!(
+            "IF {} THEN {:?}",
+            self.condition_to_string(feature_names),
+            self.prediction
+        )
+    }
+}
+
+// One rule per leaf in pre-order, leaf_value gives a leaf's prediction vector
+pub fn extract_rules<L, F>(root: &TreeNode<L>, leaf_value: F) -> Vec<Rule>
+where
+    L: Leaf,
+    F: Fn(&L) -> Vec<f64>,
+{
+    let mut rules = vec![];
+    let root_number_of_samples = root.statistics.number_of_samples;
+    collect_rules(
+        root,
+        &leaf_value,
+        &mut vec![],
+        root_number_of_samples,
+        &mut rules,
+    );
+    rules
+}
+
+// Rules of every tree of an ensemble, tagged with the index of their tree
+pub fn extract_rules_from_trees<L, F>(trees: &[Box<TreeNode<L>>], leaf_value: F) -> Vec<Rule>
+where
+    L: Leaf,
+    F: Fn(&L) -> Vec<f64>,
+{
+    trees
+        .iter()
+        .enumerate()
+        .flat_map(|(tree_index, tree)| {
+            let mut rules = extract_rules(tree, &leaf_value);
+            rules
+                .iter_mut()
+                .for_each(|rule| rule.tree_index = tree_index);
+            rules
+        })
+        .collect()
+}
+
+fn collect_rules<L, F>(
+    node: &TreeNode<L>,
+    leaf_value: &F,
+    conditions: &mut Vec<FeatureInterval>,
+    root_number_of_samples: usize,
+    rules: &mut Vec<Rule>,
+) where
+    L: Leaf,
+    F: Fn(&L) -> Vec<f64>,
+{
+    if node.is_leaf_node() {
+        let mut sorted_conditions = conditions.clone();
+        sorted_conditions.sort_by_key(|condition| condition.feature);
+        rules.push(Rule {
+            tree_index: 0,
+            leaf_id: node.id,
+            conditions: sorted_conditions,
+            number_of_samples: node.statistics.number_of_samples,
+            support: node.statistics.number_of_samples as f64 / root_number_of_samples as f64,
+            prediction: leaf_value(node.leaf.as_ref().unwrap()),
+        });
+        return;
+    }
+
+    let feature = node.question.column as usize;
+    let threshold = node.question.value;
+    for answer in [true, false] {
+        let previous_conditions = conditions.clone();
+        let interval = match conditions
+            .iter_mut()
+            .find(|condition| condition.feature == feature)
+        {
+            Some(interval) => interval,
+            None => {
+                conditions.push(FeatureInterval {
+                    feature,
+                    lower_bound: None,
+                    upper_bound: None,
+                });
+                conditions.last_mut().unwrap()
+            }
+        };
+        match answer {
+            true => {
+                interval.lower_bound = Some(
+                    interval
+                        .lower_bound
+                        .map_or(threshold, |bound| bound.max(threshold)),
+                )
+            }
+            false => {
+                interval.upper_bound = Some(
+                    interval
+                        .upper_bound
+                        .map_or(threshold, |bound| bound.min(threshold)),
+                )
+            }
+        }
+        let branch = match answer {
+            true => node.true_branch.as_ref().unwrap(),
+            false => node.false_branch.as_ref().unwrap(),
+        };
+        collect_rules(
+            branch,
+            leaf_value,
+            conditions,
+            root_number_of_samples,
+            rules,
+        );
+        *conditions = previous_conditions;
+    }
+}
+
+fn rule_table(
+    rules: &[Rule],
+    feature_names: &[String],
+    target_names: &[String],
+) -> (Vec<String>, Vec<Vec<String>>) {
+    let number_of_targets = rules.first().map_or(0, |rule| rule.prediction.len());
+    let mut header: Vec<String> = ["tree", "leaf", "rule", "samples", "support"]
+        .iter()
+        .map(|column| column.to_string())
+        .collect();
+    header.extend(
+        (0..number_of_targets).map(|target| match target_names.get(target) {
+            Some(target_name) => target_name.clone(),
+            None => format!("target_{}", target),
+        }),
+    );
+    let records = rules
+        .iter()
+        .map(|rule| {
+            let mut record = vec![
+                rule.tree_index.to_string(),
+                rule.leaf_id.to_string(),
+                rule.condition_to_string(feature_names),
+                rule.number_of_samples.to_string(),
+                format!("{:.4}", rule.support),
+            ];
+            record.extend(rule.prediction.iter().map(|value| format!("{:.4}", value)));
+            record
+        })
+        .collect();
+    (header, records)
+}
+
+// One line per rule with its tree, leaf, condition, samples, support and prediction per target
+pub fn write_rules_csv<W: Write>(
+    writer: W,
+    rules: &[Rule],
+    feature_names: &[String],
+    target_names: &[String],
+) -> Result<(), Box<dyn Error>> {
+    let (header, records) = rule_table(rules, feature_names, target_names);
+    write_csv_records_to_writer(writer, &header, &records)
+}
+
+pub fn write_rules_markdown<W: Write>(
+    writer: W,
+    rules: &[Rule],
+    feature_names: &[String],
+    target_names: &[String],
+) -> Result<(), Box<dyn Error>> {
+    let (header, records) = rule_table(rules, feature_names, target_names);
+    write_markdown_table(writer, &header, &records)
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{
+        data_reader::{get_feature_names, read_csv_data_one_hot_multi_target},
+        numerical_calculations::calculate_average_f64_vector,
+    };
+
+    use super::*;
+    use crate::{
+        decision_trees::{RegressionMultiTargetDecisionTree, TreeConfig},
+        leaf::RegressionLeaf,
+        split_finder::{SplitFinder, SplitMetric},
+    };
+
+    fn average_label(leaf: &RegressionLeaf) -> Vec<f64> {
+        calculate_average_f64_vector(&leaf.data.as_ref().unwrap().labels)
+    }
+
+    #[test]
+    fn test_each_row_matches_the_rule_of_its_leaf() {
+        let data_set = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
+        let tree_config = TreeConfig {
+            split_finder: SplitFinder::new(SplitMetric::Variance),
+            use_multi_threading: false,
+            number_of_classes: 3,
+            max_levels: 4,
+        };
+        let tree = RegressionMultiTargetDecisionTree::new(data_set.clone(), tree_config);
+        let rules = extract_rules(&tree.root, average_label);
+        assert_eq!(rules.len(), tree.root.leaf_ids().len());
+        let total_support: f64 = rules.iter().map(|rule| rule.support).sum();
+        assert!((total_support - 1.).abs() < 1e-10);
+
+        let leaf_ids = tree.apply(&data_set.feature_rows);
+        for (feature_row, leaf_id) in data_set.feature_rows.iter().zip(leaf_ids) {
+            let matching_rules: Vec<&Rule> = rules
+                .iter()
+                .filter(|rule| rule.matches(feature_row))
+                .collect();
+            assert_eq!(matching_rules.len(), 1);
+            assert_eq!(matching_rules[0].leaf_id, leaf_id);
+            assert_eq!(matching_rules[0].prediction, tree.predict(feature_row));
+        }
+        for rule in &rules {
+            let mut features: Vec<usize> = rule.conditions.iter().map(|c| c.feature).collect();
+            features.dedup();
+            assert_eq!(features.len(), rule.conditions.len());
+        }
+
+        let feature_names = get_feature_names("./../common/data-files/iris.csv");
+        assert!(rules[0].to_string(&feature_names).starts_with("IF "));
+        let mut markdown_output = vec![];
+        write_rules_markdown(&mut markdown_output, &rules, &feature_names, &[]).unwrap();
+        let markdown = String::from_utf8(markdown_output).unwrap();
+        assert_eq!(markdown.lines().count(), rules.len() + 2);
+        assert!(markdown.starts_with("| tree | leaf | rule | samples | support | target_0 |"));
+    }
+
+    #[test]
+    fn test_feature_interval_to_string() {
+        let interval = FeatureInterval {
+            feature: 0,
+            lower_bound: Some(1.5),
+            upper_bound: Some(3.),
+        };
+        assert_eq!(interval.to_string("x"), "1.5 <= x < 3");
+        assert!(interval.contains(1.5));
+        assert!(!interval.contains(3.));
+    }
+}
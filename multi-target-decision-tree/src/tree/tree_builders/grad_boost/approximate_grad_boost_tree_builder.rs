@@ -1,7 +1,11 @@
 use rayon::prelude::*;
 
 use super::{LeafOutputCalculator, TreeConfig};
-use crate::{data_partitioner::partition, leaf::AMGBoostLeaf, node::TreeNode};
+use crate::{
+    data_partitioner::partition,
+    leaf::AMGBoostLeaf,
+    node::{NodeStatistics, TreeNode},
+};
 use common::datasets::MultiTargetDataSet;
 
 pub(crate) fn build_approximate_grad_boost_regression_tree(
@@ -13,17 +17,27 @@ pub(crate) fn build_approximate_grad_boost_regression_tree(
     let split_result =
         (tree_config.split_finder.find_best_split)(&data, tree_config.number_of_classes);
     if split_result.gain == 0.0 || current_level == tree_config.max_levels {
+        let statistics = NodeStatistics::leaf(&data.labels, data.weights());
         let leaf_output = (leaf_output_calculator.calculate_leaf_output)(&data);
         let (max_value, class) = find_max_value_and_index_from_vector(&leaf_output);
         let leaf = AMGBoostLeaf {
             max_value: Some(max_value),
             class: Some(class),
         };
-        return TreeNode::leaf_node(split_result.question, leaf);
+        TreeNode::leaf_node(split_result.question, leaf, statistics)
     } else {
         let partitioned_data = partition(&data, &split_result.question);
         let left_data = partitioned_data.1;
         let right_data = partitioned_data.0;
+        let statistics = NodeStatistics::split(
+            &data.labels,
+            data.weights(),
+            &left_data.labels,
+            left_data.weights(),
+            &right_data.labels,
+            right_data.weights(),
+            split_result.gain,
+        );
 
         let new_level = current_level + 1;
         let left_tree = build_approximate_grad_boost_regression_tree(
@@ -42,6 +56,7 @@ pub(crate) fn build_approximate_grad_boost_regression_tree(
             split_result.question,
             Box::new(left_tree),
             Box::new(right_tree),
+            statistics,
         )
     }
 }
@@ -55,17 +70,27 @@ pub(crate) fn build_approximate_grad_boost_regression_tree_using_multiple_thread
     let split_result =
         (tree_config.split_finder.find_best_split)(&data, tree_config.number_of_classes);
     if split_result.gain == 0.0 || current_level == tree_config.max_levels {
+        let statistics = NodeStatistics::leaf(&data.labels, data.weights());
         let leaf_output = (leaf_output_calculator.calculate_leaf_output)(&data);
         let (max_value, class) = find_max_value_and_index_from_vector(&leaf_output);
         let leaf = AMGBoostLeaf {
             max_value: Some(max_value),
             class: Some(class),
         };
-        return TreeNode::leaf_node(split_result.question, leaf);
+        TreeNode::leaf_node(split_result.question, leaf, statistics)
     } else {
         let partitioned_data = partition(&data, &split_result.question);
         let left_data = partitioned_data.1;
         let right_data = partitioned_data.0;
+        let statistics = NodeStatistics::split(
+            &data.labels,
+            data.weights(),
+            &left_data.labels,
+            left_data.weights(),
+            &right_data.labels,
+            right_data.weights(),
+            split_result.gain,
+        );
 
         let new_level = current_level + 1;
         let (left_tree, right_tree) = rayon::join(
@@ -90,6 +115,7 @@ pub(crate) fn build_approximate_grad_boost_regression_tree_using_multiple_thread
             split_result.question,
             Box::new(left_tree),
             Box::new(right_tree),
+            statistics,
         )
     }
 }
@@ -1,11 +1,12 @@
 use common::{
     datasets::MultiTargetDataSet,
     numerical_calculations::{
-        add_f64_slices_as_vector, calculate_average_f64_vector, divide_f64_slices_as_vector,
-        multiply_f64_slice_by_f64_scalar, sum_of_f64_vectors,
+        add_f64_slices_as_vector, divide_f64_slices_as_vector, multiply_f64_slice_by_f64_scalar,
     },
 };
 
+use crate::calculations::{calculate_weighted_mean_vector_of_labels, weights_with_positive_total};
+
 #[derive(Clone, Copy)]
 pub enum LeafOutputType {
     Regression,
@@ -32,13 +33,20 @@ impl LeafOutputCalculator {
 }
 
 pub fn calculate_leaf_output_squared_loss(leaf_data: &MultiTargetDataSet) -> Vec<f64> {
-    let average_residuals = calculate_average_f64_vector(&leaf_data.labels);
+    let average_residuals = calculate_weighted_mean_vector_of_labels(
+        &leaf_data.labels,
+        leaf_data.weights(),
+        leaf_data.number_of_targets(),
+    );
     average_residuals
 }
 
+// The row weights, e.g sample weights times class weights, scale both the residuals and the
+// denominator terms. A leaf whose rows all have weight 0 is treated as unweighted.
 pub fn calculate_leaf_output_multi_class_loss(leaf_data: &MultiTargetDataSet) -> Vec<f64> {
-    let numerator = sum_of_f64_vectors(&leaf_data.labels);
-    let denominator = calculate_denominator_term_for_leaf_output(&leaf_data.labels);
+    let weights = weights_with_positive_total(leaf_data.weights());
+    let numerator = calculate_weighted_sum_of_residuals(&leaf_data.labels, weights);
+    let denominator = calculate_denominator_term_for_leaf_output(&leaf_data.labels, weights);
     let numerator_over_denominator = divide_f64_slices_as_vector(&numerator, &denominator);
     let number_of_classes = leaf_data.labels[0].len() as f64;
     let scalar = (number_of_classes - 1.) / number_of_classes;
@@ -46,19 +54,42 @@ pub fn calculate_leaf_output_multi_class_loss(leaf_data: &MultiTargetDataSet) ->
     result
 }
 
-fn calculate_denominator_term_for_leaf_output(vector_of_vectors: &Vec<Vec<f64>>) -> Vec<f64> {
+fn calculate_weighted_sum_of_residuals(
+    vector_of_vectors: &[Vec<f64>],
+    weights: Option<&[f64]>,
+) -> Vec<f64> {
+    let mut sum_vector = vec![0.; vector_of_vectors[0].len()];
+    vector_of_vectors
+        .iter()
+        .enumerate()
+        .for_each(|(row, inner_vector)| {
+            let weight = weights.map_or(1., |weights| weights[row]);
+            let term = multiply_f64_slice_by_f64_scalar(weight, inner_vector);
+            sum_vector = add_f64_slices_as_vector(&sum_vector, &term);
+        });
+    sum_vector
+}
+
+fn calculate_denominator_term_for_leaf_output(
+    vector_of_vectors: &[Vec<f64>],
+    weights: Option<&[f64]>,
+) -> Vec<f64> {
     let length_of_inner_vectors = vector_of_vectors[0].len();
     let mut sum_vector = vec![0.; length_of_inner_vectors];
-    vector_of_vectors.iter().for_each(|inner_vector| {
-        let term: Vec<f64> = inner_vector
-            .iter()
-            .map(|element| {
-                let element_abs = element.abs();
-                element_abs * (1. - element_abs)
-            })
-            .collect();
-        sum_vector = add_f64_slices_as_vector(&sum_vector, &term);
-    });
+    vector_of_vectors
+        .iter()
+        .enumerate()
+        .for_each(|(row, inner_vector)| {
+            let weight = weights.map_or(1., |weights| weights[row]);
+            let term: Vec<f64> = inner_vector
+                .iter()
+                .map(|element| {
+                    let element_abs = element.abs();
+                    weight * element_abs * (1. - element_abs)
+                })
+                .collect();
+            sum_vector = add_f64_slices_as_vector(&sum_vector, &term);
+        });
     sum_vector
 }
 
@@ -73,16 +104,71 @@ mod tests {
 
     use super::{
         calculate_denominator_term_for_leaf_output, calculate_leaf_output_multi_class_loss,
+        calculate_leaf_output_squared_loss, MultiTargetDataSet,
     };
 
     #[test]
     fn test_leaf_output_multi_class_loss() {
         let vector_of_vectors = vec![vec![0.333, 0.333, 0.333], vec![0.333, 0.333, 0.333]];
         let numerator = sum_of_f64_vectors(&vector_of_vectors);
-        let denominator = calculate_denominator_term_for_leaf_output(&vector_of_vectors);
+        let denominator = calculate_denominator_term_for_leaf_output(&vector_of_vectors, None);
         let scalar = 2. / 3.;
         let division = divide_f64_slices_as_vector(&numerator, &denominator);
         let result = multiply_f64_slice_by_f64_scalar(scalar, &division);
         println!("{:?}", result);
     }
+
+    #[test]
+    fn test_weighted_leaf_outputs_match_repeated_rows() {
+        let feature_rows = vec![vec![0.], vec![1.]];
+        let residuals = vec![vec![0.4, -0.2, -0.2], vec![-0.1, 0.3, -0.2]];
+        let weighted = MultiTargetDataSet::from_rows(feature_rows.clone(), residuals.clone())
+            .with_weights(vec![2., 1.]);
+        let repeated = MultiTargetDataSet::from_rows(
+            vec![
+                feature_rows[0].clone(),
+                feature_rows[0].clone(),
+                feature_rows[1].clone(),
+            ],
+            vec![
+                residuals[0].clone(),
+                residuals[0].clone(),
+                residuals[1].clone(),
+            ],
+        );
+        for calculate_leaf_output in &[
+            calculate_leaf_output_squared_loss,
+            calculate_leaf_output_multi_class_loss,
+        ] {
+            let expected = calculate_leaf_output(&repeated);
+            calculate_leaf_output(&weighted)
+                .iter()
+                .zip(expected)
+                .for_each(|(actual, expected)| assert!((actual - expected).abs() < 1e-12));
+        }
+    }
+
+    #[test]
+    fn test_leaf_outputs_of_zero_weight_rows_are_unweighted() {
+        let residuals = vec![
+            vec![0.4, -0.2, -0.2],
+            vec![-0.1, 0.3, -0.2],
+            vec![0.1, 0.1, -0.2],
+        ];
+        let data = MultiTargetDataSet::from_rows(vec![vec![0.], vec![1.], vec![2.]], residuals)
+            .with_weights(vec![0., 0., 1.]);
+        let zero_weight_leaf = data.subset(&[0, 1]);
+        let unweighted_leaf = MultiTargetDataSet::from_rows(
+            zero_weight_leaf.feature_rows.clone(),
+            zero_weight_leaf.labels.clone(),
+        );
+        for calculate_leaf_output in &[
+            calculate_leaf_output_squared_loss,
+            calculate_leaf_output_multi_class_loss,
+        ] {
+            let leaf_output = calculate_leaf_output(&zero_weight_leaf);
+            assert!(leaf_output.iter().all(|value| value.is_finite()));
+            assert_eq!(leaf_output, calculate_leaf_output(&unweighted_leaf));
+        }
+    }
 }
@@ -1,6 +1,10 @@
 use rayon::prelude::*;
 
-use crate::{data_partitioner::partition, leaf::GradBoostLeaf, node::TreeNode};
+use crate::{
+    data_partitioner::partition,
+    leaf::GradBoostLeaf,
+    node::{NodeStatistics, TreeNode},
+};
 use common::datasets::MultiTargetDataSet;
 
 use super::{LeafOutputCalculator, TreeConfig};
@@ -14,15 +18,25 @@ pub(crate) fn build_grad_boost_regression_tree(
     let split_result =
         (tree_config.split_finder.find_best_split)(&data, tree_config.number_of_classes);
     if split_result.gain == 0.0 || current_level == tree_config.max_levels {
+        let statistics = NodeStatistics::leaf(&data.labels, data.weights());
         let leaf_output = (leaf_output_calculator.calculate_leaf_output)(&data);
         let leaf = GradBoostLeaf {
             leaf_output: Some(leaf_output),
         };
-        return TreeNode::leaf_node(split_result.question, leaf);
+        TreeNode::leaf_node(split_result.question, leaf, statistics)
     } else {
         let partitioned_data = partition(&data, &split_result.question);
         let left_data = partitioned_data.1;
         let right_data = partitioned_data.0;
+        let statistics = NodeStatistics::split(
+            &data.labels,
+            data.weights(),
+            &left_data.labels,
+            left_data.weights(),
+            &right_data.labels,
+            right_data.weights(),
+            split_result.gain,
+        );
 
         let new_level = current_level + 1;
         let left_tree = build_grad_boost_regression_tree(
@@ -41,6 +55,7 @@ pub(crate) fn build_grad_boost_regression_tree(
             split_result.question,
             Box::new(left_tree),
             Box::new(right_tree),
+            statistics,
         )
     }
 }
@@ -54,15 +69,25 @@ pub(crate) fn build_grad_boost_regression_tree_using_multiple_threads(
     let split_result =
         (tree_config.split_finder.find_best_split)(&data, tree_config.number_of_classes);
     if split_result.gain == 0.0 || current_level == tree_config.max_levels {
+        let statistics = NodeStatistics::leaf(&data.labels, data.weights());
         let leaf_output = (leaf_output_calculator.calculate_leaf_output)(&data);
         let leaf = GradBoostLeaf {
             leaf_output: Some(leaf_output),
         };
-        return TreeNode::leaf_node(split_result.question, leaf);
+        TreeNode::leaf_node(split_result.question, leaf, statistics)
     } else {
         let partitioned_data = partition(&data, &split_result.question);
         let left_data = partitioned_data.1;
         let right_data = partitioned_data.0;
+        let statistics = NodeStatistics::split(
+            &data.labels,
+            data.weights(),
+            &left_data.labels,
+            left_data.weights(),
+            &right_data.labels,
+            right_data.weights(),
+            split_result.gain,
+        );
 
         let new_level = current_level + 1;
         let (left_tree, right_tree) = rayon::join(
@@ -87,6 +112,7 @@ pub(crate) fn build_grad_boost_regression_tree_using_multiple_threads(
             split_result.question,
             Box::new(left_tree),
             Box::new(right_tree),
+            statistics,
         )
     }
 }
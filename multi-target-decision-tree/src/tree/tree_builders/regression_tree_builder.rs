@@ -6,7 +6,7 @@ use common::{
 use crate::{
     data_partitioner::partition,
     leaf::{RegressionLeaf, RegressionLeafNewPartition},
-    node::TreeNode,
+    node::{NodeStatistics, TreeNode},
     split_finder,
 };
 
@@ -20,12 +20,22 @@ pub(crate) fn build_regression_tree(
     let split_result =
         (tree_config.split_finder.find_best_split)(&data, tree_config.number_of_classes);
     if split_result.gain == 0.0 || current_level == tree_config.max_levels {
+        let statistics = NodeStatistics::leaf(&data.labels, data.weights());
         let leaf = RegressionLeaf { data: Some(data) };
-        return TreeNode::leaf_node(split_result.question, leaf);
+        TreeNode::leaf_node(split_result.question, leaf, statistics)
     } else {
         let partitioned_data = partition(&data, &split_result.question);
         let left_data = partitioned_data.1;
         let right_data = partitioned_data.0;
+        let statistics = NodeStatistics::split(
+            &data.labels,
+            data.weights(),
+            &left_data.labels,
+            left_data.weights(),
+            &right_data.labels,
+            right_data.weights(),
+            split_result.gain,
+        );
 
         let new_level = current_level + 1;
         let left_tree = build_regression_tree(left_data, tree_config, new_level);
@@ -34,6 +44,7 @@ pub(crate) fn build_regression_tree(
             split_result.question,
             Box::new(left_tree),
             Box::new(right_tree),
+            statistics,
         )
     }
 }
@@ -41,6 +52,7 @@ pub(crate) fn build_regression_tree(
 pub(crate) fn build_regression_tree_new_partition<'a>(
     data: MultiTargetDataSetSortedFeatures<'a>,
     all_labels: &Vec<&'a Vec<f64>>,
+    all_weights: Option<&[f64]>,
     tree_config: TreeConfig,
     current_level: u32,
 ) -> TreeNode<RegressionLeafNewPartition<'a>> {
@@ -49,29 +61,56 @@ pub(crate) fn build_regression_tree_new_partition<'a>(
     let split_result = split_finder::split_finder_variance::find_best_split_new_partition(
         &data,
         all_labels,
+        all_weights,
         number_of_targets,
         number_of_cols,
     );
     if split_result.gain == 0.0 || current_level == tree_config.max_levels {
+        let statistics = NodeStatistics::leaf(&data.labels, data.weights.as_deref());
         let leaf = RegressionLeafNewPartition { data: Some(data) };
-        return TreeNode::leaf_node(split_result.question, leaf);
+        TreeNode::leaf_node(split_result.question, leaf, statistics)
     } else {
         let split_column = split_result.question.column as usize;
         let split_value = split_result.question.value;
-        let partitioned_data =
-            data_processor::new_partition(&data, split_column, split_value, all_labels);
+        let partitioned_data = data_processor::new_partition(
+            &data,
+            split_column,
+            split_value,
+            all_labels,
+            all_weights,
+        );
         let left_data = partitioned_data.0;
         let right_data = partitioned_data.1;
+        let statistics = NodeStatistics::split(
+            &data.labels,
+            data.weights.as_deref(),
+            &left_data.labels,
+            left_data.weights.as_deref(),
+            &right_data.labels,
+            right_data.weights.as_deref(),
+            split_result.gain,
+        );
 
         let new_level = current_level + 1;
-        let left_tree =
-            build_regression_tree_new_partition(left_data, all_labels, tree_config, new_level);
-        let right_tree =
-            build_regression_tree_new_partition(right_data, all_labels, tree_config, new_level);
+        let left_tree = build_regression_tree_new_partition(
+            left_data,
+            all_labels,
+            all_weights,
+            tree_config,
+            new_level,
+        );
+        let right_tree = build_regression_tree_new_partition(
+            right_data,
+            all_labels,
+            all_weights,
+            tree_config,
+            new_level,
+        );
         TreeNode::new(
             split_result.question,
             Box::new(left_tree),
             Box::new(right_tree),
+            statistics,
         )
     }
 }
@@ -84,12 +123,22 @@ pub(crate) fn build_regression_tree_using_multiple_threads(
     let split_result =
         (tree_config.split_finder.find_best_split)(&data, tree_config.number_of_classes);
     if split_result.gain == 0.0 || current_level == tree_config.max_levels {
+        let statistics = NodeStatistics::leaf(&data.labels, data.weights());
         let leaf = RegressionLeaf { data: Some(data) };
-        return TreeNode::leaf_node(split_result.question, leaf);
+        TreeNode::leaf_node(split_result.question, leaf, statistics)
     } else {
         let partitioned_data = partition(&data, &split_result.question);
         let left_data = partitioned_data.1;
         let right_data = partitioned_data.0;
+        let statistics = NodeStatistics::split(
+            &data.labels,
+            data.weights(),
+            &left_data.labels,
+            left_data.weights(),
+            &right_data.labels,
+            right_data.weights(),
+            split_result.gain,
+        );
 
         let new_level = current_level + 1;
         let (left_tree, right_tree) = rayon::join(
@@ -112,6 +161,7 @@ pub(crate) fn build_regression_tree_using_multiple_threads(
             split_result.question,
             Box::new(left_tree),
             Box::new(right_tree),
+            statistics,
         )
     }
 }
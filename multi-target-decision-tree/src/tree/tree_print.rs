@@ -1,35 +1,179 @@
-use crate::{leaf::RegressionLeaf, node::TreeNode};
-
-pub fn print_tree_regression(
-    root: &Box<TreeNode<RegressionLeaf>>,
-    spacing: String,
-    feature_names: &Vec<String>,
-) {
-    if root.leaf.is_some() {
-        let leaf_ref = &root.leaf.as_ref().unwrap();
-        println!("{} Predict:{:?}", spacing, leaf_ref.data);
-        return;
-    }
-    println!(
-        "{}",
-        format!(
-            "{} {:?}",
-            spacing.clone(),
-            root.question
-                .to_string(&feature_names[root.question.column as usize])
-        )
-    );
-    println!("{}", spacing.clone() + "--> True: ");
-    print_tree_regression(
-        &root.true_branch.as_ref().unwrap(),
-        spacing.clone() + "    ",
-        feature_names,
-    );
-
-    println!("{}", spacing.clone() + "--> False: ");
-    print_tree_regression(
-        &root.false_branch.as_ref().unwrap(),
-        spacing.clone() + "    ",
-        feature_names,
-    );
+use std::{
+    error::Error,
+    io::{self, Write},
+};
+
+use serde_json::{json, Value};
+
+use crate::{leaf::Leaf, node::TreeNode};
+
+// Renders trees as indented text or as JSON, to any writer (e.g io::stdout() or a file).
+// Missing feature names fall back to "feature_{column}", missing target names to indices.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TreeDump<'a> {
+    pub feature_names: &'a [String],
+    pub target_names: &'a [String],
+}
+
+impl TreeDump<'_> {
+    pub fn write_text<L: Leaf, W: Write>(
+        &self,
+        writer: &mut W,
+        root: &TreeNode<L>,
+    ) -> io::Result<()> {
+        self.write_text_node(writer, root, "")
+    }
+
+    // Each tree is preceded by a "Tree {index}:" line
+    pub fn write_text_for_trees<L: Leaf, W: Write>(
+        &self,
+        writer: &mut W,
+        trees: &[Box<TreeNode<L>>],
+    ) -> io::Result<()> {
+        for (tree_index, tree) in trees.iter().enumerate() {
+            writeln!(writer, "Tree {}:", tree_index)?;
+            self.write_text_node(writer, tree, "    ")?;
+        }
+        Ok(())
+    }
+
+    pub fn write_json<L: Leaf, W: Write>(
+        &self,
+        writer: W,
+        root: &TreeNode<L>,
+    ) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer_pretty(writer, &self.to_json(root))?;
+        Ok(())
+    }
+
+    // A JSON array with one entry per tree
+    pub fn write_json_for_trees<L: Leaf, W: Write>(
+        &self,
+        writer: W,
+        trees: &[Box<TreeNode<L>>],
+    ) -> Result<(), Box<dyn Error>> {
+        let json_trees: Vec<Value> = trees.iter().map(|tree| self.to_json(tree)).collect();
+        serde_json::to_writer_pretty(writer, &json_trees)?;
+        Ok(())
+    }
+
+    // Splits have their question and both branches, leaves their value, see Leaf::value_to_json
+    pub fn to_json<L: Leaf>(&self, node: &TreeNode<L>) -> Value {
+        let statistics = &node.statistics;
+        if node.is_leaf_node() {
+            return json!({
+                "id": node.id,
+                "samples": statistics.number_of_samples,
+                "impurity": statistics.impurity,
+                "leaf": node.leaf.as_ref().unwrap().value_to_json(),
+            });
+        }
+        let column = node.question.column as usize;
+        json!({
+            "id": node.id,
+            "samples": statistics.number_of_samples,
+            "impurity": statistics.impurity,
+            "gain": statistics.gain,
+            "feature": column,
+            "feature_name": feature_name(self.feature_names, column),
+            "threshold": node.question.value,
+            "true_branch": self.to_json(node.true_branch.as_ref().unwrap()),
+            "false_branch": self.to_json(node.false_branch.as_ref().unwrap()),
+        })
+    }
+
+    fn write_text_node<L: Leaf, W: Write>(
+        &self,
+        writer: &mut W,
+        node: &TreeNode<L>,
+        spacing: &str,
+    ) -> io::Result<()> {
+        let statistics = &node.statistics;
+        if node.is_leaf_node() {
+            let leaf_value = node.leaf.as_ref().unwrap().format_value(self.target_names);
+            return writeln!(
+                writer,
+                "{}Predict: {} (samples {}, impurity {:.4})",
+                spacing, leaf_value, statistics.number_of_samples, statistics.impurity
+            );
+        }
+        let feature_name = feature_name(self.feature_names, node.question.column as usize);
+        writeln!(
+            writer,
+            "{}{} (samples {}, impurity {:.4})",
+            spacing,
+            node.question.to_string(&feature_name),
+            statistics.number_of_samples,
+            statistics.impurity
+        )?;
+        let child_spacing = format!("{}    ", spacing);
+        writeln!(writer, "{}--> True:", spacing)?;
+        self.write_text_node(writer, node.true_branch.as_ref().unwrap(), &child_spacing)?;
+        writeln!(writer, "{}--> False:", spacing)?;
+        self.write_text_node(writer, node.false_branch.as_ref().unwrap(), &child_spacing)
+    }
+}
+
+// The name of the feature in column, "feature_{column}" when there is no name for it
+pub fn feature_name(feature_names: &[String], column: usize) -> String {
+    match feature_names.get(column) {
+        Some(feature_name) => feature_name.clone(),
+        None => format!("feature_{}", column),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::data_reader::{get_feature_names, read_csv_data_one_hot_multi_target};
+
+    use super::*;
+    use crate::{
+        decision_trees::{RegressionMultiTargetDecisionTree, TreeConfig},
+        split_finder::{SplitFinder, SplitMetric},
+    };
+
+    #[test]
+    fn test_text_and_json_dump_for_iris() {
+        let data_set = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
+        let tree_config = TreeConfig {
+            split_finder: SplitFinder::new(SplitMetric::Variance),
+            use_multi_threading: false,
+            number_of_classes: 3,
+            max_levels: 2,
+        };
+        let tree = RegressionMultiTargetDecisionTree::new(data_set, tree_config);
+        let feature_names = get_feature_names("./../common/data-files/iris.csv");
+        let tree_dump = TreeDump {
+            feature_names: &feature_names,
+            target_names: &[],
+        };
+
+        let mut text_output = vec![];
+        tree_dump.write_text(&mut text_output, &tree.root).unwrap();
+        let text = String::from_utf8(text_output).unwrap();
+        let number_of_leaves = tree.root.leaf_ids().len();
+        assert_eq!(
+            text.lines()
+                .filter(|line| line.contains("Predict: mean ["))
+                .count(),
+            number_of_leaves
+        );
+        assert!(text.starts_with(
+            &tree
+                .root
+                .question
+                .to_string(&feature_names[tree.root.question.column as usize])
+        ));
+
+        let mut json_output = vec![];
+        tree_dump.write_json(&mut json_output, &tree.root).unwrap();
+        let json: Value = serde_json::from_slice(&json_output).unwrap();
+        assert_eq!(json["samples"], 150);
+        assert_eq!(json["threshold"], tree.root.question.value);
+        assert_eq!(
+            json["feature_name"],
+            feature_names[tree.root.question.column as usize]
+        );
+        assert!(json["true_branch"]["id"].is_number());
+    }
 }
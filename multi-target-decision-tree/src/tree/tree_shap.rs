@@ -0,0 +1,585 @@
+use crate::{
+    leaf::Leaf,
+    node::{NodeStatistics, TreeNode},
+};
+
+// Exact TreeSHAP (Lundberg et al., Algorithm 2) for multi-output trees. The training cover
+// recorded on each node (NodeStatistics::cover) is used to weight the branches a
+// row does not follow. Every target is explained at once since the path weights don't depend
+// on the leaf value.
+
+// SHAP values of one row, indexed by feature then target.
+// expected_value + the sum over features equals the explained prediction.
+#[derive(Debug, Clone)]
+pub struct ShapValues {
+    pub expected_value: Vec<f64>,
+    pub values: Vec<Vec<f64>>,
+}
+
+// SHAP interaction values of one row, indexed by feature, feature then target.
+// Summing over the second feature gives the SHAP values, main effects are on the diagonal.
+#[derive(Debug, Clone)]
+pub struct ShapInteractionValues {
+    pub expected_value: Vec<f64>,
+    pub values: Vec<Vec<Vec<f64>>>,
+}
+
+impl ShapValues {
+    pub fn zeros(number_of_features: usize, number_of_targets: usize) -> Self {
+        Self {
+            expected_value: vec![0.; number_of_targets],
+            values: vec![vec![0.; number_of_targets]; number_of_features],
+        }
+    }
+
+    // Adds scale * other, used to sum the trees of an ensemble
+    pub fn add_scaled(&mut self, other: &ShapValues, scale: f64) {
+        add_scaled_slice(&mut self.expected_value, &other.expected_value, scale);
+        for (values, other_values) in self.values.iter_mut().zip(&other.values) {
+            add_scaled_slice(values, other_values, scale);
+        }
+    }
+}
+
+impl ShapInteractionValues {
+    pub fn zeros(number_of_features: usize, number_of_targets: usize) -> Self {
+        Self {
+            expected_value: vec![0.; number_of_targets],
+            values: vec![vec![vec![0.; number_of_targets]; number_of_features]; number_of_features],
+        }
+    }
+
+    pub fn add_scaled(&mut self, other: &ShapInteractionValues, scale: f64) {
+        add_scaled_slice(&mut self.expected_value, &other.expected_value, scale);
+        for (rows, other_rows) in self.values.iter_mut().zip(&other.values) {
+            for (values, other_values) in rows.iter_mut().zip(other_rows) {
+                add_scaled_slice(values, other_values, scale);
+            }
+        }
+    }
+}
+
+// leaf_value gives the output vector of a leaf, e.g its average label or its leaf output
+pub fn calculate_shap_values<L, F>(
+    root: &TreeNode<L>,
+    feature_row: &[f64],
+    leaf_value: F,
+) -> ShapValues
+where
+    L: Leaf,
+    F: Fn(&L) -> Vec<f64>,
+{
+    let expected_value = calculate_expected_value(root, &leaf_value);
+    let mut values = vec![vec![0.; expected_value.len()]; feature_row.len()];
+    let explainer = TreeShapExplainer {
+        feature_row,
+        leaf_value: &leaf_value,
+        condition: Condition::None,
+    };
+    explainer.recurse(root, &[], 1., 1., None, 1., &mut values);
+    ShapValues {
+        expected_value,
+        values,
+    }
+}
+
+// Slower, runs TreeSHAP twice more for every feature the tree splits on
+pub fn calculate_shap_interaction_values<L, F>(
+    root: &TreeNode<L>,
+    feature_row: &[f64],
+    leaf_value: F,
+) -> ShapInteractionValues
+where
+    L: Leaf,
+    F: Fn(&L) -> Vec<f64>,
+{
+    let shap_values = calculate_shap_values(root, feature_row, &leaf_value);
+    let number_of_features = feature_row.len();
+    let number_of_targets = shap_values.expected_value.len();
+    let mut interaction_values =
+        ShapInteractionValues::zeros(number_of_features, number_of_targets);
+    interaction_values.expected_value = shap_values.expected_value;
+
+    let mut split_features = vec![false; number_of_features];
+    mark_split_features(root, &mut split_features);
+    let features_split_on = (0..number_of_features).filter(|feature| split_features[*feature]);
+    for feature in features_split_on {
+        let mut values_with_feature_present = vec![vec![0.; number_of_targets]; number_of_features];
+        let mut values_with_feature_absent = vec![vec![0.; number_of_targets]; number_of_features];
+        for (condition, values) in [
+            (
+                Condition::Present(feature),
+                &mut values_with_feature_present,
+            ),
+            (Condition::Absent(feature), &mut values_with_feature_absent),
+        ] {
+            let explainer = TreeShapExplainer {
+                feature_row,
+                leaf_value: &leaf_value,
+                condition,
+            };
+            explainer.recurse(root, &[], 1., 1., None, 1., values);
+        }
+        for other_feature in 0..number_of_features {
+            if other_feature == feature {
+                continue;
+            }
+            for target in 0..number_of_targets {
+                let interaction = (values_with_feature_present[other_feature][target]
+                    - values_with_feature_absent[other_feature][target])
+                    / 2.;
+                interaction_values.values[feature][other_feature][target] = interaction;
+            }
+        }
+    }
+
+    // main effect is whatever of the SHAP value isn't explained by interactions
+    for feature in 0..number_of_features {
+        for target in 0..number_of_targets {
+            let sum_of_interactions: f64 = (0..number_of_features)
+                .filter(|other_feature| *other_feature != feature)
+                .map(|other_feature| interaction_values.values[feature][other_feature][target])
+                .sum();
+            interaction_values.values[feature][feature][target] =
+                shap_values.values[feature][target] - sum_of_interactions;
+        }
+    }
+    interaction_values
+}
+
+// Cover weighted average of the leaf values, i.e the mean training prediction
+pub fn calculate_expected_value<L, F>(root: &TreeNode<L>, leaf_value: &F) -> Vec<f64>
+where
+    L: Leaf,
+    F: Fn(&L) -> Vec<f64>,
+{
+    let mut expected_value = vec![];
+    add_weighted_leaf_values(root, leaf_value, &root.statistics, &mut expected_value);
+    expected_value
+}
+
+// Share of the node's cover that went down the branch. Nodes only reached by rows of weight 0
+// have no cover and share by the number of samples instead.
+pub(crate) fn cover_fraction(branch: &NodeStatistics, node: &NodeStatistics) -> f64 {
+    if node.cover == 0. {
+        return branch.number_of_samples as f64 / node.number_of_samples as f64;
+    }
+    branch.cover / node.cover
+}
+
+fn add_weighted_leaf_values<L, F>(
+    node: &TreeNode<L>,
+    leaf_value: &F,
+    root_statistics: &NodeStatistics,
+    expected_value: &mut Vec<f64>,
+) where
+    L: Leaf,
+    F: Fn(&L) -> Vec<f64>,
+{
+    if node.is_leaf_node() {
+        let value = leaf_value(node.leaf.as_ref().unwrap());
+        if expected_value.is_empty() {
+            expected_value.resize(value.len(), 0.);
+        }
+        let weight = cover_fraction(&node.statistics, root_statistics);
+        add_scaled_slice(expected_value, &value, weight);
+        return;
+    }
+    add_weighted_leaf_values(
+        node.true_branch.as_ref().unwrap(),
+        leaf_value,
+        root_statistics,
+        expected_value,
+    );
+    add_weighted_leaf_values(
+        node.false_branch.as_ref().unwrap(),
+        leaf_value,
+        root_statistics,
+        expected_value,
+    );
+}
+
+fn mark_split_features<L: Leaf>(node: &TreeNode<L>, split_features: &mut [bool]) {
+    if node.is_leaf_node() {
+        return;
+    }
+    split_features[node.question.column as usize] = true;
+    mark_split_features(node.true_branch.as_ref().unwrap(), split_features);
+    mark_split_features(node.false_branch.as_ref().unwrap(), split_features);
+}
+
+fn add_scaled_slice(sum: &mut [f64], values: &[f64], scale: f64) {
+    for (total, value) in sum.iter_mut().zip(values) {
+        *total += scale * value;
+    }
+}
+
+// Fixes one feature as present or absent, used to compute interaction values
+#[derive(Clone, Copy, PartialEq)]
+enum Condition {
+    None,
+    Present(usize),
+    Absent(usize),
+}
+
+impl Condition {
+    fn feature(&self) -> Option<usize> {
+        match self {
+            Condition::None => None,
+            Condition::Present(feature) | Condition::Absent(feature) => Some(*feature),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct PathElement {
+    feature: Option<usize>,
+    zero_fraction: f64,
+    one_fraction: f64,
+    weight: f64,
+}
+
+struct TreeShapExplainer<'a, F> {
+    feature_row: &'a [f64],
+    leaf_value: &'a F,
+    condition: Condition,
+}
+
+impl<'a, F> TreeShapExplainer<'a, F> {
+    #[allow(clippy::too_many_arguments)]
+    fn recurse<L>(
+        &self,
+        node: &TreeNode<L>,
+        parent_path: &[PathElement],
+        zero_fraction: f64,
+        one_fraction: f64,
+        feature: Option<usize>,
+        condition_fraction: f64,
+        values: &mut [Vec<f64>],
+    ) where
+        L: Leaf,
+        F: Fn(&L) -> Vec<f64>,
+    {
+        // branches no training sample reached and the row doesn't follow add nothing
+        if condition_fraction == 0. || (zero_fraction == 0. && one_fraction == 0.) {
+            return;
+        }
+
+        let mut path = parent_path.to_vec();
+        if self.condition == Condition::None || self.condition.feature() != feature {
+            extend_path(&mut path, zero_fraction, one_fraction, feature);
+        }
+
+        if node.is_leaf_node() {
+            let leaf_value = (self.leaf_value)(node.leaf.as_ref().unwrap());
+            for path_index in 1..path.len() {
+                let element = path[path_index];
+                let weight = calculate_unwound_path_sum(&path, path_index);
+                let scale =
+                    weight * (element.one_fraction - element.zero_fraction) * condition_fraction;
+                add_scaled_slice(&mut values[element.feature.unwrap()], &leaf_value, scale);
+            }
+            return;
+        }
+
+        let split_feature = node.question.column as usize;
+        let true_branch = node.true_branch.as_ref().unwrap();
+        let false_branch = node.false_branch.as_ref().unwrap();
+        let (hot_branch, cold_branch) = match node.question.solve(self.feature_row) {
+            true => (true_branch, false_branch),
+            false => (false_branch, true_branch),
+        };
+        let hot_zero_fraction = cover_fraction(&hot_branch.statistics, &node.statistics);
+        let cold_zero_fraction = cover_fraction(&cold_branch.statistics, &node.statistics);
+
+        // a feature already split on higher up is undone so it only appears once in the path
+        let mut incoming_zero_fraction = 1.;
+        let mut incoming_one_fraction = 1.;
+        if let Some(path_index) = path
+            .iter()
+            .position(|element| element.feature == Some(split_feature))
+        {
+            incoming_zero_fraction = path[path_index].zero_fraction;
+            incoming_one_fraction = path[path_index].one_fraction;
+            unwind_path(&mut path, path_index);
+        }
+
+        let mut hot_condition_fraction = condition_fraction;
+        let mut cold_condition_fraction = condition_fraction;
+        match self.condition {
+            Condition::Present(feature) if feature == split_feature => {
+                cold_condition_fraction = 0.;
+            }
+            Condition::Absent(feature) if feature == split_feature => {
+                hot_condition_fraction *= hot_zero_fraction;
+                cold_condition_fraction *= cold_zero_fraction;
+            }
+            _ => {}
+        }
+
+        self.recurse(
+            hot_branch,
+            &path,
+            hot_zero_fraction * incoming_zero_fraction,
+            incoming_one_fraction,
+            Some(split_feature),
+            hot_condition_fraction,
+            values,
+        );
+        self.recurse(
+            cold_branch,
+            &path,
+            cold_zero_fraction * incoming_zero_fraction,
+            0.,
+            Some(split_feature),
+            cold_condition_fraction,
+            values,
+        );
+    }
+}
+
+fn extend_path(
+    path: &mut Vec<PathElement>,
+    zero_fraction: f64,
+    one_fraction: f64,
+    feature: Option<usize>,
+) {
+    let depth = path.len();
+    path.push(PathElement {
+        feature,
+        zero_fraction,
+        one_fraction,
+        weight: if depth == 0 { 1. } else { 0. },
+    });
+    let new_length = (depth + 1) as f64;
+    for i in (0..depth).rev() {
+        path[i + 1].weight += one_fraction * path[i].weight * (i + 1) as f64 / new_length;
+        path[i].weight = zero_fraction * path[i].weight * (depth - i) as f64 / new_length;
+    }
+}
+
+fn unwind_path(path: &mut Vec<PathElement>, path_index: usize) {
+    let depth = path.len() - 1;
+    let one_fraction = path[path_index].one_fraction;
+    let zero_fraction = path[path_index].zero_fraction;
+    let length = (depth + 1) as f64;
+    let mut next_one_portion = path[depth].weight;
+    for i in (0..depth).rev() {
+        if one_fraction != 0. {
+            let weight = path[i].weight;
+            path[i].weight = next_one_portion * length / ((i + 1) as f64 * one_fraction);
+            next_one_portion =
+                weight - path[i].weight * zero_fraction * (depth - i) as f64 / length;
+        } else {
+            path[i].weight = path[i].weight * length / (zero_fraction * (depth - i) as f64);
+        }
+    }
+    for i in path_index..depth {
+        path[i].feature = path[i + 1].feature;
+        path[i].zero_fraction = path[i + 1].zero_fraction;
+        path[i].one_fraction = path[i + 1].one_fraction;
+    }
+    path.pop();
+}
+
+// Total weight of the path if the element at path_index were unwound from it
+fn calculate_unwound_path_sum(path: &[PathElement], path_index: usize) -> f64 {
+    let depth = path.len() - 1;
+    let one_fraction = path[path_index].one_fraction;
+    let zero_fraction = path[path_index].zero_fraction;
+    let length = (depth + 1) as f64;
+    let mut next_one_portion = path[depth].weight;
+    let mut total = 0.;
+    for i in (0..depth).rev() {
+        if one_fraction != 0. {
+            let weight = next_one_portion * length / ((i + 1) as f64 * one_fraction);
+            total += weight;
+            next_one_portion =
+                path[i].weight - weight * zero_fraction * (depth - i) as f64 / length;
+        } else if zero_fraction != 0. {
+            total += path[i].weight / zero_fraction / ((depth - i) as f64 / length);
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{data_reader::read_csv_data_one_hot_multi_target, datasets::MultiTargetDataSet};
+
+    use super::*;
+    use crate::{
+        calculations::calculate_weighted_mean_vector_of_labels,
+        decision_trees::{RegressionMultiTargetDecisionTree, TreeConfig},
+        leaf::RegressionLeaf,
+        split_finder::{SplitFinder, SplitMetric},
+    };
+    use common::numerical_calculations::{
+        calculate_average_f64_vector, calculate_weighted_average_f64_vector,
+    };
+
+    fn average_label(leaf: &RegressionLeaf) -> Vec<f64> {
+        calculate_average_f64_vector(&leaf.data.as_ref().unwrap().labels)
+    }
+
+    fn build_iris_tree() -> (RegressionMultiTargetDecisionTree, MultiTargetDataSet) {
+        let data_set = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
+        let tree_config = TreeConfig {
+            split_finder: SplitFinder::new(SplitMetric::Variance),
+            use_multi_threading: false,
+            number_of_classes: 3,
+            max_levels: 4,
+        };
+        let tree = RegressionMultiTargetDecisionTree::new(data_set.clone(), tree_config);
+        (tree, data_set)
+    }
+
+    // Expected prediction when only the features in the subset are known, unknown features
+    // follow both branches weighted by cover
+    fn conditional_expectation(
+        node: &TreeNode<RegressionLeaf>,
+        feature_row: &[f64],
+        subset: usize,
+    ) -> Vec<f64> {
+        if node.is_leaf_node() {
+            return average_label(node.leaf.as_ref().unwrap());
+        }
+        let true_branch = node.true_branch.as_ref().unwrap();
+        let false_branch = node.false_branch.as_ref().unwrap();
+        if subset & (1 << node.question.column) != 0 {
+            return match node.question.solve(feature_row) {
+                true => conditional_expectation(true_branch, feature_row, subset),
+                false => conditional_expectation(false_branch, feature_row, subset),
+            };
+        }
+        let true_weight = cover_fraction(&true_branch.statistics, &node.statistics);
+        let false_weight = cover_fraction(&false_branch.statistics, &node.statistics);
+        conditional_expectation(true_branch, feature_row, subset)
+            .iter()
+            .zip(conditional_expectation(false_branch, feature_row, subset))
+            .map(|(true_value, false_value)| true_weight * true_value + false_weight * false_value)
+            .collect()
+    }
+
+    fn factorial(n: usize) -> f64 {
+        (1..=n).product::<usize>() as f64
+    }
+
+    fn brute_force_shap_values(
+        root: &TreeNode<RegressionLeaf>,
+        feature_row: &[f64],
+    ) -> Vec<Vec<f64>> {
+        let number_of_features = feature_row.len();
+        (0..number_of_features)
+            .map(|feature| {
+                let mut shap_value = vec![0.; 3];
+                for subset in 0..(1usize << number_of_features) {
+                    if subset & (1 << feature) != 0 {
+                        continue;
+                    }
+                    let subset_size = subset.count_ones() as usize;
+                    let weight = factorial(subset_size)
+                        * factorial(number_of_features - subset_size - 1)
+                        / factorial(number_of_features);
+                    let with_feature =
+                        conditional_expectation(root, feature_row, subset | (1 << feature));
+                    let without_feature = conditional_expectation(root, feature_row, subset);
+                    for target in 0..3 {
+                        shap_value[target] +=
+                            weight * (with_feature[target] - without_feature[target]);
+                    }
+                }
+                shap_value
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_shap_values_match_brute_force_and_sum_to_prediction() {
+        let (tree, data_set) = build_iris_tree();
+        for feature_row in data_set.feature_rows.iter().step_by(10) {
+            let shap_values = calculate_shap_values(&tree.root, feature_row, average_label);
+            let brute_force = brute_force_shap_values(&tree.root, feature_row);
+            let prediction = tree.predict(feature_row);
+            for target in 0..3 {
+                let mut sum = shap_values.expected_value[target];
+                for (feature_values, brute_force_values) in
+                    shap_values.values.iter().zip(&brute_force)
+                {
+                    let value = feature_values[target];
+                    assert!((value - brute_force_values[target]).abs() < 1e-10);
+                    sum += value;
+                }
+                assert!((sum - prediction[target]).abs() < 1e-10);
+            }
+        }
+    }
+
+    #[test]
+    fn test_weighted_tree_uses_weighted_cover() {
+        let (_, data_set) = build_iris_tree();
+        // every fourth row has weight 0
+        let weights: Vec<f64> = (0..data_set.number_of_rows())
+            .map(|row| (row % 4) as f64)
+            .collect();
+        let data_set = data_set.with_weights(weights.clone());
+        let tree_config = TreeConfig {
+            split_finder: SplitFinder::new(SplitMetric::Variance),
+            use_multi_threading: false,
+            number_of_classes: 3,
+            max_levels: 4,
+        };
+        let tree = RegressionMultiTargetDecisionTree::new(data_set.clone(), tree_config);
+        let weighted_average_label = |leaf: &RegressionLeaf| {
+            let leaf_data = leaf.data.as_ref().unwrap();
+            calculate_weighted_mean_vector_of_labels(&leaf_data.labels, leaf_data.weights(), 3)
+        };
+
+        let weighted_average_prediction =
+            calculate_weighted_average_f64_vector(&data_set.labels, Some(&weights));
+        for feature_row in data_set.feature_rows.iter().step_by(10) {
+            let shap_values =
+                calculate_shap_values(&tree.root, feature_row, weighted_average_label);
+            let prediction = tree.predict(feature_row);
+            for target in 0..3 {
+                let expected_value = shap_values.expected_value[target];
+                assert!((expected_value - weighted_average_prediction[target]).abs() < 1e-10);
+                let sum: f64 = shap_values
+                    .values
+                    .iter()
+                    .map(|feature_values| feature_values[target])
+                    .sum();
+                assert!((expected_value + sum - prediction[target]).abs() < 1e-10);
+            }
+        }
+    }
+
+    #[test]
+    fn test_shap_interaction_values_sum_to_shap_values() {
+        let (tree, data_set) = build_iris_tree();
+        let average_prediction = calculate_average_f64_vector(&data_set.labels);
+        for feature_row in data_set.feature_rows.iter().step_by(25) {
+            let shap_values = calculate_shap_values(&tree.root, feature_row, average_label);
+            let interaction_values =
+                calculate_shap_interaction_values(&tree.root, feature_row, average_label);
+            for target in 0..3 {
+                assert!(
+                    (interaction_values.expected_value[target] - average_prediction[target]).abs()
+                        < 1e-10
+                );
+                for feature in 0..feature_row.len() {
+                    let row_sum: f64 = interaction_values.values[feature]
+                        .iter()
+                        .map(|values| values[target])
+                        .sum();
+                    assert!((row_sum - shap_values.values[feature][target]).abs() < 1e-10);
+                    for other_feature in 0..feature_row.len() {
+                        let interaction = interaction_values.values[feature][other_feature][target];
+                        let mirrored = interaction_values.values[other_feature][feature][target];
+                        assert!((interaction - mirrored).abs() < 1e-10);
+                    }
+                }
+            }
+        }
+    }
+}
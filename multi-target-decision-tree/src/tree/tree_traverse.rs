@@ -0,0 +1,186 @@
+use common::question::Question;
+
+use crate::{leaf::Leaf, node::TreeNode, printer::feature_name};
+
+pub fn find_leaf_node_for_data<'a, L: Leaf>(feature_row: &[f64], node: &'a TreeNode<L>) -> &'a L {
+    if !node.is_leaf_node() {
+        if node.question.solve(feature_row) {
+            return find_leaf_node_for_data(feature_row, node.true_branch.as_ref().unwrap());
+        } else {
+            return find_leaf_node_for_data(feature_row, node.false_branch.as_ref().unwrap());
+        }
+    }
+    node.leaf.as_ref().unwrap()
+}
+
+// Id of the leaf node the row ends up in, see TreeNode::assign_node_ids
+pub fn find_leaf_node_id_for_data<L: Leaf>(feature_row: &[f64], node: &TreeNode<L>) -> usize {
+    if !node.is_leaf_node() {
+        if node.question.solve(feature_row) {
+            return find_leaf_node_id_for_data(feature_row, node.true_branch.as_ref().unwrap());
+        } else {
+            return find_leaf_node_id_for_data(feature_row, node.false_branch.as_ref().unwrap());
+        }
+    }
+    node.id
+}
+
+// One split on the way to a leaf, answer is whether the row satisfied the question
+#[derive(Debug, Clone)]
+pub struct DecisionStep {
+    pub node_id: usize,
+    pub question: Question,
+    pub answer: bool,
+    pub number_of_samples: usize,
+    pub mean_label: Vec<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DecisionPath {
+    pub steps: Vec<DecisionStep>,
+    pub leaf_id: usize,
+    pub leaf_number_of_samples: usize,
+    pub leaf_mean_label: Vec<f64>,
+}
+
+impl DecisionPath {
+    // One line per step, questions are written as in Question::to_string
+    pub fn to_string(&self, feature_names: &[String]) -> String {
+        let mut lines: Vec<String> = self
+            .steps
+            .iter()
+            .map(|step| {
+                format!(
+                    "{}? {} ({} samples, mean label {:?})",
+                    step.question
+                        .to_string(&feature_name(feature_names, step.question.column as usize)),
+                    step.answer,
+                    step.number_of_samples,
+                    step.mean_label
+                )
+            })
+            .collect();
+        lines.push(format!(
+            "Leaf {} ({} samples, mean label {:?})",
+            self.leaf_id, self.leaf_number_of_samples, self.leaf_mean_label
+        ));
+        lines.join("\n")
+    }
+}
+
+pub fn find_decision_path<L: Leaf>(feature_row: &[f64], root: &TreeNode<L>) -> DecisionPath {
+    let mut steps = vec![];
+    let mut node = root;
+    while !node.is_leaf_node() {
+        let answer = node.question.solve(feature_row);
+        steps.push(DecisionStep {
+            node_id: node.id,
+            question: node.question.clone(),
+            answer,
+            number_of_samples: node.statistics.number_of_samples,
+            mean_label: node.statistics.mean_label.clone(),
+        });
+        node = match answer {
+            true => node.true_branch.as_ref().unwrap(),
+            false => node.false_branch.as_ref().unwrap(),
+        };
+    }
+    DecisionPath {
+        steps,
+        leaf_id: node.id,
+        leaf_number_of_samples: node.statistics.number_of_samples,
+        leaf_mean_label: node.statistics.mean_label.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{
+        data_reader::{get_feature_names, read_csv_data_one_hot_multi_target},
+        numerical_calculations::calculate_weighted_average_f64_vector,
+    };
+
+    use super::*;
+    use crate::{
+        decision_trees::{RegressionMultiTargetDecisionTree, TreeConfig},
+        split_finder::{SplitFinder, SplitMetric},
+    };
+
+    #[test]
+    fn test_decision_path_ends_in_predicted_leaf() {
+        let data_set = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
+        let tree_config = TreeConfig {
+            split_finder: SplitFinder::new(SplitMetric::Variance),
+            use_multi_threading: false,
+            number_of_classes: 3,
+            max_levels: 3,
+        };
+        let tree = RegressionMultiTargetDecisionTree::new(data_set.clone(), tree_config);
+        let feature_row = &data_set.feature_rows[120];
+
+        let decision_path = tree.decision_path(feature_row);
+        assert_eq!(decision_path.steps[0].node_id, 0);
+        assert_eq!(decision_path.steps[0].number_of_samples, 150);
+        assert_eq!(decision_path.steps[0].mean_label.len(), 3);
+        assert_eq!(
+            decision_path.leaf_id,
+            tree.apply(std::slice::from_ref(feature_row))[0]
+        );
+        assert_eq!(decision_path.leaf_mean_label, tree.predict(feature_row));
+        let mut previous_number_of_samples = usize::MAX;
+        for step in &decision_path.steps {
+            assert_eq!(step.answer, step.question.solve(feature_row));
+            assert!(step.number_of_samples <= previous_number_of_samples);
+            previous_number_of_samples = step.number_of_samples;
+        }
+
+        let feature_names = get_feature_names("./../common/data-files/iris.csv");
+        let explanation = decision_path.to_string(&feature_names);
+        assert_eq!(explanation.lines().count(), decision_path.steps.len() + 1);
+        assert!(explanation.starts_with(
+            &decision_path.steps[0]
+                .question
+                .to_string(&feature_names[decision_path.steps[0].question.column as usize])
+        ));
+
+        // features without a name fall back to feature_{column}
+        let short_explanation = decision_path.to_string(&feature_names[..1]);
+        assert_eq!(
+            short_explanation.lines().count(),
+            decision_path.steps.len() + 1
+        );
+        assert!(decision_path.to_string(&[]).starts_with(&format!(
+            "Is feature_{} >=",
+            decision_path.steps[0].question.column
+        )));
+    }
+
+    #[test]
+    fn test_decision_path_statistics_are_weighted() {
+        let data_set = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
+        let weights: Vec<f64> = (0..data_set.number_of_rows())
+            .map(|row| (row % 3) as f64)
+            .collect();
+        let data_set = data_set.with_weights(weights.clone());
+        let tree_config = TreeConfig {
+            split_finder: SplitFinder::new(SplitMetric::Variance),
+            use_multi_threading: false,
+            number_of_classes: 3,
+            max_levels: 3,
+        };
+        let tree = RegressionMultiTargetDecisionTree::new(data_set.clone(), tree_config);
+        let feature_row = &data_set.feature_rows[120];
+
+        let decision_path = tree.decision_path(feature_row);
+        assert_eq!(tree.root.statistics.cover, weights.iter().sum::<f64>());
+        assert_eq!(decision_path.steps[0].number_of_samples, 150);
+        let weighted_mean_label =
+            calculate_weighted_average_f64_vector(&data_set.labels, Some(&weights));
+        decision_path.steps[0]
+            .mean_label
+            .iter()
+            .zip(weighted_mean_label)
+            .for_each(|(actual, expected)| assert!((actual - expected).abs() < 1e-12));
+        assert_eq!(decision_path.leaf_mean_label, tree.predict(feature_row));
+    }
+}
@@ -48,16 +48,18 @@ fn calculate_variance(mean_squared: f64, square_sum: f64, number_of_labels: f64)
     numerator / number_of_labels
 }
 
-pub fn get_multi_target_label_metrics(
-    labels: &Vec<Vec<f64>>,
+// Sums of the weighted labels, the mean divides by the total weight instead of the number of labels
+pub fn get_weighted_multi_target_label_metrics<T: AsRef<[f64]>>(
+    labels: &[T],
+    weights: Option<&[f64]>,
     number_of_targets: usize,
 ) -> MultiTargetLabelMetrics {
-    let label_sum_vectors = get_label_sum_vectors(labels, number_of_targets);
+    let label_sum_vectors = get_label_sum_vectors(labels, weights, number_of_targets);
     let sum_of_labels_vector = label_sum_vectors.0;
     let sum_of_squared_labels_vector = label_sum_vectors.1;
-    let number_of_labels = labels.len() as f64;
+    let total_weight = calculate_total_weight(weights, labels.len());
     let mean_of_labels_vector =
-        get_mean_of_labels_vector(number_of_labels, number_of_targets, &sum_of_labels_vector);
+        get_mean_of_labels_vector(total_weight, number_of_targets, &sum_of_labels_vector);
     MultiTargetLabelMetrics {
         sum_of_labels_vector,
         sum_of_squared_labels_vector,
@@ -65,45 +67,26 @@ pub fn get_multi_target_label_metrics(
     }
 }
 
-pub fn get_multi_target_label_metrics_new_partition(
-    labels: &Vec<&Vec<f64>>,
-    number_of_targets: usize,
-) -> MultiTargetLabelMetrics {
-    let label_sum_vectors = get_label_sum_vectors_new_partition(labels, number_of_targets);
-    let sum_of_labels_vector = label_sum_vectors.0;
-    let sum_of_squared_labels_vector = label_sum_vectors.1;
-    let number_of_labels = labels.len() as f64;
-    let mean_of_labels_vector =
-        get_mean_of_labels_vector(number_of_labels, number_of_targets, &sum_of_labels_vector);
-    MultiTargetLabelMetrics {
-        sum_of_labels_vector,
-        sum_of_squared_labels_vector,
-        mean_of_labels_vector,
+// The number of labels when unweighted
+pub fn calculate_total_weight(weights: Option<&[f64]>, number_of_labels: usize) -> f64 {
+    match weights {
+        Some(weights) => weights.iter().sum(),
+        None => number_of_labels as f64,
     }
 }
 
-fn get_label_sum_vectors_new_partition(
-    labels: &Vec<&Vec<f64>>,
+fn get_label_sum_vectors<T: AsRef<[f64]>>(
+    labels: &[T],
+    weights: Option<&[f64]>,
     number_of_targets: usize,
 ) -> (Vec<f64>, Vec<f64>) {
     let mut sum_of_labels_vector = vec![0.0; number_of_targets];
     let mut sum_of_squared_labels_vector = vec![0.0; number_of_targets];
-    labels.iter().for_each(|label_vector| {
-        for (i, label_value) in label_vector.iter().enumerate() {
-            sum_of_labels_vector[i] += label_value;
-            sum_of_squared_labels_vector[i] += label_value * label_value;
-        }
-    });
-    (sum_of_labels_vector, sum_of_squared_labels_vector)
-}
-
-fn get_label_sum_vectors(labels: &Vec<Vec<f64>>, number_of_targets: usize) -> (Vec<f64>, Vec<f64>) {
-    let mut sum_of_labels_vector = vec![0.0; number_of_targets];
-    let mut sum_of_squared_labels_vector = vec![0.0; number_of_targets];
-    labels.iter().for_each(|label_vector| {
-        for (i, label_value) in label_vector.iter().enumerate() {
-            sum_of_labels_vector[i] += label_value;
-            sum_of_squared_labels_vector[i] += label_value * label_value;
+    labels.iter().enumerate().for_each(|(row, label_vector)| {
+        let weight = weights.map_or(1., |weights| weights[row]);
+        for (i, label_value) in label_vector.as_ref().iter().enumerate() {
+            sum_of_labels_vector[i] += weight * label_value;
+            sum_of_squared_labels_vector[i] += weight * label_value * label_value;
         }
     });
     (sum_of_labels_vector, sum_of_squared_labels_vector)
@@ -121,6 +104,71 @@ fn get_mean_of_labels_vector(
     mean_of_labels_vector
 }
 
+// Weights that are all 0 would give 0/0, the unweighted statistics are used instead
+pub fn weights_with_positive_total(weights: Option<&[f64]>) -> Option<&[f64]> {
+    weights.filter(|weights| weights.iter().any(|weight| *weight != 0.))
+}
+
+pub fn calculate_weighted_mean_vector_of_labels<T: AsRef<[f64]>>(
+    labels: &[T],
+    weights: Option<&[f64]>,
+    number_of_targets: usize,
+) -> Vec<f64> {
+    let weights = weights_with_positive_total(weights);
+    get_weighted_multi_target_label_metrics(labels, weights, number_of_targets)
+        .mean_of_labels_vector
+}
+
+// Population variance of each target over the given label vectors
+pub fn calculate_weighted_variance_vector_of_labels<T: AsRef<[f64]>>(
+    labels: &[T],
+    weights: Option<&[f64]>,
+    number_of_targets: usize,
+) -> Vec<f64> {
+    let weights = weights_with_positive_total(weights);
+    let multi_target_label_metrics =
+        get_weighted_multi_target_label_metrics(labels, weights, number_of_targets);
+    calculate_variance_vector(
+        &multi_target_label_metrics,
+        calculate_total_weight(weights, labels.len()),
+        number_of_targets,
+    )
+}
+
+// Decrease in variance of each target from splitting labels into true and false labels, the
+// branches' variances are weighted by their total weight
+pub fn calculate_weighted_gain_vector<T: AsRef<[f64]>>(
+    labels: &[T],
+    weights: Option<&[f64]>,
+    true_labels: &[T],
+    true_weights: Option<&[f64]>,
+    false_labels: &[T],
+    false_weights: Option<&[f64]>,
+) -> Vec<f64> {
+    let (true_weights, false_weights) = match weights_with_positive_total(weights) {
+        Some(_) => (true_weights, false_weights),
+        None => (None, None),
+    };
+    let number_of_targets = labels[0].as_ref().len();
+    let variance_vector =
+        calculate_weighted_variance_vector_of_labels(labels, weights, number_of_targets);
+    let loss_vector = calculate_loss_vector(
+        calculate_weighted_variance_vector_of_labels(true_labels, true_weights, number_of_targets),
+        calculate_weighted_variance_vector_of_labels(
+            false_labels,
+            false_weights,
+            number_of_targets,
+        ),
+        calculate_total_weight(true_weights, true_labels.len()),
+        calculate_total_weight(false_weights, false_labels.len()),
+    );
+    variance_vector
+        .iter()
+        .zip(loss_vector)
+        .map(|(variance, loss)| variance - loss)
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -128,7 +176,7 @@ mod tests {
     #[test]
     fn test_get_label_sums() {
         let labels = vec![vec![1., 3., 4.], vec![12., 5., 3.], vec![3., 5., 7.]];
-        let label_metrics = super::get_multi_target_label_metrics(&labels, 3);
+        let label_metrics = super::get_weighted_multi_target_label_metrics(&labels, None, 3);
         println!("{:?}", label_metrics);
     }
 
@@ -136,7 +184,8 @@ mod tests {
     fn test_calculate_variance_vector() {
         let labels = vec![vec![1., 3., 4.], vec![2., 5., 3.], vec![3., -5., 7.]];
         let number_of_targets = 3;
-        let label_metrics = super::get_multi_target_label_metrics(&labels, number_of_targets);
+        let label_metrics =
+            super::get_weighted_multi_target_label_metrics(&labels, None, number_of_targets);
         let variance_vector = calculate_variance_vector(
             &label_metrics,
             label_metrics.sum_of_labels_vector.len() as f64,
@@ -146,4 +195,47 @@ mod tests {
         println!("{:?}", variance_vector);
         assert_eq!(variance_vector[0], 2.0 / 3.0);
     }
+
+    #[test]
+    fn test_calculate_gain_vector() {
+        let labels = vec![vec![1., 0.], vec![1., 0.], vec![0., 1.], vec![0., 3.]];
+        let true_labels = vec![labels[0].clone(), labels[1].clone()];
+        let false_labels = vec![labels[2].clone(), labels[3].clone()];
+        let gain_vector =
+            calculate_weighted_gain_vector(&labels, None, &true_labels, None, &false_labels, None);
+        // all variance of the first target is removed, the second keeps the variance of [1, 3]
+        assert_eq!(gain_vector[0], 0.25);
+        assert_eq!(gain_vector[1], 1.5 - 0.5);
+
+        // the second row counts twice, the third not at all
+        let weights = [1., 2., 0., 1.];
+        let weighted_gain_vector = calculate_weighted_gain_vector(
+            &labels,
+            Some(&weights),
+            &true_labels,
+            Some(&weights[..2]),
+            &false_labels,
+            Some(&weights[2..]),
+        );
+        assert_eq!(weighted_gain_vector[0], 0.1875);
+        assert_eq!(weighted_gain_vector[1], 27. / 16.);
+    }
+
+    #[test]
+    fn test_zero_weights_fall_back_to_unweighted() {
+        let labels = vec![vec![1., 0.], vec![3., 2.]];
+        let zero_weights = [0., 0.];
+        assert_eq!(
+            calculate_weighted_mean_vector_of_labels(&labels, Some(&zero_weights), 2),
+            vec![2., 1.]
+        );
+        assert_eq!(
+            calculate_weighted_variance_vector_of_labels(&labels, Some(&zero_weights), 2),
+            vec![1., 1.]
+        );
+        assert_eq!(
+            calculate_weighted_mean_vector_of_labels(&labels, Some(&[0., 1.]), 2),
+            vec![3., 2.]
+        );
+    }
 }
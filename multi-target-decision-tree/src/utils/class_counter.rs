@@ -1,12 +1,13 @@
 #[derive(Debug, Clone)]
 pub struct ClassCounter {
-    pub counts: Vec<u32>,
+    // Sum of the weights of the rows of each class
+    pub counts: Vec<f64>,
 }
 
 impl ClassCounter {
     pub fn new(number_of_classes: usize) -> Self {
         Self {
-            counts: vec![0; number_of_classes],
+            counts: vec![0.; number_of_classes],
         }
     }
 }
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use common::datasets::SparseBinaryMatrix;
+
+use crate::{leaf::Leaf, node::TreeNode};
+
+// Maps the leaf ids reached in each tree of an ensemble onto one column per leaf, so the
+// output of `apply` can be used as one-hot features for a downstream model (GBDT + LR).
+// Columns are ordered by tree, then by leaf id.
+pub struct LeafOneHotEncoder {
+    leaf_columns_per_tree: Vec<HashMap<usize, usize>>,
+    column_names: Vec<String>,
+}
+
+impl LeafOneHotEncoder {
+    pub fn new<L: Leaf>(trees: &[Box<TreeNode<L>>]) -> Self {
+        let mut leaf_columns_per_tree = Vec::with_capacity(trees.len());
+        let mut column_names = vec![];
+        for (tree_index, tree) in trees.iter().enumerate() {
+            let mut leaf_columns = HashMap::new();
+            for leaf_id in tree.leaf_ids() {
+                leaf_columns.insert(leaf_id, column_names.len());
+                column_names.push(format!("tree_{}_leaf_{}", tree_index, leaf_id));
+            }
+            leaf_columns_per_tree.push(leaf_columns);
+        }
+        Self {
+            leaf_columns_per_tree,
+            column_names,
+        }
+    }
+
+    pub fn number_of_columns(&self) -> usize {
+        self.column_names.len()
+    }
+
+    pub fn column_names(&self) -> &[String] {
+        &self.column_names
+    }
+
+    // leaf_ids holds one row per instance and one leaf id per tree, as returned by `apply`
+    pub fn transform(&self, leaf_ids: &[Vec<usize>]) -> SparseBinaryMatrix {
+        let rows = leaf_ids
+            .iter()
+            .map(|row_leaf_ids| {
+                assert_eq!(row_leaf_ids.len(), self.leaf_columns_per_tree.len());
+                row_leaf_ids
+                    .iter()
+                    .zip(&self.leaf_columns_per_tree)
+                    .map(|(leaf_id, leaf_columns)| leaf_columns[leaf_id])
+                    .collect()
+            })
+            .collect();
+        SparseBinaryMatrix {
+            number_of_columns: self.number_of_columns(),
+            rows,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::data_reader::read_csv_data_one_hot_multi_target;
+
+    use super::*;
+    use crate::{
+        decision_trees::{RegressionMultiTargetDecisionTree, TreeConfig},
+        split_finder::{SplitFinder, SplitMetric},
+        tree_traverse::find_leaf_node_id_for_data,
+    };
+
+    #[test]
+    fn test_leaf_one_hot_encoding_iris() {
+        let data_set = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
+        let tree_config = TreeConfig {
+            split_finder: SplitFinder::new(SplitMetric::Variance),
+            use_multi_threading: false,
+            number_of_classes: 3,
+            max_levels: 2,
+        };
+        let rows = data_set.feature_rows.clone();
+        let tree = RegressionMultiTargetDecisionTree::new(data_set, tree_config);
+        let trees = vec![Box::new(tree.root)];
+
+        let encoder = LeafOneHotEncoder::new(&trees);
+        let number_of_leaves = trees[0].leaf_ids().len();
+        assert_eq!(encoder.number_of_columns(), number_of_leaves);
+
+        let leaf_ids: Vec<Vec<usize>> = rows
+            .iter()
+            .map(|row| vec![find_leaf_node_id_for_data(row, &trees[0])])
+            .collect();
+        let matrix = encoder.transform(&leaf_ids);
+        assert_eq!(matrix.rows.len(), rows.len());
+        assert!(matrix.rows.iter().all(|row| row.len() == 1));
+        assert!(matrix
+            .rows
+            .iter()
+            .all(|row| row[0] < encoder.number_of_columns()));
+    }
+}
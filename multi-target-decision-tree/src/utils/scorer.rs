@@ -2,10 +2,13 @@ use crate::{
     class_counter::ClassCounter,
     leaf::{Leaf, RegressionLeaf},
     node::TreeNode,
+    tree_traverse::find_leaf_node_for_data,
 };
 use common::datasets::MultiTargetDataSet;
 
 pub mod classification {
+    use common::metrics::classification::ClassificationReport;
+
     use super::*;
 
     pub fn calculate_accuracy(
@@ -17,21 +20,33 @@ pub mod classification {
             let prediction = predict_class(&test_data.feature_rows[i], tree_root);
             let actual = &test_data.labels[i];
             if prediction == *actual {
-                accuracy += 1.;
+                accuracy += test_data.weight(i);
             } else {
                 //for debugging - print incorrect classifications
                 //println!("Prediction: {:?}, Actual: {:?}", prediction, actual);
             }
         }
-        accuracy / test_data.feature_rows.len() as f64
+        accuracy / test_data.total_weight()
+    }
+
+    pub fn calculate_classification_report(
+        test_data: &MultiTargetDataSet,
+        tree_root: &TreeNode<RegressionLeaf>,
+    ) -> ClassificationReport {
+        let predictions: Vec<Vec<f64>> = test_data
+            .feature_rows
+            .iter()
+            .map(|feature_row| predict_class(feature_row, tree_root))
+            .collect();
+        ClassificationReport::new_weighted(&predictions, &test_data.labels, test_data.weights())
     }
 
-    pub fn predict_class(feature_row: &Vec<f64>, node: &Box<TreeNode<RegressionLeaf>>) -> Vec<f64> {
+    pub fn predict_class(feature_row: &[f64], node: &TreeNode<RegressionLeaf>) -> Vec<f64> {
         let leaf = find_leaf_node_for_data(feature_row, node);
-        let labels = &leaf.data.as_ref().unwrap().labels;
-        let number_of_classes = labels[0].len();
-        let leaf_class_counts = get_class_counts_multi_target(labels, number_of_classes);
-        let mut max = 0;
+        let leaf_data = leaf.data.as_ref().unwrap();
+        let number_of_classes = leaf_data.labels[0].len();
+        let leaf_class_counts = get_class_counts_multi_target(leaf_data, number_of_classes);
+        let mut max = 0.;
         let mut max_class = 0.;
         let mut index = 0.;
         leaf_class_counts.counts.iter().for_each(|count| {
@@ -47,108 +62,76 @@ pub mod classification {
     }
 
     fn get_class_counts_multi_target(
-        labels: &Vec<Vec<f64>>,
+        leaf_data: &MultiTargetDataSet,
         number_of_classes: usize,
     ) -> ClassCounter {
         let mut class_counter = ClassCounter::new(number_of_classes);
-        labels.iter().for_each(|label_vector| {
-            for i in 0..label_vector.len() {
-                if label_vector[i] == 1. {
-                    class_counter.counts[i] += 1;
+        leaf_data
+            .labels
+            .iter()
+            .enumerate()
+            .for_each(|(row, label_vector)| {
+                for (count, label) in class_counter.counts.iter_mut().zip(label_vector) {
+                    if *label == 1. {
+                        *count += leaf_data.weight(row);
+                    }
                 }
-            }
-        });
+            });
         class_counter
     }
 }
 
 pub mod regression {
-    use crate::leaf::RegressionLeafNewPartition;
+    use common::metrics::regression::calculate_weighted_mean_squared_error;
 
     use super::*;
+    use crate::{
+        calculations::calculate_weighted_mean_vector_of_labels, leaf::RegressionLeafNewPartition,
+    };
 
     pub fn calculate_overall_mean_squared_error(
         test_data: &MultiTargetDataSet,
         tree_root: &Box<TreeNode<RegressionLeaf>>,
     ) -> f64 {
-        let mut total_error = 0.;
-        for i in 0..test_data.feature_rows.len() {
-            let leaf = find_leaf_node_for_data(&test_data.feature_rows[i], tree_root);
-            let prediction = calculate_average_label_vector(leaf.data.as_ref().unwrap());
-            let actual = &test_data.labels[i];
-            total_error += mean_sum_of_squared_differences_between_vectors(&prediction, actual);
-        }
-        total_error / test_data.feature_rows.len() as f64
+        let predictions: Vec<Vec<f64>> = test_data
+            .feature_rows
+            .iter()
+            .map(|feature_row| {
+                let leaf_data = find_leaf_node_for_data(feature_row, tree_root)
+                    .data
+                    .as_ref()
+                    .unwrap();
+                calculate_weighted_mean_vector_of_labels(
+                    &leaf_data.labels,
+                    leaf_data.weights(),
+                    leaf_data.number_of_targets(),
+                )
+            })
+            .collect();
+        calculate_weighted_mean_squared_error(&predictions, &test_data.labels, test_data.weights())
     }
 
     pub fn calculate_overall_mean_squared_error_new_partition(
         test_data: &MultiTargetDataSet,
         tree_root: &Box<TreeNode<RegressionLeafNewPartition>>,
     ) -> f64 {
-        let mut total_error = 0.;
-        for i in 0..test_data.feature_rows.len() {
-            let leaf = find_leaf_node_for_data(&test_data.feature_rows[i], tree_root);
-            let prediction = {
-                let data = leaf.data.as_ref().unwrap();
-                let labels = &data.labels;
-                let label_length = data.labels[0].len();
-                let mut average_vector = vec![0.; label_length];
-                for i in 0..data.labels.len() {
-                    for j in 0..label_length {
-                        average_vector[j] += labels[i][j];
-                    }
-                }
-                for j in 0..label_length {
-                    average_vector[j] /= labels.len() as f64;
-                }
-                average_vector
-            };
-            let actual = &test_data.labels[i];
-            total_error += mean_sum_of_squared_differences_between_vectors(&prediction, actual);
-        }
-        total_error / test_data.feature_rows.len() as f64
-    }
-
-    fn calculate_average_label_vector(data: &MultiTargetDataSet) -> Vec<f64> {
-        let labels = &data.labels;
-        let label_length = data.labels[0].len();
-        let mut average_vector = vec![0.; label_length];
-        for i in 0..data.labels.len() {
-            for j in 0..label_length {
-                average_vector[j] += labels[i][j];
-            }
-        }
-        for j in 0..label_length {
-            average_vector[j] /= labels.len() as f64;
-        }
-        average_vector
-    }
-
-    fn mean_sum_of_squared_differences_between_vectors(
-        prediction: &Vec<f64>,
-        actual: &Vec<f64>,
-    ) -> f64 {
-        let mut sum_of_squared_differences = 0.;
-        for i in 0..prediction.len() {
-            let error = prediction[i] - actual[i];
-            sum_of_squared_differences += f64::powf(error, 2.);
-        }
-        sum_of_squared_differences / prediction.len() as f64
-    }
-}
-
-fn find_leaf_node_for_data<'a, L: Leaf>(
-    feature_row: &Vec<f64>,
-    node: &'a Box<TreeNode<L>>,
-) -> &'a L {
-    if !node.is_leaf_node() {
-        if node.question.solve(feature_row) {
-            return find_leaf_node_for_data(feature_row, &node.true_branch.as_ref().unwrap());
-        } else {
-            return find_leaf_node_for_data(feature_row, &node.false_branch.as_ref().unwrap());
-        }
+        let predictions: Vec<Vec<f64>> = test_data
+            .feature_rows
+            .iter()
+            .map(|feature_row| {
+                let leaf_data = find_leaf_node_for_data(feature_row, tree_root)
+                    .data
+                    .as_ref()
+                    .unwrap();
+                calculate_weighted_mean_vector_of_labels(
+                    &leaf_data.labels,
+                    leaf_data.weights.as_deref(),
+                    leaf_data.labels[0].len(),
+                )
+            })
+            .collect();
+        calculate_weighted_mean_squared_error(&predictions, &test_data.labels, test_data.weights())
     }
-    node.leaf.as_ref().unwrap()
 }
 
 #[cfg(test)]
@@ -3,9 +3,10 @@ use common::data_reader::{
 };
 use multi_target_decision_tree::{
     decision_trees::{RegressionMultiTargetDecisionTree, TreeConfig},
-    printer::print_tree_regression,
+    printer::TreeDump,
     scorer::{
-        classification::calculate_accuracy, regression::calculate_overall_mean_squared_error,
+        classification::{calculate_accuracy, calculate_classification_report},
+        regression::calculate_overall_mean_squared_error,
     },
     split_finder::{SplitFinder, SplitMetric},
 };
@@ -197,7 +198,13 @@ fn print_tree_for_wine() {
 
     let tree = RegressionMultiTargetDecisionTree::new(data_set, tree_config);
     let feature_names = get_feature_names("./../common/data-files/wine_train.csv");
-    print_tree_regression(&Box::new(tree.root), "".to_string(), &feature_names);
+    let tree_dump = TreeDump {
+        feature_names: &feature_names,
+        target_names: &[],
+    };
+    tree_dump
+        .write_text(&mut std::io::stdout(), &tree.root)
+        .unwrap();
 }
 
 #[test]
@@ -216,5 +223,64 @@ fn print_tree_for_synthetic() {
 
     let tree = RegressionMultiTargetDecisionTree::new(data_set, tree_config);
     let feature_names = get_feature_names("./../common/data-files/synthetic_1.csv");
-    print_tree_regression(&Box::new(tree.root), "".to_string(), &feature_names);
+    let tree_dump = TreeDump {
+        feature_names: &feature_names,
+        target_names: &[],
+    };
+    tree_dump
+        .write_text(&mut std::io::stdout(), &tree.root)
+        .unwrap();
+}
+
+#[test]
+fn test_decision_tree_predict_batch_for_wine() {
+    let data_set = read_csv_data_one_hot_multi_target("./../common/data-files/wine_train.csv", 3);
+
+    let number_of_classes = data_set.labels[0].len() as u32;
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes,
+        max_levels: 4,
+    };
+
+    let tree = RegressionMultiTargetDecisionTree::new(data_set, tree_config);
+    let test_set = read_csv_data_one_hot_multi_target("./../common/data-files/wine_test.csv", 3);
+    let predictions = tree.predict_batch(&test_set.feature_rows);
+    assert_eq!(predictions.len(), test_set.feature_rows.len());
+    for (prediction, row) in predictions.iter().zip(&test_set.feature_rows) {
+        assert_eq!(*prediction, tree.predict(row));
+        let sum_of_class_proportions: f64 = prediction.iter().sum();
+        assert!((sum_of_class_proportions - 1.).abs() < 1e-10);
+    }
+}
+
+#[test]
+fn test_classification_report_for_wine() {
+    let data_set = read_csv_data_one_hot_multi_target("./../common/data-files/wine_train.csv", 3);
+
+    let number_of_classes = data_set.labels[0].len() as u32;
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes,
+        max_levels: 4,
+    };
+
+    let tree = RegressionMultiTargetDecisionTree::new(data_set, tree_config);
+    let boxed_tree = Box::new(tree.root);
+    let test_set = read_csv_data_one_hot_multi_target("./../common/data-files/wine_test.csv", 3);
+    let report = calculate_classification_report(&test_set, &boxed_tree);
+    println!("{}", report.to_string(&[]));
+    assert_eq!(report.accuracy, calculate_accuracy(&test_set, &boxed_tree));
+    assert_eq!(
+        report.support.iter().sum::<f64>(),
+        test_set.feature_rows.len() as f64
+    );
+    assert!(report.cohen_kappa > 0.5);
+    assert!(report.macro_f1 > 0.7);
 }
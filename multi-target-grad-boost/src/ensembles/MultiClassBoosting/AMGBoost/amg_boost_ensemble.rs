@@ -1,74 +1,343 @@
+use std::error::Error;
+
 use self::{
     amg_boost_executor_functions::execute_gradient_boosting_loop,
-    amg_boost_predict_functions::predict_instance,
+    amg_boost_predict_functions::{
+        accumulate_leaf_outputs_for_test_instance, leaf_value, predict_instance,
+    },
 };
 use common::datasets::MultiTargetDataSet;
-use multi_target_decision_tree::decision_trees::TreeConfig;
+use multi_target_decision_tree::{
+    decision_trees::TreeConfig,
+    feature_importance::FeatureImportance,
+    rule_extraction::Rule,
+    tree_shap::{ShapInteractionValues, ShapValues},
+};
+use rayon::prelude::*;
 
 use super::{
-    boosting_loop::boosting_loop,
+    boosting_loop::{
+        boosting_loop, checkpointed_boosting_loop, continue_boosting_loop, resume_boosting_loop,
+    },
     boosting_types::{
-        AMGBoostModel, BoostingEnsembleType, BoostingExecutor, GradBoostTrainingData,
+        AMGBoostModel, BatchPredictionStrategy, BoostingEnsembleType, BoostingExecutor,
+        GradBoostTrainingData, MultiClassObjective, ScoreDirection, Shrinkage,
     },
+    common_boosting_functions::apply_common::apply,
+    common_boosting_functions::continue_common::{
+        check_continued_training_data, continued_learning_rate,
+    },
+    common_boosting_functions::decision_path_common::{decision_path, EnsembleDecisionPath},
+    common_boosting_functions::predict_common::{predict_batch, StagedPredictions},
+    common_boosting_functions::rule_common::extract_rules,
+    common_boosting_functions::shap_common::{shap_interaction_values, shap_values},
     common_multi_class_boosting_functions::predict_helper_functions::{
         calculate_accuracy_from_predictions, get_binary_prediction,
     },
+    learning_rate_schedule::LearningRateSchedule,
     GradientBoostedEnsemble,
 };
+use crate::checkpoint::{Checkpoint, CheckpointConfig};
+
 pub mod amg_boost_executor_functions;
 mod amg_boost_predict_functions;
 
-impl GradientBoostedEnsemble for AMGBoostModel {
-    fn train(
+impl AMGBoostModel {
+    pub fn shrinkage(&self) -> Shrinkage<'_> {
+        Shrinkage {
+            learning_rates: &self.learning_rates,
+            target_shrinkage: None,
+        }
+    }
+
+    // Trains with class weights and/or the focal loss, train uses the default objective
+    pub fn train_with_objective(
         data: MultiTargetDataSet,
         tree_config: TreeConfig,
         number_of_iterations: u32,
-        learning_rate: f64,
+        learning_rate_schedule: &LearningRateSchedule,
+        objective: MultiClassObjective,
     ) -> Self {
         let boosting_executor = BoostingExecutor {
             ensemble_type: BoostingEnsembleType::AMGBoost,
+            objective: Some(objective),
+            target_shrinkage: None,
             loop_executor_function: execute_gradient_boosting_loop,
         };
         let boosting_model = boosting_loop(
             data,
             tree_config,
             number_of_iterations,
-            learning_rate,
+            learning_rate_schedule,
             boosting_executor,
         );
         AMGBoostModel {
             trees: boosting_model.trees,
             initial_guess: boosting_model.initial_guess,
-            learning_rate: boosting_model.learning_rate,
+            learning_rates: boosting_model.learning_rates,
         }
     }
 
+    // As train, writing checkpoints that resume_from can carry on from
+    pub fn train_with_checkpoints(
+        data: MultiTargetDataSet,
+        tree_config: TreeConfig,
+        number_of_iterations: u32,
+        learning_rate_schedule: &LearningRateSchedule,
+        objective: MultiClassObjective,
+        checkpoint_config: &CheckpointConfig,
+    ) -> Result<Self, Box<dyn Error>> {
+        let boosting_executor = BoostingExecutor {
+            ensemble_type: BoostingEnsembleType::AMGBoost,
+            objective: Some(objective),
+            target_shrinkage: None,
+            loop_executor_function: execute_gradient_boosting_loop,
+        };
+        let boosting_model = checkpointed_boosting_loop(
+            data,
+            tree_config,
+            number_of_iterations,
+            learning_rate_schedule,
+            boosting_executor,
+            checkpoint_config,
+        )?;
+        Ok(Self {
+            trees: boosting_model.trees,
+            initial_guess: boosting_model.initial_guess,
+            learning_rates: boosting_model.learning_rates,
+        })
+    }
+
+    // Finishes the run a checkpoint file was written by, given the data and tree config it was
+    // started with. Gives the same model as the uninterrupted run.
+    pub fn resume_from(
+        checkpoint_path: &str,
+        data: MultiTargetDataSet,
+        tree_config: TreeConfig,
+        checkpoint_config: Option<&CheckpointConfig>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let boosting_executor = BoostingExecutor {
+            ensemble_type: BoostingEnsembleType::AMGBoost,
+            objective: None,
+            target_shrinkage: None,
+            loop_executor_function: execute_gradient_boosting_loop,
+        };
+        let boosting_model = resume_boosting_loop(
+            Checkpoint::load(checkpoint_path)?,
+            data,
+            tree_config,
+            boosting_executor,
+            checkpoint_config,
+        )?;
+        Ok(Self {
+            trees: boosting_model.trees,
+            initial_guess: boosting_model.initial_guess,
+            learning_rates: boosting_model.learning_rates,
+        })
+    }
+
+    // Continued training with class weights and/or the focal loss, see
+    // GradientBoostedEnsemble::continue_training
+    pub fn continue_training_with_objective(
+        &mut self,
+        data: MultiTargetDataSet,
+        tree_config: TreeConfig,
+        extra_iterations: u32,
+        learning_rate: Option<f64>,
+        objective: MultiClassObjective,
+    ) {
+        check_continued_training_data(&data, &self.initial_guess);
+        let current_predictions = predict_batch(
+            &data.feature_rows,
+            &self.trees,
+            &self.initial_guess,
+            self.shrinkage(),
+            accumulate_leaf_outputs_for_test_instance,
+            BatchPredictionStrategy::ParallelRows,
+        );
+        let learning_rates = vec![
+            continued_learning_rate(learning_rate, &self.learning_rates);
+            extra_iterations as usize
+        ];
+        let boosting_executor = BoostingExecutor {
+            ensemble_type: BoostingEnsembleType::AMGBoost,
+            objective: Some(objective),
+            target_shrinkage: None,
+            loop_executor_function: execute_gradient_boosting_loop,
+        };
+        let new_trees = continue_boosting_loop(
+            data,
+            current_predictions,
+            tree_config,
+            &learning_rates,
+            boosting_executor,
+        );
+        self.trees.extend(new_trees);
+        self.learning_rates.extend(learning_rates);
+    }
+}
+
+impl GradientBoostedEnsemble for AMGBoostModel {
+    fn train_with_schedule(
+        data: MultiTargetDataSet,
+        tree_config: TreeConfig,
+        number_of_iterations: u32,
+        learning_rate_schedule: &LearningRateSchedule,
+    ) -> Self {
+        Self::train_with_objective(
+            data,
+            tree_config,
+            number_of_iterations,
+            learning_rate_schedule,
+            MultiClassObjective::default(),
+        )
+    }
+
+    fn continue_training(
+        &mut self,
+        data: MultiTargetDataSet,
+        tree_config: TreeConfig,
+        extra_iterations: u32,
+        learning_rate: Option<f64>,
+    ) {
+        self.continue_training_with_objective(
+            data,
+            tree_config,
+            extra_iterations,
+            learning_rate,
+            MultiClassObjective::default(),
+        )
+    }
+
     fn predict(&self, feature_row: &[f64]) -> Vec<f64> {
-        let number_of_classes = self.initial_guess.len();
         let prediction = predict_instance(
             feature_row,
             &self.trees,
             &self.initial_guess,
-            self.learning_rate,
-            number_of_classes,
+            self.shrinkage(),
         );
         get_binary_prediction(&prediction)
     }
 
-    fn calculate_all_predictions(&self, test_set: &MultiTargetDataSet) -> Vec<Vec<f64>> {
-        let number_of_test_instances = test_set.feature_rows.len();
-        let mut predictions = Vec::with_capacity(number_of_test_instances);
-        for i in 0..number_of_test_instances {
-            let test_feature_row = &test_set.feature_rows[i];
-            let prediction = self.predict(test_feature_row);
-            predictions.push(prediction);
-        }
+    fn predict_batch_with_strategy(
+        &self,
+        feature_rows: &[Vec<f64>],
+        strategy: BatchPredictionStrategy,
+    ) -> Vec<Vec<f64>> {
+        let predictions = predict_batch(
+            feature_rows,
+            &self.trees,
+            &self.initial_guess,
+            self.shrinkage(),
+            accumulate_leaf_outputs_for_test_instance,
+            strategy,
+        );
         predictions
+            .par_iter()
+            .map(|prediction| get_binary_prediction(prediction))
+            .collect()
+    }
+
+    fn predict_with_n_trees(&self, feature_row: &[f64], number_of_trees: usize) -> Vec<f64> {
+        let number_of_trees = number_of_trees.min(self.trees.len());
+        let prediction = predict_instance(
+            feature_row,
+            &self.trees[..number_of_trees],
+            &self.initial_guess,
+            self.shrinkage().of_trees(0..number_of_trees),
+        );
+        get_binary_prediction(&prediction)
+    }
+
+    fn staged_predict<'a>(
+        &'a self,
+        feature_rows: &'a [Vec<f64>],
+    ) -> Box<dyn Iterator<Item = Vec<Vec<f64>>> + 'a> {
+        Box::new(StagedPredictions::new(
+            feature_rows,
+            &self.trees,
+            &self.initial_guess,
+            self.shrinkage(),
+            accumulate_leaf_outputs_for_test_instance,
+            get_binary_prediction,
+        ))
+    }
+
+    fn truncate(&self, number_of_trees: usize) -> Self {
+        let number_of_trees = number_of_trees.min(self.trees.len());
+        AMGBoostModel {
+            trees: self.trees[..number_of_trees].to_vec(),
+            initial_guess: self.initial_guess.clone(),
+            learning_rates: self.learning_rates[..number_of_trees].to_vec(),
+        }
+    }
+
+    fn apply(&self, feature_rows: &[Vec<f64>]) -> Vec<Vec<usize>> {
+        apply(feature_rows, &self.trees)
+    }
+
+    fn feature_importance(&self, number_of_features: usize) -> FeatureImportance {
+        FeatureImportance::from_trees(&self.trees, number_of_features)
+    }
+
+    fn shap_values(&self, feature_row: &[f64]) -> ShapValues {
+        let number_of_classes = self.initial_guess.len();
+        shap_values(
+            feature_row,
+            &self.trees,
+            &self.initial_guess,
+            self.shrinkage(),
+            |leaf| leaf_value(leaf, number_of_classes),
+        )
+    }
+
+    fn shap_interaction_values(&self, feature_row: &[f64]) -> ShapInteractionValues {
+        let number_of_classes = self.initial_guess.len();
+        shap_interaction_values(
+            feature_row,
+            &self.trees,
+            &self.initial_guess,
+            self.shrinkage(),
+            |leaf| leaf_value(leaf, number_of_classes),
+        )
+    }
+
+    fn decision_path(&self, feature_row: &[f64]) -> EnsembleDecisionPath {
+        let number_of_classes = self.initial_guess.len();
+        decision_path(
+            feature_row,
+            &self.trees,
+            &self.initial_guess,
+            self.shrinkage(),
+            |leaf| leaf_value(leaf, number_of_classes),
+        )
+    }
+
+    fn extract_rules(&self) -> Vec<Rule> {
+        let number_of_classes = self.initial_guess.len();
+        extract_rules(&self.trees, self.shrinkage(), |leaf| {
+            leaf_value(leaf, number_of_classes)
+        })
+    }
+
+    fn calculate_all_predictions(&self, test_set: &MultiTargetDataSet) -> Vec<Vec<f64>> {
+        self.predict_batch(&test_set.feature_rows)
+    }
+
+    fn calculate_score_from_predictions(
+        &self,
+        predictions: &[Vec<f64>],
+        test_set: &MultiTargetDataSet,
+    ) -> f64 {
+        calculate_accuracy_from_predictions(predictions, test_set)
+    }
+
+    fn score_direction(&self) -> ScoreDirection {
+        ScoreDirection::HigherIsBetter
     }
 
-    fn calculate_score(&self, test_set: &MultiTargetDataSet) -> f64 {
-        let predictions = self.calculate_all_predictions(test_set);
-        calculate_accuracy_from_predictions(&predictions, test_set)
+    fn is_classifier(&self) -> bool {
+        true
     }
 }
 
@@ -1,7 +1,4 @@
-use crate::{
-    boosting_ensemble::common_multi_class_boosting_functions::executor_helper_functions::calculate_residuals,
-    tree_traverse::find_leaf_node_for_data,
-};
+use crate::boosting_ensemble::common_multi_class_boosting_functions::executor_helper_functions::calculate_residuals;
 use multi_target_decision_tree::{
     decision_trees::TreeConfig,
     grad_boost_decision_trees::{
@@ -10,27 +7,28 @@ use multi_target_decision_tree::{
     },
     leaf::AMGBoostLeaf,
     node::TreeNode,
+    tree_traverse::find_leaf_node_for_data,
 };
 
 use super::{calculate_approximate_value, GradBoostTrainingData};
 
 pub(crate) fn execute_gradient_boosting_loop(
     training_data: &mut GradBoostTrainingData,
-    number_of_iterations: u32,
     tree_config: TreeConfig,
-    learning_rate: f64,
+    learning_rates: &[f64],
 ) -> Vec<Box<TreeNode<AMGBoostLeaf>>> {
-    let mut trees = Vec::with_capacity(number_of_iterations as usize);
+    let mut trees = Vec::with_capacity(learning_rates.len());
     let leaf_output_calculator =
         LeafOutputCalculator::new(LeafOutputType::MultiClassClassification);
     //Training data mutable labels gets altered in each iteration, dependency between each iteration, can't parallelise
-    for _i in 0..number_of_iterations {
-        let residuals = calculate_residuals(training_data);
+    for learning_rate in learning_rates {
+        let (residuals, row_weights) = calculate_residuals(training_data);
         let mut learner_data = training_data.data.clone();
         learner_data.labels = residuals;
+        learner_data.weights = row_weights;
         let residual_tree = AMGBoostTree::new(learner_data, tree_config, leaf_output_calculator);
         let boxed_residual_tree = Box::new(residual_tree.root);
-        update_dataset_labels(training_data, &boxed_residual_tree, learning_rate);
+        update_dataset_labels(training_data, &boxed_residual_tree, *learning_rate);
         trees.push(boxed_residual_tree);
     }
     trees
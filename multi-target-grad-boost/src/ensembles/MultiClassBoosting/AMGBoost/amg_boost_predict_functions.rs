@@ -1,58 +1,56 @@
-use multi_target_decision_tree::{leaf::AMGBoostLeaf, node::TreeNode};
+use multi_target_decision_tree::{
+    leaf::AMGBoostLeaf, node::TreeNode, tree_traverse::find_leaf_node_for_data,
+};
 
-use crate::tree_traverse::find_leaf_node_for_data;
+use crate::boosting_ensemble::boosting_types::Shrinkage;
 
 use super::calculate_approximate_value;
 
 pub fn predict_instance(
     test_feature_row: &[f64],
-    trees: &Vec<Box<TreeNode<AMGBoostLeaf>>>,
+    trees: &[Box<TreeNode<AMGBoostLeaf>>],
     initial_guess: &[f64],
-    learning_rate: f64,
-    number_of_classes: usize,
+    shrinkage: Shrinkage,
 ) -> Vec<f64> {
-    let test_instance_leaf_outputs = collect_leaf_outputs_for_test_instance(
+    let mut sum_of_leaf_outputs = initial_guess.to_owned();
+    accumulate_leaf_outputs_for_test_instance(
         test_feature_row,
         trees,
-        learning_rate,
-        number_of_classes,
+        shrinkage,
+        &mut sum_of_leaf_outputs,
     );
-    let mut sum_of_leaf_outputs = initial_guess.clone().to_owned();
-    for leaf_output in test_instance_leaf_outputs {
-        for i in 0..sum_of_leaf_outputs.len() {
-            sum_of_leaf_outputs[i] += leaf_output[i];
-        }
-    }
     sum_of_leaf_outputs
 }
 
-fn collect_leaf_outputs_for_test_instance(
+//The number of classes is taken from the length of the buffer being summed into
+pub fn accumulate_leaf_outputs_for_test_instance(
     test_feature_row: &[f64],
-    trees: &Vec<Box<TreeNode<AMGBoostLeaf>>>,
-    learning_rate: f64,
-    number_of_classes: usize,
-) -> Vec<Vec<f64>> {
-    let mut leaf_outputs = vec![];
-    for i in 0..trees.len() {
-        let leaf = find_leaf_node_for_data(test_feature_row, &trees[i]);
-        let leaf_output = construct_approximate_leaf_output(leaf, number_of_classes);
-        let weighted_leaf_output = leaf_output
-            .into_iter()
-            .map(|x| learning_rate * x)
-            .collect::<Vec<_>>();
-        leaf_outputs.push(weighted_leaf_output.clone());
+    trees: &[Box<TreeNode<AMGBoostLeaf>>],
+    shrinkage: Shrinkage,
+    sum_of_leaf_outputs: &mut [f64],
+) {
+    let number_of_classes = sum_of_leaf_outputs.len() as f64;
+    for (tree_index, tree) in trees.iter().enumerate() {
+        let leaf = find_leaf_node_for_data(test_feature_row, tree);
+        let max_value = leaf.max_value.unwrap();
+        let max_value_class = leaf.class.unwrap();
+        let non_max_value = calculate_approximate_value(max_value, number_of_classes);
+        for (class, sum) in sum_of_leaf_outputs.iter_mut().enumerate() {
+            let factor = shrinkage.factor(tree_index, class);
+            if class == max_value_class {
+                *sum += factor * max_value;
+            } else {
+                *sum += factor * non_max_value;
+            }
+        }
     }
-    leaf_outputs
 }
 
-fn construct_approximate_leaf_output(
-    leaf_data: &AMGBoostLeaf,
-    number_of_classes: usize,
-) -> Vec<f64> {
-    let max_value = leaf_data.max_value.unwrap();
-    let max_value_class = leaf_data.class.unwrap();
+//The output vector an AMGBoost leaf adds to the ensemble's sum, before the shrinkage
+pub fn leaf_value(leaf: &AMGBoostLeaf, number_of_classes: usize) -> Vec<f64> {
+    let max_value = leaf.max_value.unwrap();
     let non_max_value = calculate_approximate_value(max_value, number_of_classes as f64);
-    let mut approximated_leaf_output = vec![non_max_value; number_of_classes as usize];
-    approximated_leaf_output[max_value_class] = max_value;
-    approximated_leaf_output
+    let mut value = vec![non_max_value; number_of_classes];
+    value[leaf.class.unwrap()] = max_value;
+    value
 }
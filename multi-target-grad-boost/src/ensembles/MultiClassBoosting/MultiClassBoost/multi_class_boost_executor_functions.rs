@@ -16,24 +16,24 @@ use crate::boosting_ensemble::{
 
 pub(crate) fn execute_gradient_boosting_loop(
     training_data: &mut GradBoostTrainingData,
-    number_of_iterations: u32,
     tree_config: TreeConfig,
-    learning_rate: f64,
+    learning_rates: &[f64],
 ) -> Vec<Box<TreeNode<GradBoostLeaf>>> {
-    let mut trees = Vec::with_capacity(number_of_iterations as usize);
+    let mut trees = Vec::with_capacity(learning_rates.len());
     let leaf_output_calculator =
         LeafOutputCalculator::new(LeafOutputType::MultiClassClassification);
-    for _i in 0..number_of_iterations {
-        let residuals = calculate_residuals(training_data);
+    for learning_rate in learning_rates {
+        let (residuals, row_weights) = calculate_residuals(training_data);
         let mut learner_data = training_data.data.clone();
         learner_data.labels = residuals;
+        learner_data.weights = row_weights;
         let residual_tree = GradBoostMultiTargetDecisionTree::new(
             learner_data,
             tree_config,
             leaf_output_calculator,
         );
         let boxed_residual_tree = Box::new(residual_tree.root);
-        update_dataset_labels(training_data, &boxed_residual_tree, learning_rate);
+        update_dataset_labels(training_data, &boxed_residual_tree, *learning_rate);
         trees.push(boxed_residual_tree);
     }
     trees
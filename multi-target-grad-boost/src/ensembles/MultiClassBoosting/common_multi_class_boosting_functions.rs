@@ -16,7 +16,7 @@ pub mod predict_helper_functions {
     }
 
     pub fn calculate_accuracy_from_predictions(
-        predictions: &Vec<Vec<f64>>,
+        predictions: &[Vec<f64>],
         test_set: &MultiTargetDataSet,
     ) -> f64 {
         let mut correct_count = 0.;
@@ -26,35 +26,109 @@ pub mod predict_helper_functions {
             .for_each(|(index, prediction)| {
                 let actual = &test_set.labels[index];
                 if prediction == actual {
-                    correct_count += 1.;
+                    correct_count += test_set.weight(index);
                 }
             });
-        correct_count / predictions.len() as f64
+        correct_count / test_set.total_weight()
     }
 }
 
 pub mod executor_helper_functions {
-    use common::numerical_calculations::{
-        multiply_f64_slice_by_f64_scalar, subtract_f64_slices_as_vector,
+    use common::{
+        datasets::MultiTargetDataSet,
+        metrics::classification::class_index,
+        numerical_calculations::{multiply_f64_slice_by_f64_scalar, subtract_f64_slices_as_vector},
     };
     use rayon::prelude::*;
 
-    use crate::boosting_ensemble::boosting_types::GradBoostTrainingData;
+    use crate::boosting_ensemble::boosting_types::{ClassWeight, GradBoostTrainingData};
 
-    pub fn calculate_residuals(training_data: &GradBoostTrainingData) -> Vec<Vec<f64>> {
+    // Residuals and the row weights of the learner data. A row's weight is its sample weight times
+    // the weight of its true class and, for the focal loss, its focal factor. The leaf outputs
+    // scale the residuals and the leaf denominators by these weights.
+    pub fn calculate_residuals(
+        training_data: &GradBoostTrainingData,
+    ) -> (Vec<Vec<f64>>, Option<Vec<f64>>) {
         //iterate over training data and use 'true label' and current to calculate residuals
         let indices: Vec<_> = (0..training_data.size).collect();
-        let residuals = indices
+        let (residuals, row_weights): (Vec<Vec<f64>>, Vec<f64>) = indices
             .par_iter()
             .map(|i| {
                 let true_label = &training_data.data.labels[*i];
                 let current_label = &training_data.mutable_labels[*i];
                 let probabilities = calculate_probabilities_of_predictions(current_label);
+                let true_class = class_index(true_label);
+                let mut row_weight = training_data.data.weight(*i);
+                if let Some(class_weights) = &training_data.class_weights {
+                    row_weight *= class_weights[true_class];
+                }
+                if let Some(focal_gamma) = training_data.focal_gamma {
+                    row_weight *= calculate_focal_factor(probabilities[true_class], focal_gamma);
+                }
                 let residual = subtract_f64_slices_as_vector(true_label, &probabilities);
-                residual
+                (residual, row_weight)
             })
-            .collect::<Vec<_>>();
-        residuals
+            .unzip();
+        let unweighted = training_data.data.weights.is_none()
+            && training_data.class_weights.is_none()
+            && training_data.focal_gamma.is_none();
+        (residuals, if unweighted { None } else { Some(row_weights) })
+    }
+
+    // The negative gradient of -(1 - p)^gamma ln(p) with respect to the scores is the softmax
+    // residual times this factor, which is also used in place of the focal loss' hessian
+    fn calculate_focal_factor(probability: f64, focal_gamma: f64) -> f64 {
+        if probability >= 1. {
+            return 0.;
+        }
+        let complement = 1. - probability;
+        complement.powf(focal_gamma)
+            - focal_gamma * probability * complement.powf(focal_gamma - 1.) * probability.ln()
+    }
+
+    // None for uniform class weights. Balanced weights of classes without rows are 0.
+    pub fn calculate_class_weights(
+        class_weight: &ClassWeight,
+        data: &MultiTargetDataSet,
+    ) -> Option<Vec<f64>> {
+        let number_of_classes = data.number_of_targets();
+        match class_weight {
+            ClassWeight::Uniform => None,
+            ClassWeight::Balanced => {
+                let mut class_totals = vec![0.; number_of_classes];
+                data.labels
+                    .iter()
+                    .enumerate()
+                    .for_each(|(row, label)| class_totals[class_index(label)] += data.weight(row));
+                let total_weight: f64 = class_totals.iter().sum();
+                Some(
+                    class_totals
+                        .iter()
+                        .map(|class_total| {
+                            if *class_total > 0. {
+                                total_weight / (number_of_classes as f64 * class_total)
+                            } else {
+                                0.
+                            }
+                        })
+                        .collect(),
+                )
+            }
+            ClassWeight::Explicit(class_weights) => {
+                assert_eq!(
+                    class_weights.len(),
+                    number_of_classes,
+                    "one class weight per class is needed"
+                );
+                assert!(
+                    class_weights
+                        .iter()
+                        .all(|weight| weight.is_finite() && *weight >= 0.),
+                    "class weights must be finite and non-negative"
+                );
+                Some(class_weights.clone())
+            }
+        }
     }
 
     //current label ~ current prediction
@@ -70,3 +144,37 @@ pub mod executor_helper_functions {
         probabilties
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use common::datasets::MultiTargetDataSet;
+
+    use super::executor_helper_functions::calculate_class_weights;
+    use crate::boosting_ensemble::boosting_types::ClassWeight;
+
+    #[test]
+    fn test_balanced_class_weights() {
+        let labels = vec![
+            vec![1., 0., 0.],
+            vec![1., 0., 0.],
+            vec![1., 0., 0.],
+            vec![0., 1., 0.],
+        ];
+        let data = MultiTargetDataSet::from_rows(vec![vec![0.]; 4], labels);
+        // 4 / (3 * 3), 4 / (3 * 1) and no rows of the last class
+        let class_weights = calculate_class_weights(&ClassWeight::Balanced, &data).unwrap();
+        assert!((class_weights[0] - 4. / 9.).abs() < 1e-12);
+        assert!((class_weights[1] - 4. / 3.).abs() < 1e-12);
+        assert_eq!(class_weights[2], 0.);
+
+        // sample weights count towards the class totals
+        let weighted_data = data.with_weights(vec![1., 1., 1., 3.]);
+        let class_weights =
+            calculate_class_weights(&ClassWeight::Balanced, &weighted_data).unwrap();
+        assert!((class_weights[0] - class_weights[1]).abs() < 1e-12);
+        assert_eq!(
+            calculate_class_weights(&ClassWeight::Uniform, &weighted_data),
+            None
+        );
+    }
+}
@@ -1,84 +1,314 @@
+use std::error::Error;
+
 use common::{
-    datasets::MultiTargetDataSet,
-    numerical_calculations::mean_sum_of_squared_differences_between_f64_slices,
+    datasets::MultiTargetDataSet, metrics::regression::calculate_weighted_mean_squared_error,
+};
+use multi_target_decision_tree::{
+    decision_trees::TreeConfig,
+    feature_importance::FeatureImportance,
+    rule_extraction::Rule,
+    tree_shap::{ShapInteractionValues, ShapValues},
 };
-use multi_target_decision_tree::decision_trees::TreeConfig;
 
 use self::regression_boost_executor_functions::execute_gradient_boosting_loop;
 
 use super::{
-    boosting_loop::boosting_loop,
-    boosting_types::{BoostingEnsembleType, BoostingExecutor, RegressionBoostModel},
-    common_boosting_functions::predict_common::predict_instance,
+    boosting_loop::{
+        boosting_loop, checkpointed_boosting_loop, continue_boosting_loop, resume_boosting_loop,
+    },
+    boosting_types::{
+        BatchPredictionStrategy, BoostingEnsembleType, BoostingExecutor, RegressionBoostModel,
+        ScoreDirection, Shrinkage,
+    },
+    common_boosting_functions::apply_common::apply,
+    common_boosting_functions::continue_common::{
+        check_continued_training_data, continued_learning_rate,
+    },
+    common_boosting_functions::decision_path_common::{decision_path, EnsembleDecisionPath},
+    common_boosting_functions::predict_common::{
+        accumulate_leaf_outputs_for_test_instance, grad_boost_leaf_value, predict_batch,
+        predict_instance, raw_prediction, StagedPredictions,
+    },
+    common_boosting_functions::rule_common::extract_rules,
+    common_boosting_functions::shap_common::{shap_interaction_values, shap_values},
+    learning_rate_schedule::LearningRateSchedule,
     GradientBoostedEnsemble,
 };
 
+use crate::checkpoint::{Checkpoint, CheckpointConfig};
+
 mod regression_boost_executor_functions;
 
-impl GradientBoostedEnsemble for RegressionBoostModel {
-    fn train(
+impl RegressionBoostModel {
+    pub fn shrinkage(&self) -> Shrinkage<'_> {
+        Shrinkage {
+            learning_rates: &self.learning_rates,
+            target_shrinkage: self.target_shrinkage.as_deref(),
+        }
+    }
+
+    // Each target's output of every tree is multiplied by its factor in target_shrinkage on top of
+    // the tree's learning rate, train_with_schedule shrinks all targets the same
+    pub fn train_with_target_shrinkage(
         data: MultiTargetDataSet,
         tree_config: TreeConfig,
         number_of_iterations: u32,
-        learning_rate: f64,
+        learning_rate_schedule: &LearningRateSchedule,
+        target_shrinkage: Option<Vec<f64>>,
     ) -> Self {
         let boosting_executor = BoostingExecutor {
             ensemble_type: BoostingEnsembleType::RegressionBoost,
+            objective: None,
+            target_shrinkage,
             loop_executor_function: execute_gradient_boosting_loop,
         };
         let boosting_model = boosting_loop(
             data,
             tree_config,
             number_of_iterations,
-            learning_rate,
+            learning_rate_schedule,
             boosting_executor,
         );
         Self {
             trees: boosting_model.trees,
             initial_guess: boosting_model.initial_guess,
-            learning_rate: boosting_model.learning_rate,
+            learning_rates: boosting_model.learning_rates,
+            target_shrinkage: boosting_model.target_shrinkage,
         }
     }
 
+    // As train_with_target_shrinkage, writing checkpoints that resume_from can carry on from
+    pub fn train_with_checkpoints(
+        data: MultiTargetDataSet,
+        tree_config: TreeConfig,
+        number_of_iterations: u32,
+        learning_rate_schedule: &LearningRateSchedule,
+        target_shrinkage: Option<Vec<f64>>,
+        checkpoint_config: &CheckpointConfig,
+    ) -> Result<Self, Box<dyn Error>> {
+        let boosting_executor = BoostingExecutor {
+            ensemble_type: BoostingEnsembleType::RegressionBoost,
+            objective: None,
+            target_shrinkage,
+            loop_executor_function: execute_gradient_boosting_loop,
+        };
+        let boosting_model = checkpointed_boosting_loop(
+            data,
+            tree_config,
+            number_of_iterations,
+            learning_rate_schedule,
+            boosting_executor,
+            checkpoint_config,
+        )?;
+        Ok(Self {
+            trees: boosting_model.trees,
+            initial_guess: boosting_model.initial_guess,
+            learning_rates: boosting_model.learning_rates,
+            target_shrinkage: boosting_model.target_shrinkage,
+        })
+    }
+
+    // Finishes the run a checkpoint file was written by, given the data and tree config it was
+    // started with. Gives the same model as the uninterrupted run.
+    pub fn resume_from(
+        checkpoint_path: &str,
+        data: MultiTargetDataSet,
+        tree_config: TreeConfig,
+        checkpoint_config: Option<&CheckpointConfig>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let boosting_executor = BoostingExecutor {
+            ensemble_type: BoostingEnsembleType::RegressionBoost,
+            objective: None,
+            target_shrinkage: None,
+            loop_executor_function: execute_gradient_boosting_loop,
+        };
+        let boosting_model = resume_boosting_loop(
+            Checkpoint::load(checkpoint_path)?,
+            data,
+            tree_config,
+            boosting_executor,
+            checkpoint_config,
+        )?;
+        Ok(Self {
+            trees: boosting_model.trees,
+            initial_guess: boosting_model.initial_guess,
+            learning_rates: boosting_model.learning_rates,
+            target_shrinkage: boosting_model.target_shrinkage,
+        })
+    }
+}
+
+impl GradientBoostedEnsemble for RegressionBoostModel {
+    fn train_with_schedule(
+        data: MultiTargetDataSet,
+        tree_config: TreeConfig,
+        number_of_iterations: u32,
+        learning_rate_schedule: &LearningRateSchedule,
+    ) -> Self {
+        Self::train_with_target_shrinkage(
+            data,
+            tree_config,
+            number_of_iterations,
+            learning_rate_schedule,
+            None,
+        )
+    }
+
+    fn continue_training(
+        &mut self,
+        data: MultiTargetDataSet,
+        tree_config: TreeConfig,
+        extra_iterations: u32,
+        learning_rate: Option<f64>,
+    ) {
+        check_continued_training_data(&data, &self.initial_guess);
+        let current_predictions = predict_batch(
+            &data.feature_rows,
+            &self.trees,
+            &self.initial_guess,
+            self.shrinkage(),
+            accumulate_leaf_outputs_for_test_instance,
+            BatchPredictionStrategy::ParallelRows,
+        );
+        let learning_rates = vec![
+            continued_learning_rate(learning_rate, &self.learning_rates);
+            extra_iterations as usize
+        ];
+        let boosting_executor = BoostingExecutor {
+            ensemble_type: BoostingEnsembleType::RegressionBoost,
+            objective: None,
+            target_shrinkage: self.target_shrinkage.clone(),
+            loop_executor_function: execute_gradient_boosting_loop,
+        };
+        let new_trees = continue_boosting_loop(
+            data,
+            current_predictions,
+            tree_config,
+            &learning_rates,
+            boosting_executor,
+        );
+        self.trees.extend(new_trees);
+        self.learning_rates.extend(learning_rates);
+    }
+
     fn predict(&self, feature_row: &[f64]) -> Vec<f64> {
         let result = predict_instance(
             feature_row,
             &self.trees,
             &self.initial_guess,
-            self.learning_rate,
+            self.shrinkage(),
         );
         result
     }
 
-    fn calculate_all_predictions(&self, test_set: &MultiTargetDataSet) -> Vec<Vec<f64>> {
-        let number_of_test_instances = test_set.feature_rows.len();
-        let mut predictions = Vec::with_capacity(number_of_test_instances);
-        for i in 0..number_of_test_instances {
-            let test_feature_row = &test_set.feature_rows[i];
-            let prediction = self.predict(test_feature_row);
-            predictions.push(prediction);
+    fn predict_batch_with_strategy(
+        &self,
+        feature_rows: &[Vec<f64>],
+        strategy: BatchPredictionStrategy,
+    ) -> Vec<Vec<f64>> {
+        predict_batch(
+            feature_rows,
+            &self.trees,
+            &self.initial_guess,
+            self.shrinkage(),
+            accumulate_leaf_outputs_for_test_instance,
+            strategy,
+        )
+    }
+
+    fn predict_with_n_trees(&self, feature_row: &[f64], number_of_trees: usize) -> Vec<f64> {
+        let number_of_trees = number_of_trees.min(self.trees.len());
+        predict_instance(
+            feature_row,
+            &self.trees[..number_of_trees],
+            &self.initial_guess,
+            self.shrinkage().of_trees(0..number_of_trees),
+        )
+    }
+
+    fn staged_predict<'a>(
+        &'a self,
+        feature_rows: &'a [Vec<f64>],
+    ) -> Box<dyn Iterator<Item = Vec<Vec<f64>>> + 'a> {
+        Box::new(StagedPredictions::new(
+            feature_rows,
+            &self.trees,
+            &self.initial_guess,
+            self.shrinkage(),
+            accumulate_leaf_outputs_for_test_instance,
+            raw_prediction,
+        ))
+    }
+
+    fn truncate(&self, number_of_trees: usize) -> Self {
+        let number_of_trees = number_of_trees.min(self.trees.len());
+        Self {
+            trees: self.trees[..number_of_trees].to_vec(),
+            initial_guess: self.initial_guess.clone(),
+            learning_rates: self.learning_rates[..number_of_trees].to_vec(),
+            target_shrinkage: self.target_shrinkage.clone(),
         }
-        predictions
     }
 
-    fn calculate_score(&self, test_set: &MultiTargetDataSet) -> f64 {
-        let predictions = self.calculate_all_predictions(test_set);
-        let error = calculate_mean_squared_error(&test_set.labels, &predictions);
-        error
+    fn apply(&self, feature_rows: &[Vec<f64>]) -> Vec<Vec<usize>> {
+        apply(feature_rows, &self.trees)
     }
-}
 
-fn calculate_mean_squared_error(
-    test_data_labels: &Vec<Vec<f64>>,
-    predictions: &Vec<Vec<f64>>,
-) -> f64 {
-    let mut total_error = 0.;
-    let number_of_labels = test_data_labels.len();
-    for i in 0..number_of_labels {
-        total_error += mean_sum_of_squared_differences_between_f64_slices(
-            &test_data_labels[i],
-            &predictions[i],
-        );
+    fn feature_importance(&self, number_of_features: usize) -> FeatureImportance {
+        FeatureImportance::from_trees(&self.trees, number_of_features)
+    }
+
+    fn shap_values(&self, feature_row: &[f64]) -> ShapValues {
+        shap_values(
+            feature_row,
+            &self.trees,
+            &self.initial_guess,
+            self.shrinkage(),
+            grad_boost_leaf_value,
+        )
+    }
+
+    fn shap_interaction_values(&self, feature_row: &[f64]) -> ShapInteractionValues {
+        shap_interaction_values(
+            feature_row,
+            &self.trees,
+            &self.initial_guess,
+            self.shrinkage(),
+            grad_boost_leaf_value,
+        )
+    }
+
+    fn decision_path(&self, feature_row: &[f64]) -> EnsembleDecisionPath {
+        decision_path(
+            feature_row,
+            &self.trees,
+            &self.initial_guess,
+            self.shrinkage(),
+            grad_boost_leaf_value,
+        )
+    }
+
+    fn extract_rules(&self) -> Vec<Rule> {
+        extract_rules(&self.trees, self.shrinkage(), grad_boost_leaf_value)
+    }
+
+    fn calculate_all_predictions(&self, test_set: &MultiTargetDataSet) -> Vec<Vec<f64>> {
+        self.predict_batch(&test_set.feature_rows)
+    }
+
+    fn calculate_score_from_predictions(
+        &self,
+        predictions: &[Vec<f64>],
+        test_set: &MultiTargetDataSet,
+    ) -> f64 {
+        calculate_weighted_mean_squared_error(predictions, &test_set.labels, test_set.weights())
+    }
+
+    fn score_direction(&self) -> ScoreDirection {
+        ScoreDirection::LowerIsBetter
+    }
+
+    fn is_classifier(&self) -> bool {
+        false
     }
-    total_error / number_of_labels as f64
 }
@@ -17,13 +17,12 @@ use crate::boosting_ensemble::{
 
 pub(super) fn execute_gradient_boosting_loop(
     training_data: &mut GradBoostTrainingData,
-    number_of_iterations: u32,
     tree_config: TreeConfig,
-    learning_rate: f64,
+    learning_rates: &[f64],
 ) -> Vec<Box<TreeNode<GradBoostLeaf>>> {
-    let mut trees = Vec::with_capacity(number_of_iterations as usize);
+    let mut trees = Vec::with_capacity(learning_rates.len());
     let leaf_output_calculator = LeafOutputCalculator::new(LeafOutputType::Regression);
-    for _i in 0..number_of_iterations {
+    for learning_rate in learning_rates {
         let residuals = calculate_residuals(training_data);
         let mut learner_data = training_data.data.clone();
         learner_data.labels = residuals;
@@ -33,7 +32,7 @@ pub(super) fn execute_gradient_boosting_loop(
             leaf_output_calculator,
         );
         let boxed_residual_tree = Box::new(residual_tree.root);
-        update_dataset_labels(training_data, &boxed_residual_tree, learning_rate);
+        update_dataset_labels(training_data, &boxed_residual_tree, *learning_rate);
         trees.push(boxed_residual_tree);
     }
     trees
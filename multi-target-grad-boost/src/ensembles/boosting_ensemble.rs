@@ -1,5 +1,19 @@
-use common::datasets::MultiTargetDataSet;
-use multi_target_decision_tree::decision_trees::TreeConfig;
+use common::{
+    datasets::MultiTargetDataSet,
+    metrics::{classification::ClassificationReport, regression::RegressionReport},
+};
+use multi_target_decision_tree::{
+    decision_trees::TreeConfig,
+    feature_importance::FeatureImportance,
+    rule_extraction::Rule,
+    tree_shap::{ShapInteractionValues, ShapValues},
+};
+
+use self::{
+    boosting_types::{BatchPredictionStrategy, ScoreDirection},
+    common_boosting_functions::decision_path_common::EnsembleDecisionPath,
+    learning_rate_schedule::LearningRateSchedule,
+};
 
 #[path = "./MultiClassBoosting/AMGBoost/amg_boost_ensemble.rs"]
 pub mod amg_boost_ensemble;
@@ -13,6 +27,7 @@ pub mod regression_boost_ensemble;
 pub mod boosting_loop;
 pub mod boosting_types;
 pub mod common_boosting_functions;
+pub mod learning_rate_schedule;
 
 pub trait GradientBoostedEnsemble {
     fn train(
@@ -20,8 +35,85 @@ pub trait GradientBoostedEnsemble {
         tree_config: TreeConfig,
         number_of_iterations: u32,
         learning_rate: f64,
+    ) -> Self
+    where
+        Self: Sized,
+    {
+        Self::train_with_schedule(
+            data,
+            tree_config,
+            number_of_iterations,
+            &LearningRateSchedule::Constant(learning_rate),
+        )
+    }
+    fn train_with_schedule(
+        data: MultiTargetDataSet,
+        tree_config: TreeConfig,
+        number_of_iterations: u32,
+        learning_rate_schedule: &LearningRateSchedule,
     ) -> Self;
+    // Appends extra_iterations trees trained on the data, starting from the ensemble's raw
+    // predictions for it. The new trees get the given learning rate, that of the last tree by
+    // default. Continuing on the training data with a constant learning rate gives the same
+    // ensemble as training for longer. The multiclass ensembles continue with the default
+    // objective, whatever they were trained with, see their continue_training_with_objective.
+    fn continue_training(
+        &mut self,
+        data: MultiTargetDataSet,
+        tree_config: TreeConfig,
+        extra_iterations: u32,
+        learning_rate: Option<f64>,
+    );
     fn predict(&self, feature_row: &[f64]) -> Vec<f64>;
+    fn predict_batch(&self, feature_rows: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        self.predict_batch_with_strategy(feature_rows, BatchPredictionStrategy::ParallelRows)
+    }
+    fn predict_batch_with_strategy(
+        &self,
+        feature_rows: &[Vec<f64>],
+        strategy: BatchPredictionStrategy,
+    ) -> Vec<Vec<f64>>;
+    fn predict_with_n_trees(&self, feature_row: &[f64], number_of_trees: usize) -> Vec<f64>;
+    fn staged_predict<'a>(
+        &'a self,
+        feature_rows: &'a [Vec<f64>],
+    ) -> Box<dyn Iterator<Item = Vec<Vec<f64>>> + 'a>;
+    fn truncate(&self, number_of_trees: usize) -> Self;
+    fn apply(&self, feature_rows: &[Vec<f64>]) -> Vec<Vec<usize>>;
+    fn feature_importance(&self, number_of_features: usize) -> FeatureImportance;
+    // SHAP values of the raw output, for the classifiers this is the score before the argmax
+    fn shap_values(&self, feature_row: &[f64]) -> ShapValues;
+    fn shap_interaction_values(&self, feature_row: &[f64]) -> ShapInteractionValues;
+    fn decision_path(&self, feature_row: &[f64]) -> EnsembleDecisionPath;
+    // One rule per leaf of every tree, predictions are the leaf outputs times the tree's shrinkage
+    fn extract_rules(&self) -> Vec<Rule>;
     fn calculate_all_predictions(&self, test_set: &MultiTargetDataSet) -> Vec<Vec<f64>>;
-    fn calculate_score(&self, test_set: &MultiTargetDataSet) -> f64;
+    fn calculate_score(&self, test_set: &MultiTargetDataSet) -> f64 {
+        let predictions = self.calculate_all_predictions(test_set);
+        self.calculate_score_from_predictions(&predictions, test_set)
+    }
+    fn calculate_score_from_predictions(
+        &self,
+        predictions: &[Vec<f64>],
+        test_set: &MultiTargetDataSet,
+    ) -> f64;
+    fn score_direction(&self) -> ScoreDirection;
+    // Whether the predictions are one-hot class votes rather than regression targets
+    fn is_classifier(&self) -> bool;
+    // Per target errors of calculate_all_predictions, for the classifiers on the one-hot votes
+    fn regression_report(&self, test_set: &MultiTargetDataSet) -> RegressionReport {
+        RegressionReport::new_weighted(
+            &self.calculate_all_predictions(test_set),
+            &test_set.labels,
+            test_set.weights(),
+        )
+    }
+    // Classes are the largest entries of the predictions and of the one-hot labels
+    fn classification_report(&self, test_set: &MultiTargetDataSet) -> ClassificationReport {
+        ClassificationReport::new_weighted(
+            &self.calculate_all_predictions(test_set),
+            &test_set.labels,
+            test_set.weights(),
+        )
+    }
 }
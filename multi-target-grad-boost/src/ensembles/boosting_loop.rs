@@ -1,39 +1,259 @@
-use common::{datasets::MultiTargetDataSet, numerical_calculations::calculate_average_f64_vector};
-use multi_target_decision_tree::{decision_trees::TreeConfig, leaf::Leaf};
+use std::error::Error;
+
+use common::{
+    datasets::MultiTargetDataSet, numerical_calculations::calculate_weighted_average_f64_vector,
+};
+use multi_target_decision_tree::{decision_trees::TreeConfig, leaf::Leaf, node::TreeNode};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::checkpoint::{Checkpoint, CheckpointConfig, CHECKPOINT_FORMAT_VERSION};
 
 use super::{
     boosting_types::{
         BoostingEnsembleType, BoostingExecutor, BoostingResult, GradBoostTrainingData,
+        MultiClassObjective,
     },
     common_boosting_functions::update_common::update_dataset_labels_with_initial_guess,
+    common_multi_class_boosting_functions::executor_helper_functions::calculate_class_weights,
+    learning_rate_schedule::LearningRateSchedule,
 };
 
 pub fn boosting_loop<T: Leaf>(
     data: MultiTargetDataSet,
     tree_config: TreeConfig,
     number_of_iterations: u32,
-    learning_rate: f64,
+    learning_rate_schedule: &LearningRateSchedule,
     boosting_executor: BoostingExecutor<T>,
 ) -> BoostingResult<T> {
-    let mutable_labels = data.labels.clone();
-    let training_data_size = data.labels.len();
-    let mut training_data = GradBoostTrainingData {
+    if let Err(message) = learning_rate_schedule.validate() {
+        panic!("{}", message);
+    }
+    let mut training_data = create_training_data(
         data,
-        mutable_labels,
-        size: training_data_size,
-    };
+        boosting_executor.objective,
+        boosting_executor.target_shrinkage,
+    );
     let initial_guess = determine_initial_guess(&training_data, boosting_executor.ensemble_type);
     update_dataset_labels_with_initial_guess(&mut training_data.mutable_labels, &initial_guess);
+    let learning_rates =
+        learning_rate_schedule.learning_rates(0, number_of_iterations, number_of_iterations);
     let trees = (boosting_executor.loop_executor_function)(
         &mut training_data,
-        number_of_iterations,
         tree_config,
-        learning_rate,
+        &learning_rates,
     );
     BoostingResult {
         trees,
         initial_guess,
-        learning_rate,
+        learning_rates,
+        target_shrinkage: training_data.target_shrinkage,
+    }
+}
+
+// As boosting_loop, writing checkpoints as given by the config
+pub fn checkpointed_boosting_loop<T: Leaf + Serialize + DeserializeOwned>(
+    data: MultiTargetDataSet,
+    tree_config: TreeConfig,
+    number_of_iterations: u32,
+    learning_rate_schedule: &LearningRateSchedule,
+    boosting_executor: BoostingExecutor<T>,
+    checkpoint_config: &CheckpointConfig,
+) -> Result<BoostingResult<T>, Box<dyn Error>> {
+    assert!(checkpoint_config.every_n_iterations > 0);
+    learning_rate_schedule.validate()?;
+    if data.number_of_rows() == 0 {
+        return Err("training needs at least one row".into());
+    }
+    let mut training_data = create_training_data(
+        data,
+        boosting_executor.objective.clone(),
+        boosting_executor.target_shrinkage.clone(),
+    );
+    let initial_guess = determine_initial_guess(&training_data, boosting_executor.ensemble_type);
+    update_dataset_labels_with_initial_guess(&mut training_data.mutable_labels, &initial_guess);
+    let checkpoint = Checkpoint {
+        format_version: CHECKPOINT_FORMAT_VERSION,
+        ensemble_type: boosting_executor.ensemble_type,
+        number_of_iterations,
+        completed_iterations: 0,
+        max_levels: tree_config.max_levels,
+        number_of_classes: tree_config.number_of_classes,
+        objective: boosting_executor.objective.clone(),
+        learning_rate_schedule: learning_rate_schedule.clone(),
+        learning_rates: vec![],
+        initial_guess,
+        mutable_labels: vec![],
+        class_weights: training_data.class_weights.clone(),
+        focal_gamma: training_data.focal_gamma,
+        target_shrinkage: training_data.target_shrinkage.clone(),
+        trees: vec![],
+    };
+    run_checkpointed_iterations(
+        checkpoint,
+        training_data,
+        tree_config,
+        &boosting_executor,
+        Some(checkpoint_config),
+    )
+}
+
+// Carries on from a checkpoint of a run on the same data and tree config, giving the same trees as
+// the uninterrupted run. The objective, learning rate schedule and target shrinkage are the
+// checkpoint's, not the executor's.
+pub fn resume_boosting_loop<T: Leaf + Serialize + DeserializeOwned>(
+    mut checkpoint: Checkpoint<T>,
+    data: MultiTargetDataSet,
+    tree_config: TreeConfig,
+    boosting_executor: BoostingExecutor<T>,
+    checkpoint_config: Option<&CheckpointConfig>,
+) -> Result<BoostingResult<T>, Box<dyn Error>> {
+    if checkpoint.ensemble_type != boosting_executor.ensemble_type {
+        return Err(format!(
+            "the checkpoint is of a {:?} ensemble, not {:?}",
+            checkpoint.ensemble_type, boosting_executor.ensemble_type
+        )
+        .into());
+    }
+    if data.number_of_rows() != checkpoint.mutable_labels.len()
+        || data.number_of_targets() != checkpoint.initial_guess.len()
+    {
+        return Err(format!(
+            "the checkpoint was trained on {} rows with {} targets, the data has {} rows with {}",
+            checkpoint.mutable_labels.len(),
+            checkpoint.initial_guess.len(),
+            data.number_of_rows(),
+            data.number_of_targets()
+        )
+        .into());
+    }
+    if tree_config.max_levels != checkpoint.max_levels
+        || tree_config.number_of_classes != checkpoint.number_of_classes
+    {
+        return Err(format!(
+            "the checkpoint was trained with max_levels {} and {} classes, not {} and {}",
+            checkpoint.max_levels,
+            checkpoint.number_of_classes,
+            tree_config.max_levels,
+            tree_config.number_of_classes
+        )
+        .into());
+    }
+    checkpoint.learning_rate_schedule.validate()?;
+    if let Some(checkpoint_config) = checkpoint_config {
+        assert!(checkpoint_config.every_n_iterations > 0);
+    }
+    let training_data = GradBoostTrainingData {
+        mutable_labels: std::mem::take(&mut checkpoint.mutable_labels),
+        size: data.labels.len(),
+        class_weights: checkpoint.class_weights.clone(),
+        focal_gamma: checkpoint.focal_gamma,
+        target_shrinkage: checkpoint.target_shrinkage.clone(),
+        data,
+    };
+    run_checkpointed_iterations(
+        checkpoint,
+        training_data,
+        tree_config,
+        &boosting_executor,
+        checkpoint_config,
+    )
+}
+
+// The executors keep all of their state in the training data, so running them for a few
+// iterations at a time gives the same trees as a single run
+fn run_checkpointed_iterations<T: Leaf + Serialize + DeserializeOwned>(
+    mut checkpoint: Checkpoint<T>,
+    mut training_data: GradBoostTrainingData,
+    tree_config: TreeConfig,
+    boosting_executor: &BoostingExecutor<T>,
+    checkpoint_config: Option<&CheckpointConfig>,
+) -> Result<BoostingResult<T>, Box<dyn Error>> {
+    while checkpoint.completed_iterations < checkpoint.number_of_iterations {
+        let remaining_iterations =
+            checkpoint.number_of_iterations - checkpoint.completed_iterations;
+        let iterations = checkpoint_config.map_or(remaining_iterations, |checkpoint_config| {
+            checkpoint_config
+                .every_n_iterations
+                .min(remaining_iterations)
+        });
+        let learning_rates = checkpoint.learning_rate_schedule.learning_rates(
+            checkpoint.completed_iterations,
+            iterations,
+            checkpoint.number_of_iterations,
+        );
+        let trees = (boosting_executor.loop_executor_function)(
+            &mut training_data,
+            tree_config,
+            &learning_rates,
+        );
+        checkpoint.trees.extend(trees);
+        checkpoint.learning_rates.extend(learning_rates);
+        checkpoint.completed_iterations += iterations;
+        if let Some(checkpoint_config) = checkpoint_config {
+            checkpoint.mutable_labels = training_data.mutable_labels.clone();
+            checkpoint.save(&checkpoint_config.directory)?;
+        }
+    }
+    Ok(BoostingResult {
+        trees: checkpoint.trees,
+        initial_guess: checkpoint.initial_guess,
+        learning_rates: checkpoint.learning_rates,
+        target_shrinkage: checkpoint.target_shrinkage,
+    })
+}
+
+// New trees, one per learning rate, for an ensemble whose raw predictions for the data are
+// current_predictions, as if boosting had carried on from there. The executor's target shrinkage
+// must be the ensemble's.
+pub fn continue_boosting_loop<T: Leaf>(
+    data: MultiTargetDataSet,
+    current_predictions: Vec<Vec<f64>>,
+    tree_config: TreeConfig,
+    learning_rates: &[f64],
+    boosting_executor: BoostingExecutor<T>,
+) -> Vec<Box<TreeNode<T>>> {
+    assert_eq!(current_predictions.len(), data.labels.len());
+    let mut training_data = create_training_data(
+        data,
+        boosting_executor.objective,
+        boosting_executor.target_shrinkage,
+    );
+    training_data.mutable_labels = current_predictions;
+    (boosting_executor.loop_executor_function)(&mut training_data, tree_config, learning_rates)
+}
+
+fn create_training_data(
+    data: MultiTargetDataSet,
+    objective: Option<MultiClassObjective>,
+    target_shrinkage: Option<Vec<f64>>,
+) -> GradBoostTrainingData {
+    assert!(data.number_of_rows() > 0, "training needs at least one row");
+    let mutable_labels = data.labels.clone();
+    let training_data_size = data.labels.len();
+    let objective = objective.unwrap_or_default();
+    if let Some(focal_gamma) = objective.focal_gamma {
+        assert!(focal_gamma >= 0., "the focal gamma must be non-negative");
+    }
+    if let Some(target_shrinkage) = &target_shrinkage {
+        assert_eq!(
+            target_shrinkage.len(),
+            data.number_of_targets(),
+            "the target shrinkage needs one factor per target"
+        );
+        assert!(
+            target_shrinkage
+                .iter()
+                .all(|shrinkage| shrinkage.is_finite() && *shrinkage >= 0.),
+            "the target shrinkage must be finite and non-negative"
+        );
+    }
+    GradBoostTrainingData {
+        class_weights: calculate_class_weights(&objective.class_weight, &data),
+        focal_gamma: objective.focal_gamma,
+        target_shrinkage,
+        data,
+        mutable_labels,
+        size: training_data_size,
     }
 }
 
@@ -43,13 +263,15 @@ fn determine_initial_guess(
 ) -> Vec<f64> {
     let number_of_classes = training_data.mutable_labels[0].len() as f64;
     let initial_guess = match ensemble_type {
+        // the multi class guesses do not depend on the data, so weights do not change them
         BoostingEnsembleType::AMGBoost => vec![1. / number_of_classes; number_of_classes as usize],
         BoostingEnsembleType::MultiClassBoost => {
             vec![1. / number_of_classes; number_of_classes as usize]
         }
-        BoostingEnsembleType::RegressionBoost => {
-            calculate_average_f64_vector(&training_data.data.labels)
-        }
+        BoostingEnsembleType::RegressionBoost => calculate_weighted_average_f64_vector(
+            &training_data.data.labels,
+            training_data.data.weights(),
+        ),
     };
     initial_guess
 }
@@ -1,47 +1,145 @@
+use std::ops::Range;
+
 use common::datasets::MultiTargetDataSet;
 use multi_target_decision_tree::{
     decision_trees::TreeConfig,
     leaf::{AMGBoostLeaf, GradBoostLeaf, Leaf},
     node::TreeNode,
 };
+use serde::{Deserialize, Serialize};
 
 pub struct BoostingResult<T: Leaf> {
     pub trees: Vec<Box<TreeNode<T>>>,
     pub initial_guess: Vec<f64>,
-    pub learning_rate: f64,
+    pub learning_rates: Vec<f64>,
+    pub target_shrinkage: Option<Vec<f64>>,
 }
 
+// learning_rates holds the learning rate each tree was trained with, index by index
+#[derive(Serialize, Deserialize)]
 pub struct AMGBoostModel {
     pub trees: Vec<Box<TreeNode<AMGBoostLeaf>>>,
     pub initial_guess: Vec<f64>,
-    pub learning_rate: f64,
+    pub learning_rates: Vec<f64>,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct MultiClassBoostModel {
     pub trees: Vec<Box<TreeNode<GradBoostLeaf>>>,
     pub initial_guess: Vec<f64>,
-    pub learning_rate: f64,
+    pub learning_rates: Vec<f64>,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct RegressionBoostModel {
     pub trees: Vec<Box<TreeNode<GradBoostLeaf>>>,
     pub initial_guess: Vec<f64>,
-    pub learning_rate: f64,
+    pub learning_rates: Vec<f64>,
+    // Multiplies every tree's output for each target on top of its learning rate, e.g to shrink
+    // noisy targets harder than clean ones
+    pub target_shrinkage: Option<Vec<f64>>,
+}
+
+// What each tree's leaf outputs are multiplied by when they are added to the ensemble's sum: the
+// learning rate the tree was trained with and, for RegressionBoost, an optional factor per target
+#[derive(Clone, Copy)]
+pub struct Shrinkage<'a> {
+    pub learning_rates: &'a [f64],
+    pub target_shrinkage: Option<&'a [f64]>,
 }
 
+impl<'a> Shrinkage<'a> {
+    // The shrinkage of the trees in the range of the ensemble's trees
+    pub fn of_trees(&self, range: Range<usize>) -> Self {
+        Self {
+            learning_rates: &self.learning_rates[range],
+            target_shrinkage: self.target_shrinkage,
+        }
+    }
+
+    pub fn factor(&self, tree_index: usize, target: usize) -> f64 {
+        self.learning_rates[tree_index]
+            * self
+                .target_shrinkage
+                .map_or(1., |target_shrinkage| target_shrinkage[target])
+    }
+
+    // Applies the target shrinkage only, i.e for a leaf output the learning rate is still missing
+    pub fn scale_targets(&self, mut values: Vec<f64>) -> Vec<f64> {
+        if let Some(target_shrinkage) = self.target_shrinkage {
+            values
+                .iter_mut()
+                .zip(target_shrinkage)
+                .for_each(|(value, shrinkage)| *value *= shrinkage);
+        }
+        values
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum BoostingEnsembleType {
     AMGBoost,
     MultiClassBoost,
     RegressionBoost,
 }
 
+// How a batch of test rows is split over threads when predicting, rows are always predicted in
+// parallel. Splitting each row's trees into chunks helps when there are few rows and many trees.
+#[derive(Clone, Copy, Debug)]
+pub enum BatchPredictionStrategy {
+    ParallelRows,
+    ParallelRowsAndTrees { trees_per_chunk: usize },
+}
+
+// Whether a larger value of `calculate_score` means a better model
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScoreDirection {
+    HigherIsBetter,
+    LowerIsBetter,
+}
+
+// How the classes are weighted in the loss of the multiclass ensembles, classes are the largest
+// entries of the one-hot labels
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ClassWeight {
+    Uniform,
+    // The total weight over the number of classes times the weight of the class, as in scikit-learn
+    Balanced,
+    // One non-negative weight per class
+    Explicit(Vec<f64>),
+}
+
+// Softmax loss of the multiclass ensembles. The focal variant (Lin et al., 2017) scales each row's
+// loss by (1 - p)^gamma, p being the probability of its true class, to focus on the hard rows.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MultiClassObjective {
+    pub class_weight: ClassWeight,
+    pub focal_gamma: Option<f64>,
+}
+
+impl Default for MultiClassObjective {
+    fn default() -> Self {
+        Self {
+            class_weight: ClassWeight::Uniform,
+            focal_gamma: None,
+        }
+    }
+}
+
+// Adds the shrunk leaf outputs of the given trees for one row into the buffer
+pub type LeafOutputAccumulator<T> = fn(&[f64], &[Box<TreeNode<T>>], Shrinkage, &mut [f64]);
+
 pub struct BoostingExecutor<T: Leaf> {
     pub ensemble_type: BoostingEnsembleType,
+    // None for RegressionBoost
+    pub objective: Option<MultiClassObjective>,
+    // Only ever set for RegressionBoost
+    pub target_shrinkage: Option<Vec<f64>>,
+    // Trains one tree per learning rate
     pub loop_executor_function: fn(
         training_data: &mut GradBoostTrainingData,
-        number_of_iterations: u32,
         tree_config: TreeConfig,
-        learning_rate: f64,
+        learning_rates: &[f64],
     ) -> Vec<Box<TreeNode<T>>>,
 }
 
@@ -49,4 +147,8 @@ pub struct GradBoostTrainingData {
     pub data: MultiTargetDataSet,
     pub mutable_labels: Vec<Vec<f64>>,
     pub size: usize,
+    // Resolved from the MultiClassObjective, None when every class weighs 1
+    pub class_weights: Option<Vec<f64>>,
+    pub focal_gamma: Option<f64>,
+    pub target_shrinkage: Option<Vec<f64>>,
 }
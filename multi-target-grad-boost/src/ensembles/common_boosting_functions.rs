@@ -1,12 +1,11 @@
 pub mod update_common {
     use common::numerical_calculations::add_f64_slices_as_vector;
-    use multi_target_decision_tree::{leaf::GradBoostLeaf, node::TreeNode};
-
-    use crate::{
-        boosting_ensemble::boosting_types::GradBoostTrainingData,
-        tree_traverse::find_leaf_node_for_data,
+    use multi_target_decision_tree::{
+        leaf::GradBoostLeaf, node::TreeNode, tree_traverse::find_leaf_node_for_data,
     };
 
+    use crate::boosting_ensemble::boosting_types::GradBoostTrainingData;
+
     //Common to AMGBoost MultiClassBoost and RegressionBoost
     pub fn update_dataset_labels_with_initial_guess(
         mutable_labels: &mut Vec<Vec<f64>>,
@@ -18,6 +17,7 @@ pub mod update_common {
     }
 
     //Common to MultiClassBoost and RegressionBoost
+    //The target shrinkage of the training data applies on top of the learning rate
     pub fn update_dataset_labels(
         training_data: &mut GradBoostTrainingData,
         boxed_tree_ref: &Box<TreeNode<GradBoostLeaf>>,
@@ -28,8 +28,15 @@ pub mod update_common {
                 find_leaf_node_for_data(&training_data.data.feature_rows[i], boxed_tree_ref);
             let leaf_output = leaf_data.leaf_output.as_ref().unwrap();
             let weighted_leaf_output = leaf_output
-                .into_iter()
-                .map(|x| learning_rate * x)
+                .iter()
+                .enumerate()
+                .map(|(target, x)| {
+                    let target_shrinkage = training_data
+                        .target_shrinkage
+                        .as_ref()
+                        .map_or(1., |target_shrinkage| target_shrinkage[target]);
+                    learning_rate * target_shrinkage * x
+                })
                 .collect::<Vec<_>>();
             training_data.mutable_labels[i] =
                 add_f64_slices_as_vector(&training_data.mutable_labels[i], &weighted_leaf_output);
@@ -37,45 +44,398 @@ pub mod update_common {
     }
 }
 
+pub mod continue_common {
+    use common::datasets::MultiTargetDataSet;
+
+    use crate::boosting_ensemble::learning_rate_schedule::LearningRateSchedule;
+
+    //Common to AMGBoost, MultiClassBoost and RegressionBoost
+    //The new trees get the given learning rate, or carry on with that of the last tree
+    pub fn continued_learning_rate(learning_rate: Option<f64>, learning_rates: &[f64]) -> f64 {
+        let learning_rate = learning_rate
+            .or_else(|| learning_rates.last().copied())
+            .expect("an ensemble without trees needs a learning rate to continue training");
+        if let Err(message) = LearningRateSchedule::Constant(learning_rate).validate() {
+            panic!("{}", message);
+        }
+        learning_rate
+    }
+
+    //Common to AMGBoost, MultiClassBoost and RegressionBoost
+    pub fn check_continued_training_data(data: &MultiTargetDataSet, initial_guess: &[f64]) {
+        assert!(
+            data.number_of_rows() > 0,
+            "continued training needs at least one row"
+        );
+        assert_eq!(
+            data.number_of_targets(),
+            initial_guess.len(),
+            "the data has a different number of targets than the ensemble"
+        );
+    }
+}
+
 pub mod predict_common {
-    use multi_target_decision_tree::{leaf::GradBoostLeaf, node::TreeNode};
+    use multi_target_decision_tree::{
+        leaf::{GradBoostLeaf, Leaf},
+        node::TreeNode,
+        tree_traverse::find_leaf_node_for_data,
+    };
+    use rayon::prelude::*;
 
-    use crate::tree_traverse::find_leaf_node_for_data;
+    use crate::boosting_ensemble::boosting_types::{
+        BatchPredictionStrategy, LeafOutputAccumulator, Shrinkage,
+    };
 
     //Common to MultiClassBoost and RegressionBoost
     pub fn predict_instance(
         test_feature_row: &[f64],
-        trees: &Vec<Box<TreeNode<GradBoostLeaf>>>,
+        trees: &[Box<TreeNode<GradBoostLeaf>>],
         initial_guess: &[f64],
-        learning_rate: f64,
+        shrinkage: Shrinkage,
     ) -> Vec<f64> {
-        let test_instance_leaf_outputs =
-            collect_leaf_outputs_for_test_instance(test_feature_row, trees, learning_rate);
-        let mut sum_of_leaf_outputs = initial_guess.clone().to_owned();
-        for leaf_output in test_instance_leaf_outputs {
-            for i in 0..sum_of_leaf_outputs.len() {
-                sum_of_leaf_outputs[i] += leaf_output[i];
-            }
-        }
+        let mut sum_of_leaf_outputs = initial_guess.to_owned();
+        accumulate_leaf_outputs_for_test_instance(
+            test_feature_row,
+            trees,
+            shrinkage,
+            &mut sum_of_leaf_outputs,
+        );
         sum_of_leaf_outputs
     }
 
     //Common to MultiClassBoost and RegressionBoost
-    fn collect_leaf_outputs_for_test_instance(
+    //Adds the shrunk leaf output of every tree into the given buffer, no allocation per tree
+    pub fn accumulate_leaf_outputs_for_test_instance(
         test_feature_row: &[f64],
-        trees: &Vec<Box<TreeNode<GradBoostLeaf>>>,
-        learning_rate: f64,
+        trees: &[Box<TreeNode<GradBoostLeaf>>],
+        shrinkage: Shrinkage,
+        sum_of_leaf_outputs: &mut [f64],
+    ) {
+        for (tree_index, tree) in trees.iter().enumerate() {
+            let leaf = find_leaf_node_for_data(test_feature_row, tree);
+            let leaf_output = leaf.leaf_output.as_ref().unwrap();
+            for (target, (sum, output)) in
+                sum_of_leaf_outputs.iter_mut().zip(leaf_output).enumerate()
+            {
+                *sum += shrinkage.factor(tree_index, target) * output;
+            }
+        }
+    }
+
+    //Common to AMGBoost, MultiClassBoost and RegressionBoost
+    //Raw (untransformed) predictions for each row, rows are predicted in parallel and
+    //optionally each row's trees are split into chunks that are summed in parallel
+    pub fn predict_batch<L: Leaf + Sync>(
+        test_feature_rows: &[Vec<f64>],
+        trees: &[Box<TreeNode<L>>],
+        initial_guess: &[f64],
+        shrinkage: Shrinkage,
+        accumulate_leaf_outputs: LeafOutputAccumulator<L>,
+        strategy: BatchPredictionStrategy,
     ) -> Vec<Vec<f64>> {
-        let mut leaf_outputs = vec![];
-        for i in 0..trees.len() {
-            let leaf = find_leaf_node_for_data(test_feature_row, &trees[i]);
-            let leaf_output = &*leaf.leaf_output.as_ref().unwrap();
-            let weighted_leaf_output = leaf_output
-                .into_iter()
-                .map(|x| learning_rate * x)
-                .collect::<Vec<_>>();
-            leaf_outputs.push(weighted_leaf_output.clone());
+        match strategy {
+            BatchPredictionStrategy::ParallelRows => test_feature_rows
+                .par_iter()
+                .map(|test_feature_row| {
+                    let mut sum_of_leaf_outputs = initial_guess.to_owned();
+                    accumulate_leaf_outputs(
+                        test_feature_row,
+                        trees,
+                        shrinkage,
+                        &mut sum_of_leaf_outputs,
+                    );
+                    sum_of_leaf_outputs
+                })
+                .collect(),
+            BatchPredictionStrategy::ParallelRowsAndTrees { trees_per_chunk } => {
+                let number_of_targets = initial_guess.len();
+                let trees_per_chunk = trees_per_chunk.max(1);
+                test_feature_rows
+                    .par_iter()
+                    .map(|test_feature_row| {
+                        let sum_of_chunks = trees
+                            .par_chunks(trees_per_chunk)
+                            .enumerate()
+                            .fold(
+                                || vec![0.; number_of_targets],
+                                |mut partial_sum, (chunk_index, tree_chunk)| {
+                                    let first_tree = chunk_index * trees_per_chunk;
+                                    accumulate_leaf_outputs(
+                                        test_feature_row,
+                                        tree_chunk,
+                                        shrinkage
+                                            .of_trees(first_tree..first_tree + tree_chunk.len()),
+                                        &mut partial_sum,
+                                    );
+                                    partial_sum
+                                },
+                            )
+                            .reduce(
+                                || vec![0.; number_of_targets],
+                                |mut first, second| {
+                                    first
+                                        .iter_mut()
+                                        .zip(second)
+                                        .for_each(|(element, other)| *element += other);
+                                    first
+                                },
+                            );
+                        initial_guess
+                            .iter()
+                            .zip(sum_of_chunks)
+                            .map(|(guess, sum)| guess + sum)
+                            .collect()
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    //Common to AMGBoost, MultiClassBoost and RegressionBoost
+    //Yields the predictions for every row after each tree is added, i.e. the first item uses
+    //one tree and the last item uses the whole ensemble. Sums are carried between stages so
+    //each tree is only traversed once per row.
+    pub struct StagedPredictions<'a, L: Leaf> {
+        feature_rows: &'a [Vec<f64>],
+        trees: &'a [Box<TreeNode<L>>],
+        shrinkage: Shrinkage<'a>,
+        accumulate_leaf_outputs: LeafOutputAccumulator<L>,
+        transform_prediction: fn(&[f64]) -> Vec<f64>,
+        sums_of_leaf_outputs: Vec<Vec<f64>>,
+        number_of_trees_used: usize,
+    }
+
+    impl<'a, L: Leaf> StagedPredictions<'a, L> {
+        pub fn new(
+            feature_rows: &'a [Vec<f64>],
+            trees: &'a [Box<TreeNode<L>>],
+            initial_guess: &[f64],
+            shrinkage: Shrinkage<'a>,
+            accumulate_leaf_outputs: LeafOutputAccumulator<L>,
+            transform_prediction: fn(&[f64]) -> Vec<f64>,
+        ) -> Self {
+            Self {
+                feature_rows,
+                trees,
+                shrinkage,
+                accumulate_leaf_outputs,
+                transform_prediction,
+                sums_of_leaf_outputs: vec![initial_guess.to_owned(); feature_rows.len()],
+                number_of_trees_used: 0,
+            }
+        }
+    }
+
+    impl<'a, L: Leaf + Sync> Iterator for StagedPredictions<'a, L> {
+        type Item = Vec<Vec<f64>>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            if self.number_of_trees_used == self.trees.len() {
+                return None;
+            }
+            let next_tree_range = self.number_of_trees_used..self.number_of_trees_used + 1;
+            let next_tree = &self.trees[next_tree_range.clone()];
+            let shrinkage = self.shrinkage.of_trees(next_tree_range);
+            let accumulate_leaf_outputs = self.accumulate_leaf_outputs;
+            let transform_prediction = self.transform_prediction;
+            self.number_of_trees_used += 1;
+            let predictions = self
+                .sums_of_leaf_outputs
+                .par_iter_mut()
+                .zip(self.feature_rows)
+                .map(|(sum_of_leaf_outputs, feature_row)| {
+                    accumulate_leaf_outputs(feature_row, next_tree, shrinkage, sum_of_leaf_outputs);
+                    transform_prediction(sum_of_leaf_outputs)
+                })
+                .collect();
+            Some(predictions)
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            let remaining_stages = self.trees.len() - self.number_of_trees_used;
+            (remaining_stages, Some(remaining_stages))
+        }
+    }
+
+    //Regression predictions are used as is
+    pub fn raw_prediction(prediction: &[f64]) -> Vec<f64> {
+        prediction.to_owned()
+    }
+
+    //Output vector a tree's leaf adds to the sum, before the shrinkage
+    pub fn grad_boost_leaf_value(leaf: &GradBoostLeaf) -> Vec<f64> {
+        leaf.leaf_output.as_ref().unwrap().clone()
+    }
+}
+
+pub mod apply_common {
+    use multi_target_decision_tree::{
+        leaf::Leaf, node::TreeNode, tree_traverse::find_leaf_node_id_for_data,
+    };
+    use rayon::prelude::*;
+
+    //Common to AMGBoost, MultiClassBoost and RegressionBoost
+    //For each row, the id of the leaf reached in every tree of the ensemble
+    pub fn apply<L: Leaf + Sync>(
+        feature_rows: &[Vec<f64>],
+        trees: &[Box<TreeNode<L>>],
+    ) -> Vec<Vec<usize>> {
+        feature_rows
+            .par_iter()
+            .map(|feature_row| {
+                trees
+                    .iter()
+                    .map(|tree| find_leaf_node_id_for_data(feature_row, tree))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+pub mod shap_common {
+    use crate::boosting_ensemble::boosting_types::Shrinkage;
+    use multi_target_decision_tree::{
+        leaf::Leaf,
+        node::TreeNode,
+        tree_shap::{
+            calculate_shap_interaction_values, calculate_shap_values, ShapInteractionValues,
+            ShapValues,
+        },
+    };
+
+    //Common to AMGBoost, MultiClassBoost and RegressionBoost
+    //Explains the raw ensemble output, the initial guess plus the shrunk tree outputs,
+    //before it is turned into a class prediction. SHAP values are linear in the leaf values,
+    //so the target shrinkage is applied to the leaves and the learning rate to each tree.
+    pub fn shap_values<L: Leaf, F: Fn(&L) -> Vec<f64>>(
+        feature_row: &[f64],
+        trees: &[Box<TreeNode<L>>],
+        initial_guess: &[f64],
+        shrinkage: Shrinkage,
+        leaf_value: F,
+    ) -> ShapValues {
+        let mut shap_values = ShapValues::zeros(feature_row.len(), initial_guess.len());
+        shap_values.expected_value = initial_guess.to_vec();
+        let shrunk_leaf_value = |leaf: &L| shrinkage.scale_targets(leaf_value(leaf));
+        for (tree, learning_rate) in trees.iter().zip(shrinkage.learning_rates) {
+            let tree_shap_values = calculate_shap_values(tree, feature_row, shrunk_leaf_value);
+            shap_values.add_scaled(&tree_shap_values, *learning_rate);
+        }
+        shap_values
+    }
+
+    pub fn shap_interaction_values<L: Leaf, F: Fn(&L) -> Vec<f64>>(
+        feature_row: &[f64],
+        trees: &[Box<TreeNode<L>>],
+        initial_guess: &[f64],
+        shrinkage: Shrinkage,
+        leaf_value: F,
+    ) -> ShapInteractionValues {
+        let mut interaction_values =
+            ShapInteractionValues::zeros(feature_row.len(), initial_guess.len());
+        interaction_values.expected_value = initial_guess.to_vec();
+        let shrunk_leaf_value = |leaf: &L| shrinkage.scale_targets(leaf_value(leaf));
+        for (tree, learning_rate) in trees.iter().zip(shrinkage.learning_rates) {
+            let tree_interaction_values =
+                calculate_shap_interaction_values(tree, feature_row, shrunk_leaf_value);
+            interaction_values.add_scaled(&tree_interaction_values, *learning_rate);
+        }
+        interaction_values
+    }
+}
+
+pub mod decision_path_common {
+    use crate::boosting_ensemble::boosting_types::Shrinkage;
+    use multi_target_decision_tree::{
+        leaf::Leaf,
+        node::TreeNode,
+        tree_traverse::{find_decision_path, find_leaf_node_for_data, DecisionPath},
+    };
+
+    #[derive(Debug, Clone)]
+    pub struct TreeDecisionPath {
+        pub path: DecisionPath,
+        // The tree's leaf output multiplied by its shrinkage
+        pub contribution: Vec<f64>,
+    }
+
+    // The initial guess plus every tree's contribution is the raw ensemble output
+    #[derive(Debug, Clone)]
+    pub struct EnsembleDecisionPath {
+        pub initial_guess: Vec<f64>,
+        pub trees: Vec<TreeDecisionPath>,
+    }
+
+    impl EnsembleDecisionPath {
+        pub fn to_string(&self, feature_names: &[String]) -> String {
+            let mut sections = vec![format!("Initial guess {:?}", self.initial_guess)];
+            for (tree_index, tree) in self.trees.iter().enumerate() {
+                sections.push(format!(
+                    "Tree {} contributes {:?}\n{}",
+                    tree_index,
+                    tree.contribution,
+                    tree.path.to_string(feature_names)
+                ));
+            }
+            sections.join("\n")
+        }
+    }
+
+    //Common to AMGBoost, MultiClassBoost and RegressionBoost
+    pub fn decision_path<L: Leaf, F: Fn(&L) -> Vec<f64>>(
+        feature_row: &[f64],
+        trees: &[Box<TreeNode<L>>],
+        initial_guess: &[f64],
+        shrinkage: Shrinkage,
+        leaf_value: F,
+    ) -> EnsembleDecisionPath {
+        let trees = trees
+            .iter()
+            .zip(shrinkage.learning_rates)
+            .map(|(tree, learning_rate)| {
+                let leaf = find_leaf_node_for_data(feature_row, tree);
+                TreeDecisionPath {
+                    path: find_decision_path(feature_row, tree),
+                    contribution: shrinkage
+                        .scale_targets(leaf_value(leaf))
+                        .iter()
+                        .map(|value| learning_rate * value)
+                        .collect(),
+                }
+            })
+            .collect();
+        EnsembleDecisionPath {
+            initial_guess: initial_guess.to_vec(),
+            trees,
+        }
+    }
+}
+
+pub mod rule_common {
+    use multi_target_decision_tree::{
+        leaf::Leaf,
+        node::TreeNode,
+        rule_extraction::{extract_rules_from_trees, Rule},
+    };
+
+    use crate::boosting_ensemble::boosting_types::Shrinkage;
+
+    //Common to AMGBoost, MultiClassBoost and RegressionBoost
+    //The rules of a tree predict its leaf outputs times its shrinkage
+    pub fn extract_rules<L: Leaf, F: Fn(&L) -> Vec<f64>>(
+        trees: &[Box<TreeNode<L>>],
+        shrinkage: Shrinkage,
+        leaf_value: F,
+    ) -> Vec<Rule> {
+        let mut rules =
+            extract_rules_from_trees(trees, |leaf| shrinkage.scale_targets(leaf_value(leaf)));
+        for rule in &mut rules {
+            let learning_rate = shrinkage.learning_rates[rule.tree_index];
+            rule.prediction
+                .iter_mut()
+                .for_each(|prediction| *prediction *= learning_rate);
         }
-        leaf_outputs
+        rules
     }
 }
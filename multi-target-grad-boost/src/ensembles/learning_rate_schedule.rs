@@ -0,0 +1,204 @@
+use std::f64::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+// The learning rate of each boosting iteration. It is evaluated once per iteration and the rate is
+// stored with the tree, so predicting does not need the schedule.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LearningRateSchedule {
+    Constant(f64),
+    // Multiplied by factor every step_size iterations
+    StepDecay {
+        initial_rate: f64,
+        factor: f64,
+        step_size: u32,
+    },
+    // Multiplied by decay every iteration
+    Exponential {
+        initial_rate: f64,
+        decay: f64,
+    },
+    // Half a cosine from initial_rate towards minimum_rate over the run (Loshchilov and Hutter, 2017)
+    Cosine {
+        initial_rate: f64,
+        minimum_rate: f64,
+    },
+    // Rises linearly to the first rate of the schedule over warmup_iterations, then follows the
+    // schedule over the remaining iterations
+    Warmup {
+        warmup_iterations: u32,
+        schedule: Box<LearningRateSchedule>,
+    },
+}
+
+impl LearningRateSchedule {
+    // Iterations count from 0, number_of_iterations is the length of the whole run
+    pub fn learning_rate(&self, iteration: u32, number_of_iterations: u32) -> f64 {
+        match self {
+            LearningRateSchedule::Constant(learning_rate) => *learning_rate,
+            LearningRateSchedule::StepDecay {
+                initial_rate,
+                factor,
+                step_size,
+            } => initial_rate * factor.powi((iteration / step_size) as i32),
+            LearningRateSchedule::Exponential {
+                initial_rate,
+                decay,
+            } => initial_rate * decay.powi(iteration as i32),
+            LearningRateSchedule::Cosine {
+                initial_rate,
+                minimum_rate,
+            } => {
+                // iterations past the end of the run, e.g when continuing, stay at the minimum
+                let progress = (iteration as f64 / number_of_iterations.max(1) as f64).min(1.);
+                minimum_rate + (initial_rate - minimum_rate) * (1. + (PI * progress).cos()) / 2.
+            }
+            LearningRateSchedule::Warmup {
+                warmup_iterations,
+                schedule,
+            } => {
+                let remaining_iterations = number_of_iterations.saturating_sub(*warmup_iterations);
+                if iteration < *warmup_iterations {
+                    schedule.learning_rate(0, remaining_iterations) * (iteration + 1) as f64
+                        / *warmup_iterations as f64
+                } else {
+                    schedule.learning_rate(iteration - warmup_iterations, remaining_iterations)
+                }
+            }
+        }
+    }
+
+    // The rates of the iterations first_iteration..first_iteration + count of a run
+    pub fn learning_rates(
+        &self,
+        first_iteration: u32,
+        count: u32,
+        number_of_iterations: u32,
+    ) -> Vec<f64> {
+        (first_iteration..first_iteration + count)
+            .map(|iteration| self.learning_rate(iteration, number_of_iterations))
+            .collect()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let is_positive = |rate: f64| rate.is_finite() && rate > 0.;
+        let is_decay = |factor: f64| factor > 0. && factor <= 1.;
+        match self {
+            LearningRateSchedule::Constant(learning_rate) => {
+                if !is_positive(*learning_rate) {
+                    return Err("the learning rate must be positive".to_string());
+                }
+            }
+            LearningRateSchedule::StepDecay {
+                initial_rate,
+                factor,
+                step_size,
+            } => {
+                if !is_positive(*initial_rate) || !is_decay(*factor) || *step_size == 0 {
+                    return Err(
+                        "step decay needs a positive rate, a factor in (0, 1] and a step size"
+                            .to_string(),
+                    );
+                }
+            }
+            LearningRateSchedule::Exponential {
+                initial_rate,
+                decay,
+            } => {
+                if !is_positive(*initial_rate) || !is_decay(*decay) {
+                    return Err(
+                        "exponential decay needs a positive learning rate and a decay in (0, 1]"
+                            .to_string(),
+                    );
+                }
+            }
+            LearningRateSchedule::Cosine {
+                initial_rate,
+                minimum_rate,
+            } => {
+                if !is_positive(*initial_rate) || !(0. ..=*initial_rate).contains(minimum_rate) {
+                    return Err(
+                        "the cosine schedule needs a positive rate and a minimum from 0 to it"
+                            .to_string(),
+                    );
+                }
+            }
+            LearningRateSchedule::Warmup { schedule, .. } => schedule.validate()?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_rates_close(actual: &[f64], expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual_rate, expected_rate) in actual.iter().zip(expected) {
+            assert!((actual_rate - expected_rate).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_schedules() {
+        let constant = LearningRateSchedule::Constant(0.1);
+        assert_eq!(constant.learning_rates(0, 3, 3), vec![0.1; 3]);
+
+        let step_decay = LearningRateSchedule::StepDecay {
+            initial_rate: 0.4,
+            factor: 0.5,
+            step_size: 2,
+        };
+        assert_rates_close(
+            &step_decay.learning_rates(0, 5, 5),
+            &[0.4, 0.4, 0.2, 0.2, 0.1],
+        );
+
+        let exponential = LearningRateSchedule::Exponential {
+            initial_rate: 0.4,
+            decay: 0.5,
+        };
+        assert_rates_close(&exponential.learning_rates(1, 2, 5), &[0.2, 0.1]);
+
+        let cosine = LearningRateSchedule::Cosine {
+            initial_rate: 0.3,
+            minimum_rate: 0.1,
+        };
+        assert_rates_close(
+            &cosine.learning_rates(0, 3, 4),
+            &[0.3, 0.1 + 0.1 * (1. + 0.5f64.sqrt()), 0.2],
+        );
+        assert_rates_close(&cosine.learning_rates(4, 2, 4), &[0.1, 0.1]);
+
+        let warmup = LearningRateSchedule::Warmup {
+            warmup_iterations: 4,
+            schedule: Box::new(step_decay),
+        };
+        assert_rates_close(
+            &warmup.learning_rates(0, 7, 7),
+            &[0.1, 0.2, 0.3, 0.4, 0.4, 0.4, 0.2],
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(LearningRateSchedule::Constant(0.1).validate().is_ok());
+        assert!(LearningRateSchedule::Constant(0.).validate().is_err());
+        assert!(LearningRateSchedule::Exponential {
+            initial_rate: 0.1,
+            decay: 1.5
+        }
+        .validate()
+        .is_err());
+        assert!(LearningRateSchedule::Warmup {
+            warmup_iterations: 5,
+            schedule: Box::new(LearningRateSchedule::Cosine {
+                initial_rate: 0.1,
+                minimum_rate: 0.2
+            })
+        }
+        .validate()
+        .is_err());
+    }
+}
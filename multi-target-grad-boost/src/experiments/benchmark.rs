@@ -0,0 +1,293 @@
+use std::{error::Error, io::Write};
+
+use common::{
+    data_writer::write_markdown_table,
+    statistics::{friedman_test, mean_with_confidence_interval, FriedmanTest, MeanWithInterval},
+};
+
+use crate::experiment::{ExperimentResult, Metric};
+
+// A benchmark is an experiment run over several seeds, see experiments/benchmark_classification.toml
+// and experiments/benchmark_multi_target.toml. Seeds only change the train/test split, so the data
+// sets should leave out the test file.
+
+// The runs of one model on one data set
+#[derive(Clone, Debug)]
+pub struct BenchmarkRow {
+    pub dataset: String,
+    pub model: String,
+    pub number_of_runs: usize,
+    // In the order of the benchmark's metrics
+    pub metric_values: Vec<MeanWithInterval>,
+    pub training_seconds: MeanWithInterval,
+    pub prediction_seconds: MeanWithInterval,
+    pub model_bytes: MeanWithInterval,
+}
+
+// Friedman test of the first metric's means over the seeds, for the models run on every data set
+#[derive(Clone, Debug)]
+pub struct ModelComparison {
+    pub metric: Metric,
+    pub models: Vec<String>,
+    pub number_of_datasets: usize,
+    pub friedman_test: FriedmanTest,
+}
+
+#[derive(Clone, Debug)]
+pub struct BenchmarkSummary {
+    pub metrics: Vec<Metric>,
+    pub rows: Vec<BenchmarkRow>,
+    // None with fewer than two data sets or models
+    pub comparison: Option<ModelComparison>,
+}
+
+// Rows keep the order in which the data sets and models first appear in the results
+pub fn summarise_benchmark(metrics: &[Metric], results: &[ExperimentResult]) -> BenchmarkSummary {
+    let mut groups: Vec<(String, String, Vec<&ExperimentResult>)> = vec![];
+    for result in results {
+        let model = result.model_name();
+        match groups
+            .iter_mut()
+            .find(|(dataset, group_model, _)| *dataset == result.dataset && *group_model == model)
+        {
+            Some((_, _, group)) => group.push(result),
+            None => groups.push((result.dataset.clone(), model, vec![result])),
+        }
+    }
+
+    let rows: Vec<BenchmarkRow> = groups
+        .into_iter()
+        .map(|(dataset, model, group)| {
+            let interval = |value: &dyn Fn(&ExperimentResult) -> f64| {
+                let values: Vec<f64> = group.iter().map(|result| value(result)).collect();
+                mean_with_confidence_interval(&values)
+            };
+            BenchmarkRow {
+                number_of_runs: group.len(),
+                metric_values: (0..metrics.len())
+                    .map(|metric| interval(&|result| result.metric_values[metric]))
+                    .collect(),
+                training_seconds: interval(&|result| result.training_seconds),
+                prediction_seconds: interval(&|result| result.prediction_seconds),
+                model_bytes: interval(&|result| result.model_bytes as f64),
+                dataset,
+                model,
+            }
+        })
+        .collect();
+
+    BenchmarkSummary {
+        comparison: compare_models(metrics, &rows),
+        metrics: metrics.to_vec(),
+        rows,
+    }
+}
+
+fn compare_models(metrics: &[Metric], rows: &[BenchmarkRow]) -> Option<ModelComparison> {
+    let metric = *metrics.first()?;
+    let mut datasets: Vec<&String> = vec![];
+    for row in rows {
+        if !datasets.contains(&&row.dataset) {
+            datasets.push(&row.dataset);
+        }
+    }
+    let mut models: Vec<String> = vec![];
+    for row in rows {
+        let on_every_dataset = datasets.iter().all(|dataset| {
+            rows.iter()
+                .any(|other| other.dataset == **dataset && other.model == row.model)
+        });
+        if on_every_dataset && !models.contains(&row.model) {
+            models.push(row.model.clone());
+        }
+    }
+    if datasets.len() < 2 || models.len() < 2 {
+        return None;
+    }
+
+    let scores: Vec<Vec<f64>> = datasets
+        .iter()
+        .map(|dataset| {
+            models
+                .iter()
+                .map(|model| {
+                    rows.iter()
+                        .find(|row| row.dataset == **dataset && row.model == *model)
+                        .unwrap()
+                        .metric_values[0]
+                        .mean
+                })
+                .collect()
+        })
+        .collect();
+    Some(ModelComparison {
+        metric,
+        number_of_datasets: datasets.len(),
+        friedman_test: friedman_test(&scores, metric.higher_is_better()),
+        models,
+    })
+}
+
+impl BenchmarkSummary {
+    // Means with their 95% confidence intervals, then the average ranks and the Friedman/Nemenyi
+    // result if there is a comparison
+    pub fn write_markdown<W: Write>(&self, mut writer: W) -> Result<(), Box<dyn Error>> {
+        let mut header: Vec<String> = ["dataset", "model", "runs"]
+            .iter()
+            .map(|column| column.to_string())
+            .collect();
+        header.extend(self.metrics.iter().map(|metric| metric.name().to_string()));
+        header.extend(
+            ["training_seconds", "prediction_seconds", "model_bytes"]
+                .iter()
+                .map(|column| column.to_string()),
+        );
+        let records: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let mut record = vec![
+                    row.dataset.clone(),
+                    row.model.clone(),
+                    row.number_of_runs.to_string(),
+                ];
+                record.extend(
+                    row.metric_values
+                        .iter()
+                        .map(|value| format_interval(value, 4)),
+                );
+                record.push(format_interval(&row.training_seconds, 4));
+                record.push(format_interval(&row.prediction_seconds, 4));
+                record.push(format_interval(&row.model_bytes, 0));
+                record
+            })
+            .collect();
+        write_markdown_table(&mut writer, &header, &records)?;
+
+        let comparison = match &self.comparison {
+            Some(comparison) => comparison,
+            None => return Ok(()),
+        };
+        let test = &comparison.friedman_test;
+        writeln!(writer)?;
+        let mut ranked: Vec<usize> = (0..comparison.models.len()).collect();
+        ranked.sort_by(|first, second| {
+            test.average_ranks[*first]
+                .partial_cmp(&test.average_ranks[*second])
+                .unwrap()
+        });
+        let rank_records: Vec<Vec<String>> = ranked
+            .iter()
+            .map(|model| {
+                vec![
+                    comparison.models[*model].clone(),
+                    format!("{:.2}", test.average_ranks[*model]),
+                ]
+            })
+            .collect();
+        write_markdown_table(
+            &mut writer,
+            &[
+                "model".to_string(),
+                format!("average rank by {}", comparison.metric.name()),
+            ],
+            &rank_records,
+        )?;
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "Friedman test over {} data sets: chi squared {:.4} with {} degrees of freedom, \
+             p = {:.4}, Iman-Davenport F = {:.4}",
+            comparison.number_of_datasets,
+            test.chi_squared,
+            test.degrees_of_freedom,
+            test.p_value,
+            test.f_statistic
+        )?;
+        match test.critical_difference {
+            Some(critical_difference) => {
+                writeln!(
+                    writer,
+                    "Nemenyi critical difference at alpha = 0.05: {:.4}",
+                    critical_difference
+                )?;
+                for (first, second) in test.significantly_different_pairs() {
+                    writeln!(
+                        writer,
+                        "- {} and {} differ significantly",
+                        comparison.models[first], comparison.models[second]
+                    )?;
+                }
+            }
+            None => writeln!(writer, "No Nemenyi critical value for more than 20 models")?,
+        }
+        Ok(())
+    }
+}
+
+fn format_interval(value: &MeanWithInterval, decimals: usize) -> String {
+    format!(
+        "{:.*} ± {:.*}",
+        decimals, value.mean, decimals, value.half_width
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::saved_model::ModelType;
+
+    fn result(dataset: &str, max_levels: u32, seed: u64, accuracy: f64) -> ExperimentResult {
+        ExperimentResult {
+            dataset: dataset.to_string(),
+            model_type: ModelType::Tree,
+            max_levels,
+            number_of_iterations: None,
+            learning_rate: None,
+            seed,
+            training_seconds: 1.,
+            prediction_seconds: 0.1,
+            model_bytes: 100,
+            metric_values: vec![accuracy],
+        }
+    }
+
+    #[test]
+    fn test_summarise_benchmark() {
+        let results = vec![
+            result("a", 2, 0, 0.8),
+            result("a", 2, 1, 0.9),
+            result("a", 3, 0, 0.95),
+            result("a", 3, 1, 0.95),
+            result("b", 2, 0, 0.7),
+            result("b", 3, 0, 0.6),
+            result("c", 3, 0, 0.5),
+        ];
+        let summary = summarise_benchmark(&[Metric::Accuracy], &results);
+        assert_eq!(summary.rows.len(), 5);
+        assert_eq!(summary.rows[0].model, "tree(2)");
+        assert_eq!(summary.rows[0].number_of_runs, 2);
+        assert!((summary.rows[0].metric_values[0].mean - 0.85).abs() < 1e-12);
+        assert_eq!(summary.rows[1].metric_values[0].half_width, 0.);
+
+        // tree(2) is missing on c, leaving one model to compare
+        assert!(summary.comparison.is_none());
+        let comparison = summarise_benchmark(&[Metric::Accuracy], &results[..6])
+            .comparison
+            .unwrap();
+        assert_eq!(comparison.models, vec!["tree(2)", "tree(3)"]);
+        assert_eq!(comparison.friedman_test.average_ranks, vec![1.5, 1.5]);
+        // a data set seen again after another one is still compared once
+        let interleaved_results = [&results[4..5], &results[..4], &results[5..6]].concat();
+        let comparison = summarise_benchmark(&[Metric::Accuracy], &interleaved_results)
+            .comparison
+            .unwrap();
+        assert_eq!(comparison.number_of_datasets, 2);
+
+        let mut output = vec![];
+        summary.write_markdown(&mut output).unwrap();
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .contains("| a | tree(2) | 2 | 0.8500 ± 0.6353 |"));
+    }
+}
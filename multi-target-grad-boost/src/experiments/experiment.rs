@@ -0,0 +1,432 @@
+use std::{error::Error, fs, io::Write, path::Path, time::Instant};
+
+use common::{
+    data_reader::{try_read_csv_data_multi_target, try_read_csv_data_one_hot_multi_target},
+    data_writer::{write_csv_records_to_writer, write_markdown_table},
+    datasets::MultiTargetDataSet,
+    metrics::{classification::ClassificationReport, regression::RegressionReport},
+};
+use multi_target_decision_tree::{
+    decision_trees::TreeConfig,
+    split_finder::{SplitFinder, SplitMetric},
+};
+use serde::Deserialize;
+
+use crate::saved_model::{Model, ModelType};
+
+// An experiment file, TOML or JSON, e.g
+//
+// seeds = [1, 2]
+// metrics = ["accuracy", "macro_f1", "mean_squared_error"]
+//
+// [[datasets]]
+// name = "iris"
+// train = "common/data-files/iris.csv"
+// test = "common/data-files/iris_test.csv"
+// labels = { type = "class_column", number_of_classes = 3 }
+//
+// [[models]]
+// model_type = "amg-boost"
+// max_levels = [2, 3]
+// number_of_iterations = [50]
+// learning_rates = [0.1]
+//
+// Paths are relative to the working directory.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExperimentConfig {
+    pub datasets: Vec<DatasetConfig>,
+    pub models: Vec<ModelConfig>,
+    // Seeds the train/test split of data sets without a test file. The boosting models are
+    // deterministic, so with a test file each seed repeats the same run.
+    #[serde(default = "default_seeds")]
+    pub seeds: Vec<u64>,
+    pub metrics: Vec<Metric>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DatasetConfig {
+    pub name: String,
+    pub train: String,
+    pub test: Option<String>,
+    // Used when there is no test file
+    #[serde(default = "default_test_fraction")]
+    pub test_fraction: f64,
+    // Keeps the class shares of the labels' argmax in both parts of the split
+    #[serde(default)]
+    pub stratify: bool,
+    pub labels: LabelArrangement,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum LabelArrangement {
+    // The last column of the data files is a class, see read_csv_data_one_hot_multi_target
+    ClassColumn {
+        number_of_classes: usize,
+    },
+    // Separate label files with one column per target, see read_csv_data_multi_target
+    LabelFiles {
+        train_labels: String,
+        test_labels: Option<String>,
+    },
+}
+
+// Every combination of the listed values is run, trees only use max_levels
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModelConfig {
+    pub model_type: ModelType,
+    pub max_levels: Vec<u32>,
+    #[serde(default)]
+    pub number_of_iterations: Vec<u32>,
+    #[serde(default)]
+    pub learning_rates: Vec<f64>,
+    #[serde(default)]
+    pub use_multi_threading: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    Accuracy,
+    BalancedAccuracy,
+    MacroF1,
+    CohenKappa,
+    MeanSquaredError,
+    AverageRelativeRootMeanSquaredError,
+    AverageCorrelationCoefficient,
+}
+
+impl Metric {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Metric::Accuracy => "accuracy",
+            Metric::BalancedAccuracy => "balanced_accuracy",
+            Metric::MacroF1 => "macro_f1",
+            Metric::CohenKappa => "cohen_kappa",
+            Metric::MeanSquaredError => "mean_squared_error",
+            Metric::AverageRelativeRootMeanSquaredError => {
+                "average_relative_root_mean_squared_error"
+            }
+            Metric::AverageCorrelationCoefficient => "average_correlation_coefficient",
+        }
+    }
+
+    pub fn higher_is_better(&self) -> bool {
+        !matches!(
+            self,
+            Metric::MeanSquaredError | Metric::AverageRelativeRootMeanSquaredError
+        )
+    }
+
+    // Classification metrics take the largest entry of each vector as its class
+    fn calculate(&self, predictions: &[Vec<f64>], test_set: &MultiTargetDataSet) -> f64 {
+        let classification_report = || {
+            ClassificationReport::new_weighted(predictions, &test_set.labels, test_set.weights())
+        };
+        let regression_report =
+            || RegressionReport::new_weighted(predictions, &test_set.labels, test_set.weights());
+        match self {
+            Metric::Accuracy => classification_report().accuracy,
+            Metric::BalancedAccuracy => classification_report().balanced_accuracy,
+            Metric::MacroF1 => classification_report().macro_f1,
+            Metric::CohenKappa => classification_report().cohen_kappa,
+            Metric::MeanSquaredError => regression_report().overall_mean_squared_error,
+            Metric::AverageRelativeRootMeanSquaredError => {
+                regression_report().average_relative_root_mean_squared_error
+            }
+            Metric::AverageCorrelationCoefficient => {
+                regression_report().average_correlation_coefficient
+            }
+        }
+    }
+}
+
+fn default_seeds() -> Vec<u64> {
+    vec![0]
+}
+
+fn default_test_fraction() -> f64 {
+    0.25
+}
+
+#[derive(Debug, Clone)]
+pub struct ExperimentResult {
+    pub dataset: String,
+    pub model_type: ModelType,
+    pub max_levels: u32,
+    // None for trees
+    pub number_of_iterations: Option<u32>,
+    pub learning_rate: Option<f64>,
+    pub seed: u64,
+    pub training_seconds: f64,
+    pub prediction_seconds: f64,
+    // Size of the model as saved JSON, trees keep their leaves' training rows
+    pub model_bytes: usize,
+    // In the order of the config's metrics
+    pub metric_values: Vec<f64>,
+}
+
+impl ExperimentResult {
+    // The model type with its hyperparameters, e.g amg-boost(3, 50, 0.1)
+    pub fn model_name(&self) -> String {
+        match (self.number_of_iterations, self.learning_rate) {
+            (Some(number_of_iterations), Some(learning_rate)) => format!(
+                "{}({}, {}, {})",
+                self.model_type.name(),
+                self.max_levels,
+                number_of_iterations,
+                learning_rate
+            ),
+            _ => format!("{}({})", self.model_type.name(), self.max_levels),
+        }
+    }
+}
+
+impl ExperimentConfig {
+    // .json files are read as JSON, anything else as TOML
+    pub fn from_file(file_path: &str) -> Result<Self, Box<dyn Error>> {
+        let contents = fs::read_to_string(file_path)
+            .map_err(|error| format!("Failed to open {}: {}", file_path, error))?;
+        let config = match Path::new(file_path).extension() {
+            Some(extension) if extension == "json" => serde_json::from_str(&contents)
+                .map_err(|error| format!("{}: {}", file_path, error))?,
+            _ => Self::from_toml(&contents).map_err(|error| format!("{}: {}", file_path, error))?,
+        };
+        Ok(config)
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(toml::from_str(contents)?)
+    }
+}
+
+// Runs every data set, model combination and seed one after the other, so the timings are not
+// skewed by other runs. Data sets are read once per seed.
+pub fn run_experiment(config: &ExperimentConfig) -> Result<Vec<ExperimentResult>, Box<dyn Error>> {
+    let mut results = vec![];
+    for dataset_config in &config.datasets {
+        for seed in &config.seeds {
+            let (train_set, test_set) = load_dataset(dataset_config, *seed)?;
+            for model_config in &config.models {
+                for (max_levels, number_of_iterations, learning_rate) in
+                    model_combinations(model_config)?
+                {
+                    let tree_config = TreeConfig {
+                        split_finder: SplitFinder::new(SplitMetric::Variance),
+                        use_multi_threading: model_config.use_multi_threading,
+                        number_of_classes: train_set.number_of_targets() as u32,
+                        max_levels,
+                    };
+                    let before_training = Instant::now();
+                    let model = Model::train(
+                        model_config.model_type,
+                        train_set.clone(),
+                        tree_config,
+                        number_of_iterations.unwrap_or(0),
+                        learning_rate.unwrap_or(0.),
+                    );
+                    let training_seconds = before_training.elapsed().as_secs_f64();
+                    let before_prediction = Instant::now();
+                    let predictions = model.predict_batch(&test_set.feature_rows);
+                    let prediction_seconds = before_prediction.elapsed().as_secs_f64();
+                    let model_bytes = serde_json::to_vec(&model)?.len();
+
+                    results.push(ExperimentResult {
+                        dataset: dataset_config.name.clone(),
+                        model_type: model_config.model_type,
+                        max_levels,
+                        number_of_iterations,
+                        learning_rate,
+                        seed: *seed,
+                        training_seconds,
+                        prediction_seconds,
+                        model_bytes,
+                        metric_values: config
+                            .metrics
+                            .iter()
+                            .map(|metric| metric.calculate(&predictions, &test_set))
+                            .collect(),
+                    });
+                }
+            }
+        }
+    }
+    Ok(results)
+}
+
+// One line per run with its settings, timings and the config's metrics
+pub fn write_results_csv<W: Write>(
+    writer: W,
+    metrics: &[Metric],
+    results: &[ExperimentResult],
+) -> Result<(), Box<dyn Error>> {
+    let (header, records) = results_table(metrics, results);
+    write_csv_records_to_writer(writer, &header, &records)
+}
+
+pub fn write_results_markdown<W: Write>(
+    writer: W,
+    metrics: &[Metric],
+    results: &[ExperimentResult],
+) -> Result<(), Box<dyn Error>> {
+    let (header, records) = results_table(metrics, results);
+    write_markdown_table(writer, &header, &records)
+}
+
+fn results_table(
+    metrics: &[Metric],
+    results: &[ExperimentResult],
+) -> (Vec<String>, Vec<Vec<String>>) {
+    let mut header: Vec<String> = [
+        "dataset",
+        "model_type",
+        "max_levels",
+        "number_of_iterations",
+        "learning_rate",
+        "seed",
+        "training_seconds",
+        "prediction_seconds",
+        "model_bytes",
+    ]
+    .iter()
+    .map(|column| column.to_string())
+    .collect();
+    header.extend(metrics.iter().map(|metric| metric.name().to_string()));
+    let records = results
+        .iter()
+        .map(|result| {
+            let mut record = vec![
+                result.dataset.clone(),
+                result.model_type.name().to_string(),
+                result.max_levels.to_string(),
+                result
+                    .number_of_iterations
+                    .map_or(String::new(), |iterations| iterations.to_string()),
+                result
+                    .learning_rate
+                    .map_or(String::new(), |learning_rate| learning_rate.to_string()),
+                result.seed.to_string(),
+                format!("{:.4}", result.training_seconds),
+                format!("{:.4}", result.prediction_seconds),
+                result.model_bytes.to_string(),
+            ];
+            record.extend(
+                result
+                    .metric_values
+                    .iter()
+                    .map(|value| format!("{:.4}", value)),
+            );
+            record
+        })
+        .collect();
+    (header, records)
+}
+
+fn load_dataset(
+    config: &DatasetConfig,
+    seed: u64,
+) -> Result<(MultiTargetDataSet, MultiTargetDataSet), Box<dyn Error>> {
+    let read = |data_file: &str, label_file: Option<&String>| match &config.labels {
+        LabelArrangement::ClassColumn { number_of_classes } => {
+            try_read_csv_data_one_hot_multi_target(data_file, *number_of_classes)
+        }
+        LabelArrangement::LabelFiles { .. } => match label_file {
+            Some(label_file) => try_read_csv_data_multi_target(data_file, label_file),
+            None => Err(format!("{}: the test data has no label file", config.name).into()),
+        },
+    };
+    let (train_labels, test_labels) = match &config.labels {
+        LabelArrangement::ClassColumn { .. } => (None, None),
+        LabelArrangement::LabelFiles {
+            train_labels,
+            test_labels,
+        } => (Some(train_labels), test_labels.as_ref()),
+    };
+    let train_set = read(&config.train, train_labels)?;
+    match &config.test {
+        Some(test) => Ok((train_set, read(test, test_labels)?)),
+        None => Ok(train_set.train_test_split(config.test_fraction, config.stratify, seed)),
+    }
+}
+
+// (max_levels, number_of_iterations, learning_rate), the last two are None for trees
+type Combination = (u32, Option<u32>, Option<f64>);
+
+fn model_combinations(config: &ModelConfig) -> Result<Vec<Combination>, Box<dyn Error>> {
+    if config.model_type == ModelType::Tree {
+        return Ok(config
+            .max_levels
+            .iter()
+            .map(|max_levels| (*max_levels, None, None))
+            .collect());
+    }
+    if config.number_of_iterations.is_empty() || config.learning_rates.is_empty() {
+        return Err(format!(
+            "{} needs number_of_iterations and learning_rates",
+            config.model_type.name()
+        )
+        .into());
+    }
+    let mut combinations = vec![];
+    for max_levels in &config.max_levels {
+        for number_of_iterations in &config.number_of_iterations {
+            for learning_rate in &config.learning_rates {
+                combinations.push((
+                    *max_levels,
+                    Some(*number_of_iterations),
+                    Some(*learning_rate),
+                ));
+            }
+        }
+    }
+    Ok(combinations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_toml_config_and_combinations() {
+        let config = ExperimentConfig::from_toml(
+            r#"
+            metrics = ["accuracy", "mean_squared_error"]
+
+            [[datasets]]
+            name = "multi-target"
+            train = "features.csv"
+            labels = { type = "label_files", train_labels = "labels.csv" }
+
+            [[models]]
+            model_type = "tree"
+            max_levels = [2, 4]
+
+            [[models]]
+            model_type = "regression-boost"
+            max_levels = [3]
+            number_of_iterations = [10, 20]
+            learning_rates = [0.1, 0.2, 0.3]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.seeds, vec![0]);
+        assert_eq!(config.datasets[0].test_fraction, 0.25);
+        assert!(matches!(
+            config.datasets[0].labels,
+            LabelArrangement::LabelFiles { .. }
+        ));
+        assert_eq!(config.models[1].model_type, ModelType::RegressionBoost);
+        assert_eq!(model_combinations(&config.models[0]).unwrap().len(), 2);
+        assert_eq!(model_combinations(&config.models[1]).unwrap().len(), 6);
+
+        assert!(ExperimentConfig::from_toml("metrics = [\"precision\"]").is_err());
+        let missing_learning_rates = ModelConfig {
+            learning_rates: vec![],
+            ..config.models[1].clone()
+        };
+        assert!(model_combinations(&missing_learning_rates).is_err());
+    }
+}
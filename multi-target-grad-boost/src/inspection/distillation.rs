@@ -0,0 +1,136 @@
+use common::{
+    datasets::MultiTargetDataSet,
+    numerical_calculations::mean_sum_of_squared_differences_between_f64_slices,
+};
+use multi_target_decision_tree::decision_trees::{RegressionMultiTargetDecisionTree, TreeConfig};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::boosting_ensemble::{
+    common_multi_class_boosting_functions::predict_helper_functions::get_binary_prediction,
+    GradientBoostedEnsemble,
+};
+
+#[derive(Clone, Copy)]
+pub struct DistillationConfig {
+    pub tree_config: TreeConfig,
+    // Drawn uniformly and independently per feature within the training set's range
+    pub number_of_synthetic_samples: usize,
+    pub seed: u64,
+}
+
+// Fidelity compares the student tree with its teacher, scores compare both with the labels
+// using the teacher's calculate_score (accuracy for the classifiers, MSE for regression).
+#[derive(Debug, Clone)]
+pub struct DistillationReport {
+    pub fidelity_mean_squared_error: f64,
+    // Fraction of rows where student and teacher predict the same largest target
+    pub fidelity_agreement: f64,
+    pub teacher_score: f64,
+    pub student_score: f64,
+}
+
+// Born-again tree: fits one RegressionMultiTargetDecisionTree to the teacher's predictions on
+// the training rows plus synthetic rows, so the tree mimics the ensemble rather than the labels
+pub fn distill_ensemble<M: GradientBoostedEnsemble>(
+    teacher: &M,
+    training_set: &MultiTargetDataSet,
+    config: DistillationConfig,
+) -> RegressionMultiTargetDecisionTree {
+    let mut feature_rows = training_set.feature_rows.clone();
+    feature_rows.extend(draw_synthetic_rows(
+        &training_set.feature_columns,
+        config.number_of_synthetic_samples,
+        config.seed,
+    ));
+    let pseudo_labels = teacher.predict_batch(&feature_rows);
+    let distillation_set = MultiTargetDataSet::from_rows(feature_rows, pseudo_labels);
+    RegressionMultiTargetDecisionTree::new(distillation_set, config.tree_config)
+}
+
+pub fn evaluate_distillation<M: GradientBoostedEnsemble>(
+    teacher: &M,
+    student: &RegressionMultiTargetDecisionTree,
+    test_set: &MultiTargetDataSet,
+) -> DistillationReport {
+    let teacher_predictions = teacher.calculate_all_predictions(test_set);
+    let mut student_predictions = student.predict_batch(&test_set.feature_rows);
+    // the classifiers' predictions are one-hot, so the student's averages are turned into votes
+    if teacher.is_classifier() {
+        student_predictions = student_predictions
+            .iter()
+            .map(|prediction| get_binary_prediction(prediction))
+            .collect();
+    }
+
+    let number_of_rows = test_set.feature_rows.len() as f64;
+    let fidelity_mean_squared_error = teacher_predictions
+        .iter()
+        .zip(&student_predictions)
+        .map(|(teacher_prediction, student_prediction)| {
+            mean_sum_of_squared_differences_between_f64_slices(
+                teacher_prediction,
+                student_prediction,
+            )
+        })
+        .sum::<f64>()
+        / number_of_rows;
+    let number_of_agreements = teacher_predictions
+        .iter()
+        .zip(&student_predictions)
+        .filter(|(teacher_prediction, student_prediction)| {
+            get_binary_prediction(teacher_prediction) == get_binary_prediction(student_prediction)
+        })
+        .count();
+
+    DistillationReport {
+        fidelity_mean_squared_error,
+        fidelity_agreement: number_of_agreements as f64 / number_of_rows,
+        teacher_score: teacher.calculate_score_from_predictions(&teacher_predictions, test_set),
+        student_score: teacher.calculate_score_from_predictions(&student_predictions, test_set),
+    }
+}
+
+fn draw_synthetic_rows(
+    feature_columns: &[Vec<f64>],
+    number_of_rows: usize,
+    seed: u64,
+) -> Vec<Vec<f64>> {
+    let ranges: Vec<(f64, f64)> = feature_columns
+        .iter()
+        .map(|column| {
+            let min = column.iter().cloned().fold(f64::INFINITY, f64::min);
+            let max = column.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            (min, max)
+        })
+        .collect();
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..number_of_rows)
+        .map(|_| {
+            ranges
+                .iter()
+                .map(|(min, max)| match min < max {
+                    true => rng.gen_range(*min..*max),
+                    false => *min,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_synthetic_rows_stay_within_feature_ranges() {
+        let feature_columns = vec![vec![1., 3., 2.], vec![5., 5., 5.], vec![-1., 0., 4.]];
+        let synthetic_rows = draw_synthetic_rows(&feature_columns, 50, 3);
+        assert_eq!(synthetic_rows.len(), 50);
+        for row in &synthetic_rows {
+            assert!((1. ..=3.).contains(&row[0]));
+            assert_eq!(row[1], 5.);
+            assert!((-1. ..=4.).contains(&row[2]));
+        }
+        assert_eq!(draw_synthetic_rows(&feature_columns, 50, 3), synthetic_rows);
+    }
+}
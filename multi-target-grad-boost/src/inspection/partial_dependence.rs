@@ -0,0 +1,269 @@
+use std::{error::Error, io::Write};
+
+use common::{data_writer::write_csv_data_to_writer, datasets::MultiTargetDataSet};
+
+use crate::boosting_ensemble::GradientBoostedEnsemble;
+
+#[derive(Clone, Copy, Debug)]
+pub struct PartialDependenceConfig {
+    pub grid_resolution: usize,
+    // Grid values are quantiles of the training column between these two, e.g 0.05 and 0.95
+    pub lower_quantile: f64,
+    pub upper_quantile: f64,
+}
+
+impl Default for PartialDependenceConfig {
+    fn default() -> Self {
+        Self {
+            grid_resolution: 20,
+            lower_quantile: 0.05,
+            upper_quantile: 0.95,
+        }
+    }
+}
+
+// Partial dependence of every target on one or two features.
+// With two features the grid is their cartesian product, first feature varying slowest.
+#[derive(Debug, Clone)]
+pub struct PartialDependence {
+    pub features: Vec<usize>,
+    // One value per feature for each grid point
+    pub grid_points: Vec<Vec<f64>>,
+    // Indexed by grid point then target
+    pub average: Vec<Vec<f64>>,
+    // ICE curves, indexed by evaluated row, grid point then target
+    pub individual: Vec<Vec<Vec<f64>>>,
+}
+
+impl PartialDependence {
+    // Columns are the grid features followed by one column per target
+    pub fn write_average_csv<W: Write>(
+        &self,
+        writer: W,
+        feature_names: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        let rows: Vec<Vec<f64>> = self
+            .grid_points
+            .iter()
+            .zip(&self.average)
+            .map(|(grid_point, average)| [grid_point.as_slice(), average].concat())
+            .collect();
+        write_csv_data_to_writer(writer, &self.csv_header(feature_names, false), &rows)
+    }
+
+    // One line per evaluated row and grid point, the row index comes first
+    pub fn write_individual_csv<W: Write>(
+        &self,
+        writer: W,
+        feature_names: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        let mut rows = Vec::with_capacity(self.individual.len() * self.grid_points.len());
+        for (row_index, curve) in self.individual.iter().enumerate() {
+            for (grid_point, prediction) in self.grid_points.iter().zip(curve) {
+                rows.push([&[row_index as f64], grid_point.as_slice(), prediction].concat());
+            }
+        }
+        write_csv_data_to_writer(writer, &self.csv_header(feature_names, true), &rows)
+    }
+
+    fn csv_header(&self, feature_names: &[String], include_row_index: bool) -> Vec<String> {
+        let number_of_targets = self.average.first().map_or(0, |average| average.len());
+        let mut header = vec![];
+        if include_row_index {
+            header.push("row".to_string());
+        }
+        header.extend(
+            self.features
+                .iter()
+                .map(|feature| feature_names[*feature].clone()),
+        );
+        header.extend((0..number_of_targets).map(|target| format!("target_{}", target)));
+        header
+    }
+}
+
+// Evenly spaced quantiles of the column, with repeated values removed. Columns with no more
+// distinct values than the resolution use those values instead.
+pub fn build_quantile_grid(feature_column: &[f64], config: PartialDependenceConfig) -> Vec<f64> {
+    let mut sorted_column = feature_column.to_vec();
+    sorted_column.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mut distinct_values = sorted_column.clone();
+    distinct_values.dedup();
+    if distinct_values.len() <= config.grid_resolution {
+        return distinct_values;
+    }
+
+    let mut grid: Vec<f64> = (0..config.grid_resolution)
+        .map(|i| {
+            let fraction = match config.grid_resolution {
+                1 => 0.5,
+                _ => i as f64 / (config.grid_resolution - 1) as f64,
+            };
+            let quantile =
+                config.lower_quantile + fraction * (config.upper_quantile - config.lower_quantile);
+            calculate_quantile(&sorted_column, quantile)
+        })
+        .collect();
+    grid.dedup();
+    grid
+}
+
+// Linear interpolation between the closest ranks
+fn calculate_quantile(sorted_values: &[f64], quantile: f64) -> f64 {
+    let position = quantile * (sorted_values.len() - 1) as f64;
+    let lower_index = position.floor() as usize;
+    let upper_index = position.ceil() as usize;
+    let fraction = position - lower_index as f64;
+    sorted_values[lower_index]
+        + fraction * (sorted_values[upper_index] - sorted_values[lower_index])
+}
+
+// predict takes a batch of rows, the grid is built from the training set's columns and the
+// curves are evaluated on evaluation_rows (often the training rows themselves)
+pub fn calculate_partial_dependence<P>(
+    predict: P,
+    training_set: &MultiTargetDataSet,
+    evaluation_rows: &[Vec<f64>],
+    features: &[usize],
+    config: PartialDependenceConfig,
+) -> PartialDependence
+where
+    P: Fn(&[Vec<f64>]) -> Vec<Vec<f64>>,
+{
+    assert!(
+        features.len() == 1 || features.len() == 2,
+        "partial dependence is for one or two features"
+    );
+    let grids: Vec<Vec<f64>> = features
+        .iter()
+        .map(|feature| build_quantile_grid(&training_set.feature_columns[*feature], config))
+        .collect();
+    let grid_points: Vec<Vec<f64>> = match grids.len() {
+        1 => grids[0].iter().map(|value| vec![*value]).collect(),
+        _ => grids[0]
+            .iter()
+            .flat_map(|first| grids[1].iter().map(move |second| vec![*first, *second]))
+            .collect(),
+    };
+
+    let mut individual = vec![Vec::with_capacity(grid_points.len()); evaluation_rows.len()];
+    let mut average = Vec::with_capacity(grid_points.len());
+    let mut modified_rows = evaluation_rows.to_vec();
+    for grid_point in &grid_points {
+        for row in modified_rows.iter_mut() {
+            for (feature, value) in features.iter().zip(grid_point) {
+                row[*feature] = *value;
+            }
+        }
+        let predictions = predict(&modified_rows);
+        let mut sum_of_predictions = vec![0.; predictions.first().map_or(0, |p| p.len())];
+        for (curve, prediction) in individual.iter_mut().zip(predictions) {
+            for (sum, value) in sum_of_predictions.iter_mut().zip(&prediction) {
+                *sum += value;
+            }
+            curve.push(prediction);
+        }
+        let number_of_rows = evaluation_rows.len() as f64;
+        average.push(
+            sum_of_predictions
+                .iter()
+                .map(|sum| sum / number_of_rows)
+                .collect(),
+        );
+    }
+
+    PartialDependence {
+        features: features.to_vec(),
+        grid_points,
+        average,
+        individual,
+    }
+}
+
+// Uses the ensemble's batch prediction, so the classifiers give the fraction of rows
+// predicted as each class
+pub fn calculate_partial_dependence_for_ensemble<M: GradientBoostedEnsemble>(
+    model: &M,
+    training_set: &MultiTargetDataSet,
+    evaluation_rows: &[Vec<f64>],
+    features: &[usize],
+    config: PartialDependenceConfig,
+) -> PartialDependence {
+    calculate_partial_dependence(
+        |feature_rows| model.predict_batch(feature_rows),
+        training_set,
+        evaluation_rows,
+        features,
+        config,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_quantile_grid() {
+        let column: Vec<f64> = (0..101).map(|value| value as f64).collect();
+        let config = PartialDependenceConfig {
+            grid_resolution: 5,
+            lower_quantile: 0.,
+            upper_quantile: 1.,
+        };
+        assert_eq!(
+            build_quantile_grid(&column, config),
+            vec![0., 25., 50., 75., 100.]
+        );
+
+        let few_values = vec![3., 1., 3., 2., 1.];
+        assert_eq!(build_quantile_grid(&few_values, config), vec![1., 2., 3.]);
+    }
+
+    #[test]
+    fn test_partial_dependence_of_additive_model() {
+        let feature_rows = vec![vec![0., 1.], vec![1., 2.], vec![2., 3.], vec![3., 5.]];
+        let training_set =
+            MultiTargetDataSet::from_rows(feature_rows.clone(), vec![vec![0., 0.]; 4]);
+        // target 0 = x0 + x1, target 1 = x0 * x1
+        let predict = |rows: &[Vec<f64>]| {
+            rows.iter()
+                .map(|row| vec![row[0] + row[1], row[0] * row[1]])
+                .collect::<Vec<_>>()
+        };
+        let config = PartialDependenceConfig::default();
+
+        let one_way =
+            calculate_partial_dependence(predict, &training_set, &feature_rows, &[0], config);
+        assert_eq!(
+            one_way.grid_points,
+            vec![vec![0.], vec![1.], vec![2.], vec![3.]]
+        );
+        assert_eq!(one_way.average[2], vec![2. + 2.75, 2. * 2.75]);
+        assert_eq!(one_way.individual.len(), 4);
+        assert_eq!(one_way.individual[3][1], vec![6., 5.]);
+
+        let two_way =
+            calculate_partial_dependence(predict, &training_set, &feature_rows, &[0, 1], config);
+        assert_eq!(two_way.grid_points.len(), 16);
+        assert_eq!(two_way.grid_points[5], vec![1., 2.]);
+        assert_eq!(two_way.average[5], vec![3., 2.]);
+
+        let mut csv_output = vec![];
+        let feature_names = vec!["a".to_string(), "b".to_string()];
+        two_way
+            .write_average_csv(&mut csv_output, &feature_names)
+            .unwrap();
+        let csv_string = String::from_utf8(csv_output).unwrap();
+        let mut lines = csv_string.lines();
+        assert_eq!(lines.next(), Some("a,b,target_0,target_1"));
+        assert_eq!(lines.count(), 16);
+
+        let mut ice_output = vec![];
+        one_way
+            .write_individual_csv(&mut ice_output, &feature_names)
+            .unwrap();
+        let ice_string = String::from_utf8(ice_output).unwrap();
+        assert_eq!(ice_string.lines().next(), Some("row,a,target_0,target_1"));
+        assert_eq!(ice_string.lines().count(), 1 + 4 * 4);
+    }
+}
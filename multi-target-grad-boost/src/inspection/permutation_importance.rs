@@ -0,0 +1,270 @@
+use common::{
+    datasets::MultiTargetDataSet,
+    metrics::regression::calculate_weighted_mean_squared_error_per_target,
+    numerical_calculations::subtract_f64_slices_as_vector,
+};
+use multi_target_decision_tree::decision_trees::RegressionMultiTargetDecisionTree;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::boosting_ensemble::{boosting_types::ScoreDirection, GradientBoostedEnsemble};
+
+#[derive(Clone, Copy, Debug)]
+pub struct PermutationImportanceConfig {
+    pub number_of_repeats: usize,
+    pub seed: u64,
+}
+
+// Drops are oriented so that a positive value always means the model got worse once the
+// feature was shuffled. Per target drops are the increase in that target's mean squared error.
+#[derive(Debug, Clone)]
+pub struct PermutationImportance {
+    pub baseline_score: f64,
+    pub importances: Vec<Vec<f64>>,
+    pub importances_mean: Vec<f64>,
+    pub importances_std: Vec<f64>,
+    pub baseline_error_per_target: Vec<f64>,
+    pub importances_mean_per_target: Vec<Vec<f64>>,
+    pub importances_std_per_target: Vec<Vec<f64>>,
+}
+
+// Works for anything that can predict a dataset and score those predictions. The score is
+// taken from the predictions so each permutation is only predicted once.
+pub fn calculate_permutation_importance<P, S>(
+    test_set: &MultiTargetDataSet,
+    predict: P,
+    score: S,
+    score_direction: ScoreDirection,
+    config: PermutationImportanceConfig,
+) -> PermutationImportance
+where
+    P: Fn(&MultiTargetDataSet) -> Vec<Vec<f64>> + Sync,
+    S: Fn(&[Vec<f64>], &MultiTargetDataSet) -> f64 + Sync,
+{
+    assert!(config.number_of_repeats > 0);
+    let baseline_predictions = predict(test_set);
+    let baseline_score = score(&baseline_predictions, test_set);
+    let baseline_error_per_target = calculate_weighted_mean_squared_error_per_target(
+        &baseline_predictions,
+        &test_set.labels,
+        test_set.weights(),
+    );
+
+    let number_of_features = test_set.feature_columns.len();
+    // one seed per (feature, repeat), drawn up front so results don't depend on thread scheduling
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let seeds: Vec<Vec<u64>> = (0..number_of_features)
+        .map(|_| (0..config.number_of_repeats).map(|_| rng.gen()).collect())
+        .collect();
+    let drops_per_feature = (0..number_of_features)
+        .into_par_iter()
+        .map(|feature_index| {
+            (0..config.number_of_repeats)
+                .into_par_iter()
+                .map(|repeat| {
+                    let seed = seeds[feature_index][repeat];
+                    let permuted_set = permute_feature(test_set, feature_index, seed);
+                    let predictions = predict(&permuted_set);
+                    let score_drop = match score_direction {
+                        ScoreDirection::HigherIsBetter => {
+                            baseline_score - score(&predictions, &permuted_set)
+                        }
+                        ScoreDirection::LowerIsBetter => {
+                            score(&predictions, &permuted_set) - baseline_score
+                        }
+                    };
+                    let error_per_target = calculate_weighted_mean_squared_error_per_target(
+                        &predictions,
+                        &permuted_set.labels,
+                        permuted_set.weights(),
+                    );
+                    let target_drops = subtract_f64_slices_as_vector(
+                        &error_per_target,
+                        &baseline_error_per_target,
+                    );
+                    (score_drop, target_drops)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let number_of_targets = baseline_error_per_target.len();
+    let mut importances = Vec::with_capacity(number_of_features);
+    let mut importances_mean = Vec::with_capacity(number_of_features);
+    let mut importances_std = Vec::with_capacity(number_of_features);
+    let mut importances_mean_per_target = Vec::with_capacity(number_of_features);
+    let mut importances_std_per_target = Vec::with_capacity(number_of_features);
+    for drops in drops_per_feature {
+        let score_drops: Vec<f64> = drops.iter().map(|(score_drop, _)| *score_drop).collect();
+        let (mean, std) = calculate_mean_and_std(&score_drops);
+        importances_mean.push(mean);
+        importances_std.push(std);
+        importances.push(score_drops);
+
+        let (means, stds): (Vec<_>, Vec<_>) = (0..number_of_targets)
+            .map(|target| {
+                let target_drops: Vec<f64> = drops
+                    .iter()
+                    .map(|(_, target_drops)| target_drops[target])
+                    .collect();
+                calculate_mean_and_std(&target_drops)
+            })
+            .unzip();
+        importances_mean_per_target.push(means);
+        importances_std_per_target.push(stds);
+    }
+
+    PermutationImportance {
+        baseline_score,
+        importances,
+        importances_mean,
+        importances_std,
+        baseline_error_per_target,
+        importances_mean_per_target,
+        importances_std_per_target,
+    }
+}
+
+pub fn calculate_permutation_importance_for_ensemble<M>(
+    model: &M,
+    test_set: &MultiTargetDataSet,
+    config: PermutationImportanceConfig,
+) -> PermutationImportance
+where
+    M: GradientBoostedEnsemble + Sync,
+{
+    calculate_permutation_importance(
+        test_set,
+        |data| model.calculate_all_predictions(data),
+        |predictions, data| model.calculate_score_from_predictions(predictions, data),
+        model.score_direction(),
+        config,
+    )
+}
+
+// Single trees are scored by the mean squared error of their averaged leaf labels
+pub fn calculate_permutation_importance_for_tree(
+    tree: &RegressionMultiTargetDecisionTree,
+    test_set: &MultiTargetDataSet,
+    config: PermutationImportanceConfig,
+) -> PermutationImportance {
+    calculate_permutation_importance(
+        test_set,
+        |data| tree.predict_batch(&data.feature_rows),
+        |predictions, data| {
+            let error_per_target = calculate_weighted_mean_squared_error_per_target(
+                predictions,
+                &data.labels,
+                data.weights(),
+            );
+            error_per_target.iter().sum::<f64>() / error_per_target.len() as f64
+        },
+        ScoreDirection::LowerIsBetter,
+        config,
+    )
+}
+
+// Shuffles one feature across rows, keeping the row and column views of the copy consistent
+fn permute_feature(
+    test_set: &MultiTargetDataSet,
+    feature_index: usize,
+    seed: u64,
+) -> MultiTargetDataSet {
+    let mut permuted_set = test_set.clone();
+    let mut rng = StdRng::seed_from_u64(seed);
+    permuted_set.feature_columns[feature_index].shuffle(&mut rng);
+    for (row, value) in permuted_set
+        .feature_rows
+        .iter_mut()
+        .zip(&permuted_set.feature_columns[feature_index])
+    {
+        row[feature_index] = *value;
+    }
+    permuted_set
+}
+
+// Population standard deviation, matching the usual permutation importance reports
+fn calculate_mean_and_std(values: &[f64]) -> (f64, f64) {
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance = values
+        .iter()
+        .map(|value| (value - mean) * (value - mean))
+        .sum::<f64>()
+        / values.len() as f64;
+    (mean, variance.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::metrics::regression::calculate_mean_squared_error_per_target;
+
+    fn build_test_set() -> MultiTargetDataSet {
+        let feature_rows = vec![
+            vec![1., 10.],
+            vec![2., 20.],
+            vec![3., 30.],
+            vec![4., 40.],
+            vec![5., 50.],
+        ];
+        let labels = feature_rows.iter().map(|row| vec![row[0], 0.]).collect();
+        MultiTargetDataSet::from_rows(feature_rows, labels)
+    }
+
+    #[test]
+    fn test_permute_feature_keeps_rows_and_columns_consistent() {
+        let test_set = build_test_set();
+        let permuted_set = permute_feature(&test_set, 1, 7);
+        for (row_index, row) in permuted_set.feature_rows.iter().enumerate() {
+            assert_eq!(row[0], test_set.feature_rows[row_index][0]);
+            assert_eq!(row[1], permuted_set.feature_columns[1][row_index]);
+        }
+        let mut shuffled_column = permuted_set.feature_columns[1].clone();
+        shuffled_column.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(shuffled_column, test_set.feature_columns[1]);
+    }
+
+    #[test]
+    fn test_only_used_feature_is_important() {
+        let test_set = build_test_set();
+        let config = PermutationImportanceConfig {
+            number_of_repeats: 5,
+            seed: 42,
+        };
+        let predict = |data: &MultiTargetDataSet| {
+            data.feature_rows
+                .iter()
+                .map(|row| vec![row[0], 0.])
+                .collect::<Vec<_>>()
+        };
+        let score = |predictions: &[Vec<f64>], data: &MultiTargetDataSet| {
+            calculate_mean_squared_error_per_target(predictions, &data.labels)[0]
+        };
+        let importance = calculate_permutation_importance(
+            &test_set,
+            predict,
+            score,
+            ScoreDirection::LowerIsBetter,
+            config,
+        );
+        assert_eq!(importance.baseline_score, 0.);
+        assert!(importance.importances_mean[0] > 0.);
+        assert_eq!(importance.importances_mean[1], 0.);
+        assert_eq!(importance.importances_std[1], 0.);
+        assert_eq!(importance.importances[0].len(), 5);
+        assert_eq!(importance.importances_mean_per_target[0][1], 0.);
+        assert_eq!(
+            importance.importances_mean_per_target[0][0],
+            importance.importances_mean[0]
+        );
+
+        let repeated = calculate_permutation_importance(
+            &test_set,
+            predict,
+            score,
+            ScoreDirection::LowerIsBetter,
+            config,
+        );
+        assert_eq!(repeated.importances, importance.importances);
+    }
+}
@@ -0,0 +1,292 @@
+use std::{error::Error, io::Write};
+
+use common::{
+    data_writer::{write_csv_records_to_writer, write_markdown_table},
+    datasets::MultiTargetDataSet,
+};
+use multi_target_decision_tree::rule_extraction::Rule;
+use rayon::prelude::*;
+
+#[derive(Clone, Copy, Debug)]
+pub struct RuleFitConfig {
+    // Strength of the penalty, larger values keep fewer rules
+    pub alpha: f64,
+    pub max_iterations: usize,
+    // Stop once no coefficient changes by more than this in an iteration
+    pub tolerance: f64,
+}
+
+impl Default for RuleFitConfig {
+    fn default() -> Self {
+        Self {
+            alpha: 0.01,
+            max_iterations: 1000,
+            tolerance: 1e-6,
+        }
+    }
+}
+
+// Sparse linear model over rule activations (RuleFit without the linear terms).
+// The penalty is a multi-task lasso, so a rule is kept or dropped for all targets together.
+// Only the rules with a non-zero coefficient are kept.
+#[derive(Debug, Clone)]
+pub struct RuleFitModel {
+    pub rules: Vec<Rule>,
+    pub intercept: Vec<f64>,
+    // Indexed by rule then target
+    pub coefficients: Vec<Vec<f64>>,
+}
+
+impl RuleFitModel {
+    // Candidate rules would typically come from GradientBoostedEnsemble::extract_rules.
+    // Rules with duplicate conditions and rules every row matches are removed first.
+    pub fn fit(
+        candidate_rules: Vec<Rule>,
+        data: &MultiTargetDataSet,
+        config: RuleFitConfig,
+    ) -> Self {
+        let number_of_rows = data.labels.len();
+        let number_of_targets = data.labels[0].len();
+        let mut rules: Vec<Rule> = vec![];
+        for rule in candidate_rules {
+            let is_duplicate = rules
+                .iter()
+                .any(|kept_rule| kept_rule.conditions == rule.conditions);
+            let matches_every_row = data
+                .feature_rows
+                .iter()
+                .all(|feature_row| rule.matches(feature_row));
+            if !matches_every_row && !is_duplicate {
+                rules.push(rule);
+            }
+        }
+
+        let activations: Vec<Vec<f64>> = rules
+            .par_iter()
+            .map(|rule| {
+                data.feature_rows
+                    .iter()
+                    .map(|feature_row| if rule.matches(feature_row) { 1. } else { 0. })
+                    .collect()
+            })
+            .collect();
+        let activation_means: Vec<f64> = activations
+            .iter()
+            .map(|activation| activation.iter().sum::<f64>() / number_of_rows as f64)
+            .collect();
+        let label_means: Vec<f64> = (0..number_of_targets)
+            .map(|target| {
+                data.labels.iter().map(|label| label[target]).sum::<f64>() / number_of_rows as f64
+            })
+            .collect();
+
+        // block coordinate descent on centred activations and labels
+        let mut residuals: Vec<Vec<f64>> = data
+            .labels
+            .iter()
+            .map(|label| {
+                label
+                    .iter()
+                    .zip(&label_means)
+                    .map(|(y, mean)| y - mean)
+                    .collect()
+            })
+            .collect();
+        let mut coefficients = vec![vec![0.; number_of_targets]; rules.len()];
+        for _ in 0..config.max_iterations {
+            let mut max_change: f64 = 0.;
+            for (rule_index, activation) in activations.iter().enumerate() {
+                let mean = activation_means[rule_index];
+                // variance of a 0/1 column
+                let variance = mean * (1. - mean);
+                if variance == 0. {
+                    continue;
+                }
+                let old_coefficient = coefficients[rule_index].clone();
+                let mut correlation: Vec<f64> =
+                    old_coefficient.iter().map(|c| variance * c).collect();
+                for (value, residual) in activation.iter().zip(&residuals) {
+                    let centred_value = (value - mean) / number_of_rows as f64;
+                    for (sum, target_residual) in correlation.iter_mut().zip(residual) {
+                        *sum += centred_value * target_residual;
+                    }
+                }
+                let norm = correlation.iter().map(|c| c * c).sum::<f64>().sqrt();
+                let shrinkage = match norm > config.alpha {
+                    true => (1. - config.alpha / norm) / variance,
+                    false => 0.,
+                };
+                let new_coefficient: Vec<f64> = correlation.iter().map(|c| shrinkage * c).collect();
+                let change: Vec<f64> = new_coefficient
+                    .iter()
+                    .zip(&old_coefficient)
+                    .map(|(new, old)| new - old)
+                    .collect();
+                if change.iter().any(|delta| *delta != 0.) {
+                    for (value, residual) in activation.iter().zip(residuals.iter_mut()) {
+                        let centred_value = value - mean;
+                        for (target_residual, delta) in residual.iter_mut().zip(&change) {
+                            *target_residual -= centred_value * delta;
+                        }
+                    }
+                }
+                for delta in change {
+                    max_change = max_change.max(delta.abs());
+                }
+                coefficients[rule_index] = new_coefficient;
+            }
+            if max_change < config.tolerance {
+                break;
+            }
+        }
+
+        let mut intercept = label_means;
+        let mut kept_rules = vec![];
+        let mut kept_coefficients = vec![];
+        for ((rule, coefficient), mean) in rules.into_iter().zip(coefficients).zip(activation_means)
+        {
+            if coefficient.iter().all(|c| *c == 0.) {
+                continue;
+            }
+            for (target_intercept, c) in intercept.iter_mut().zip(&coefficient) {
+                *target_intercept -= mean * c;
+            }
+            kept_rules.push(rule);
+            kept_coefficients.push(coefficient);
+        }
+        Self {
+            rules: kept_rules,
+            intercept,
+            coefficients: kept_coefficients,
+        }
+    }
+
+    pub fn predict(&self, feature_row: &[f64]) -> Vec<f64> {
+        let mut prediction = self.intercept.clone();
+        for (rule, coefficient) in self.rules.iter().zip(&self.coefficients) {
+            if rule.matches(feature_row) {
+                for (value, c) in prediction.iter_mut().zip(coefficient) {
+                    *value += c;
+                }
+            }
+        }
+        prediction
+    }
+
+    pub fn predict_batch(&self, feature_rows: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        feature_rows
+            .par_iter()
+            .map(|feature_row| self.predict(feature_row))
+            .collect()
+    }
+
+    // One line per kept rule with its condition, support and coefficient per target
+    pub fn write_csv<W: Write>(
+        &self,
+        writer: W,
+        feature_names: &[String],
+        target_names: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        let (header, records) = self.coefficient_table(feature_names, target_names);
+        write_csv_records_to_writer(writer, &header, &records)
+    }
+
+    pub fn write_markdown<W: Write>(
+        &self,
+        writer: W,
+        feature_names: &[String],
+        target_names: &[String],
+    ) -> Result<(), Box<dyn Error>> {
+        let (header, records) = self.coefficient_table(feature_names, target_names);
+        write_markdown_table(writer, &header, &records)
+    }
+
+    fn coefficient_table(
+        &self,
+        feature_names: &[String],
+        target_names: &[String],
+    ) -> (Vec<String>, Vec<Vec<String>>) {
+        let mut header = vec!["rule".to_string(), "support".to_string()];
+        header.extend(
+            (0..self.intercept.len()).map(|target| match target_names.get(target) {
+                Some(target_name) => target_name.clone(),
+                None => format!("target_{}", target),
+            }),
+        );
+        let mut intercept_record = vec!["intercept".to_string(), "1.0000".to_string()];
+        intercept_record.extend(self.intercept.iter().map(|value| format!("{:.4}", value)));
+        let mut records = vec![intercept_record];
+        for (rule, coefficient) in self.rules.iter().zip(&self.coefficients) {
+            let mut record = vec![
+                rule.condition_to_string(feature_names),
+                format!("{:.4}", rule.support),
+            ];
+            record.extend(coefficient.iter().map(|value| format!("{:.4}", value)));
+            records.push(record);
+        }
+        (header, records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use multi_target_decision_tree::rule_extraction::FeatureInterval;
+
+    use super::*;
+
+    fn threshold_rule(feature: usize, threshold: f64) -> Rule {
+        Rule {
+            tree_index: 0,
+            leaf_id: 0,
+            conditions: vec![FeatureInterval {
+                feature,
+                lower_bound: Some(threshold),
+                upper_bound: None,
+            }],
+            number_of_samples: 0,
+            support: 0.5,
+            prediction: vec![],
+        }
+    }
+
+    #[test]
+    fn test_rule_fit_recovers_step_functions() {
+        // target 0 steps up by 2 at x0 >= 5, target 1 steps down by 1 at the same point
+        let feature_rows: Vec<Vec<f64>> = (0..10)
+            .map(|i| vec![i as f64, (i * 7 % 10) as f64])
+            .collect();
+        let labels = feature_rows
+            .iter()
+            .map(|row| match row[0] >= 5. {
+                true => vec![3., 0.],
+                false => vec![1., 1.],
+            })
+            .collect();
+        let data = MultiTargetDataSet::from_rows(feature_rows, labels);
+        let candidate_rules = vec![
+            threshold_rule(0, 5.),
+            threshold_rule(1, 5.),
+            threshold_rule(0, 5.),
+            threshold_rule(0, 0.),
+        ];
+        let config = RuleFitConfig {
+            alpha: 0.01,
+            ..RuleFitConfig::default()
+        };
+        let rule_fit = RuleFitModel::fit(candidate_rules, &data, config);
+
+        assert_eq!(rule_fit.rules.len(), 1);
+        assert_eq!(rule_fit.rules[0].conditions[0].feature, 0);
+        let high_prediction = rule_fit.predict(&[7., 0.]);
+        let low_prediction = rule_fit.predict(&[2., 0.]);
+        assert!((high_prediction[0] - low_prediction[0] - 2.).abs() < 0.1);
+        assert!((high_prediction[1] - low_prediction[1] + 1.).abs() < 0.1);
+
+        let mut markdown_output = vec![];
+        rule_fit
+            .write_markdown(&mut markdown_output, &["x".to_string()], &[])
+            .unwrap();
+        let markdown = String::from_utf8(markdown_output).unwrap();
+        assert!(markdown.contains("| x >= 5 |"));
+    }
+}
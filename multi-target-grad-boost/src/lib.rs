@@ -1,3 +1,22 @@
+#[path = "experiments/benchmark.rs"]
+pub mod benchmark;
 #[path = "ensembles/boosting_ensemble.rs"]
 pub mod boosting_ensemble;
-mod tree_traverse;
+#[path = "persistence/checkpoint.rs"]
+pub mod checkpoint;
+#[path = "model_selection/cross_validation.rs"]
+pub mod cross_validation;
+#[path = "inspection/distillation.rs"]
+pub mod distillation;
+#[path = "experiments/experiment.rs"]
+pub mod experiment;
+#[path = "model_selection/hyperparameter_search.rs"]
+pub mod hyperparameter_search;
+#[path = "inspection/partial_dependence.rs"]
+pub mod partial_dependence;
+#[path = "inspection/permutation_importance.rs"]
+pub mod permutation_importance;
+#[path = "inspection/rule_fit.rs"]
+pub mod rule_fit;
+#[path = "persistence/saved_model.rs"]
+pub mod saved_model;
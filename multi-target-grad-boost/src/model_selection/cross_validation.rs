@@ -0,0 +1,367 @@
+use common::{
+    datasets::MultiTargetDataSet,
+    metrics::{
+        classification::class_index,
+        regression::{calculate_weighted_mean_squared_error, RegressionReport},
+    },
+};
+use multi_target_decision_tree::decision_trees::{RegressionMultiTargetDecisionTree, TreeConfig};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use rayon::prelude::*;
+
+use crate::boosting_ensemble::{boosting_types::ScoreDirection, GradientBoostedEnsemble};
+
+#[derive(Clone, Debug)]
+pub enum FoldStrategy {
+    // Consecutive blocks of rows, shuffled first when shuffle is set
+    KFold { shuffle: bool },
+    // Each fold gets about the same share of every class, the class being the label's argmax
+    Stratified,
+    // One group id per row, all rows of a group end up in the same fold
+    Grouped(Vec<usize>),
+}
+
+#[derive(Clone, Debug)]
+pub struct CrossValidationConfig {
+    pub number_of_folds: usize,
+    pub strategy: FoldStrategy,
+    pub seed: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fold {
+    pub train_indices: Vec<usize>,
+    pub test_indices: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FoldResult {
+    pub score: f64,
+    pub regression_report: RegressionReport,
+}
+
+// Scores come from the model's calculate_score, out of fold predictions are in the data's row order
+#[derive(Debug, Clone)]
+pub struct CrossValidationResult {
+    pub folds: Vec<FoldResult>,
+    pub mean_score: f64,
+    pub std_score: f64,
+    pub score_direction: ScoreDirection,
+    pub out_of_fold_predictions: Vec<Vec<f64>>,
+    pub out_of_fold_regression_report: RegressionReport,
+}
+
+// Errors when the fold count is not from 2 to the number of rows, or a grouped strategy has
+// fewer groups than folds or not one group per row
+pub fn create_folds(
+    data: &MultiTargetDataSet,
+    config: &CrossValidationConfig,
+) -> Result<Vec<Fold>, String> {
+    let number_of_rows = data.labels.len();
+    let number_of_folds = config.number_of_folds;
+    if number_of_folds < 2 || number_of_folds > number_of_rows {
+        return Err(format!(
+            "the number of folds must be from 2 to the number of rows ({}), got {}",
+            number_of_rows, number_of_folds
+        ));
+    }
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut test_indices = vec![vec![]; number_of_folds];
+    match &config.strategy {
+        FoldStrategy::KFold { shuffle } => {
+            let mut indices: Vec<usize> = (0..number_of_rows).collect();
+            if *shuffle {
+                indices.shuffle(&mut rng);
+            }
+            // the first number_of_rows % number_of_folds folds take one extra row
+            let mut start = 0;
+            for (fold, fold_indices) in test_indices.iter_mut().enumerate() {
+                let fold_size = number_of_rows / number_of_folds
+                    + usize::from(fold < number_of_rows % number_of_folds);
+                fold_indices.extend_from_slice(&indices[start..start + fold_size]);
+                start += fold_size;
+            }
+        }
+        FoldStrategy::Stratified => {
+            let number_of_classes = data.labels[0].len();
+            let mut class_indices = vec![vec![]; number_of_classes];
+            for (index, label) in data.labels.iter().enumerate() {
+                class_indices[class_index(label)].push(index);
+            }
+            // dealing continues across classes so the fold sizes differ by at most one
+            let mut fold = 0;
+            for indices in class_indices.iter_mut() {
+                indices.shuffle(&mut rng);
+                for index in indices.iter() {
+                    test_indices[fold].push(*index);
+                    fold = (fold + 1) % number_of_folds;
+                }
+            }
+        }
+        FoldStrategy::Grouped(groups) => {
+            if groups.len() != number_of_rows {
+                return Err(format!(
+                    "{} groups for {} rows, every row needs a group",
+                    groups.len(),
+                    number_of_rows
+                ));
+            }
+            let number_of_groups = groups.iter().max().map_or(0, |group| group + 1);
+            let mut group_indices = vec![vec![]; number_of_groups];
+            for (index, group) in groups.iter().enumerate() {
+                group_indices[*group].push(index);
+            }
+            group_indices.retain(|indices| !indices.is_empty());
+            if group_indices.len() < number_of_folds {
+                return Err(format!(
+                    "{} groups can't fill {} folds",
+                    group_indices.len(),
+                    number_of_folds
+                ));
+            }
+            // largest groups first, each into the currently smallest fold
+            group_indices.sort_by_key(|indices| std::cmp::Reverse(indices.len()));
+            for indices in group_indices {
+                let smallest_fold = (0..number_of_folds)
+                    .min_by_key(|fold| test_indices[*fold].len())
+                    .unwrap();
+                test_indices[smallest_fold].extend(indices);
+            }
+        }
+    }
+
+    Ok(test_indices
+        .into_iter()
+        .map(|mut fold_test_indices| {
+            fold_test_indices.sort_unstable();
+            let mut is_test_row = vec![false; number_of_rows];
+            fold_test_indices
+                .iter()
+                .for_each(|index| is_test_row[*index] = true);
+            Fold {
+                train_indices: (0..number_of_rows)
+                    .filter(|index| !is_test_row[*index])
+                    .collect(),
+                test_indices: fold_test_indices,
+            }
+        })
+        .collect())
+}
+
+// What a model trained on a fold's training rows reports for the fold's test rows
+pub struct FoldPredictions {
+    pub predictions: Vec<Vec<f64>>,
+    pub score: f64,
+    pub score_direction: ScoreDirection,
+}
+
+// Folds are trained in parallel, fit_and_predict trains on the first set and predicts the second
+pub fn cross_validate_with<F>(
+    data: &MultiTargetDataSet,
+    config: &CrossValidationConfig,
+    fit_and_predict: F,
+) -> Result<CrossValidationResult, String>
+where
+    F: Fn(MultiTargetDataSet, &MultiTargetDataSet) -> FoldPredictions + Sync,
+{
+    // at least two folds, each with a test row, from here on
+    let folds = create_folds(data, config)?;
+    let fold_outputs: Vec<FoldPredictions> = folds
+        .par_iter()
+        .map(|fold| {
+            let train_set = data.subset(&fold.train_indices);
+            let test_set = data.subset(&fold.test_indices);
+            fit_and_predict(train_set, &test_set)
+        })
+        .collect();
+
+    let mut out_of_fold_predictions = vec![vec![]; data.labels.len()];
+    let mut fold_results = vec![];
+    let score_direction = fold_outputs[0].score_direction;
+    for (fold, fold_output) in folds.iter().zip(fold_outputs) {
+        let predictions = fold_output.predictions;
+        let fold_labels: Vec<Vec<f64>> = fold
+            .test_indices
+            .iter()
+            .map(|index| data.labels[*index].clone())
+            .collect();
+        let fold_weights: Option<Vec<f64>> = data.weights().map(|weights| {
+            fold.test_indices
+                .iter()
+                .map(|index| weights[*index])
+                .collect()
+        });
+        fold_results.push(FoldResult {
+            score: fold_output.score,
+            regression_report: RegressionReport::new_weighted(
+                &predictions,
+                &fold_labels,
+                fold_weights.as_deref(),
+            ),
+        });
+        for (index, prediction) in fold.test_indices.iter().zip(predictions) {
+            out_of_fold_predictions[*index] = prediction;
+        }
+    }
+
+    let scores: Vec<f64> = fold_results.iter().map(|fold| fold.score).collect();
+    let mean_score = scores.iter().sum::<f64>() / scores.len() as f64;
+    let std_score = (scores
+        .iter()
+        .map(|score| (score - mean_score) * (score - mean_score))
+        .sum::<f64>()
+        / scores.len() as f64)
+        .sqrt();
+    Ok(CrossValidationResult {
+        out_of_fold_regression_report: RegressionReport::new_weighted(
+            &out_of_fold_predictions,
+            &data.labels,
+            data.weights(),
+        ),
+        folds: fold_results,
+        mean_score,
+        std_score,
+        score_direction,
+        out_of_fold_predictions,
+    })
+}
+
+pub fn cross_validate_ensemble<M: GradientBoostedEnsemble>(
+    data: &MultiTargetDataSet,
+    tree_config: TreeConfig,
+    number_of_iterations: u32,
+    learning_rate: f64,
+    config: &CrossValidationConfig,
+) -> Result<CrossValidationResult, String> {
+    cross_validate_with(data, config, |train_set, test_set| {
+        let model = M::train(train_set, tree_config, number_of_iterations, learning_rate);
+        let predictions = model.calculate_all_predictions(test_set);
+        FoldPredictions {
+            score: model.calculate_score_from_predictions(&predictions, test_set),
+            score_direction: model.score_direction(),
+            predictions,
+        }
+    })
+}
+
+// Scored with the overall mean squared error
+pub fn cross_validate_tree(
+    data: &MultiTargetDataSet,
+    tree_config: TreeConfig,
+    config: &CrossValidationConfig,
+) -> Result<CrossValidationResult, String> {
+    cross_validate_with(data, config, |train_set, test_set| {
+        let tree = RegressionMultiTargetDecisionTree::new(train_set, tree_config);
+        let predictions = tree.predict_batch(&test_set.feature_rows);
+        FoldPredictions {
+            score: calculate_weighted_mean_squared_error(
+                &predictions,
+                &test_set.labels,
+                test_set.weights(),
+            ),
+            score_direction: ScoreDirection::LowerIsBetter,
+            predictions,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_with_classes(classes: &[usize]) -> MultiTargetDataSet {
+        let feature_rows = (0..classes.len()).map(|i| vec![i as f64]).collect();
+        let labels = classes
+            .iter()
+            .map(|class| {
+                let mut label = vec![0.; 2];
+                label[*class] = 1.;
+                label
+            })
+            .collect();
+        MultiTargetDataSet::from_rows(feature_rows, labels)
+    }
+
+    fn assert_partition(folds: &[Fold], number_of_rows: usize) {
+        let mut all_test_indices: Vec<usize> = folds
+            .iter()
+            .flat_map(|fold| fold.test_indices.clone())
+            .collect();
+        all_test_indices.sort_unstable();
+        assert_eq!(all_test_indices, (0..number_of_rows).collect::<Vec<_>>());
+        for fold in folds {
+            assert_eq!(
+                fold.train_indices.len() + fold.test_indices.len(),
+                number_of_rows
+            );
+        }
+    }
+
+    #[test]
+    fn test_k_fold_and_stratified_folds() {
+        let classes = [0, 0, 0, 0, 0, 0, 1, 1, 1, 0, 1];
+        let data = data_with_classes(&classes);
+        let mut config = CrossValidationConfig {
+            number_of_folds: 3,
+            strategy: FoldStrategy::KFold { shuffle: false },
+            seed: 1,
+        };
+        let folds = create_folds(&data, &config).unwrap();
+        assert_partition(&folds, classes.len());
+        assert_eq!(folds[0].test_indices, vec![0, 1, 2, 3]);
+        assert_eq!(folds[2].test_indices, vec![8, 9, 10]);
+
+        config.strategy = FoldStrategy::Stratified;
+        let folds = create_folds(&data, &config).unwrap();
+        assert_partition(&folds, classes.len());
+        for fold in &folds {
+            let class_one_count = fold
+                .test_indices
+                .iter()
+                .filter(|index| classes[**index] == 1)
+                .count();
+            assert!((1..=2).contains(&class_one_count));
+        }
+        assert_eq!(create_folds(&data, &config).unwrap(), folds);
+    }
+
+    #[test]
+    fn test_grouped_folds_keep_groups_together() {
+        let groups = vec![0, 0, 1, 1, 1, 2, 3, 3, 4, 4];
+        let data = data_with_classes(&[0; 10]);
+        let config = CrossValidationConfig {
+            number_of_folds: 3,
+            strategy: FoldStrategy::Grouped(groups.clone()),
+            seed: 1,
+        };
+        let folds = create_folds(&data, &config).unwrap();
+        assert_partition(&folds, groups.len());
+        for fold in &folds {
+            for index in &fold.test_indices {
+                assert!(fold
+                    .train_indices
+                    .iter()
+                    .all(|train_index| groups[*train_index] != groups[*index]));
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_folds_are_errors() {
+        let data = data_with_classes(&[0, 1, 0, 1]);
+        let config = |number_of_folds, strategy| CrossValidationConfig {
+            number_of_folds,
+            strategy,
+            seed: 1,
+        };
+        let k_fold = FoldStrategy::KFold { shuffle: false };
+        assert!(create_folds(&data, &config(4, k_fold.clone())).is_ok());
+        assert!(create_folds(&data, &config(1, k_fold.clone())).is_err());
+        assert!(create_folds(&data, &config(5, k_fold)).is_err());
+        assert!(create_folds(&data, &config(2, FoldStrategy::Grouped(vec![0, 1]))).is_err());
+        assert!(create_folds(&data, &config(3, FoldStrategy::Grouped(vec![0, 0, 1, 1]))).is_err());
+
+        let empty_data = MultiTargetDataSet::from_rows(vec![], vec![]);
+        assert!(create_folds(&empty_data, &config(2, FoldStrategy::Stratified)).is_err());
+    }
+}
@@ -0,0 +1,425 @@
+use std::{error::Error, io::Write};
+
+use common::{
+    data_writer::{write_csv_records_to_writer, write_markdown_table},
+    datasets::MultiTargetDataSet,
+};
+use multi_target_decision_tree::decision_trees::TreeConfig;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use rayon::prelude::*;
+
+use crate::{
+    boosting_ensemble::{boosting_types::ScoreDirection, GradientBoostedEnsemble},
+    cross_validation::{cross_validate_ensemble, CrossValidationConfig},
+};
+
+// Values to try for each setting, the other TreeConfig fields come from the base config
+#[derive(Clone, Debug)]
+pub struct ParameterSpace {
+    pub max_levels: Vec<u32>,
+    pub number_of_iterations: Vec<u32>,
+    pub learning_rates: Vec<f64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Candidate {
+    pub max_levels: u32,
+    pub number_of_iterations: u32,
+    pub learning_rate: f64,
+}
+
+impl ParameterSpace {
+    pub fn grid(&self) -> Vec<Candidate> {
+        let mut candidates = vec![];
+        for max_levels in &self.max_levels {
+            for number_of_iterations in &self.number_of_iterations {
+                for learning_rate in &self.learning_rates {
+                    candidates.push(Candidate {
+                        max_levels: *max_levels,
+                        number_of_iterations: *number_of_iterations,
+                        learning_rate: *learning_rate,
+                    });
+                }
+            }
+        }
+        candidates
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum SearchStrategy {
+    Grid,
+    // Distinct candidates drawn from the grid
+    Random {
+        number_of_candidates: usize,
+        seed: u64,
+    },
+    // Candidates are drawn as for Random, ignoring number_of_iterations, and start with
+    // minimum_iterations boosting iterations. After each round the best 1/reduction_factor
+    // survive with reduction_factor times more iterations, up to the largest number_of_iterations.
+    SuccessiveHalving {
+        number_of_candidates: usize,
+        seed: u64,
+        minimum_iterations: u32,
+        reduction_factor: usize,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct SearchConfig {
+    pub strategy: SearchStrategy,
+    pub cross_validation: CrossValidationConfig,
+    // Threads shared by the candidates and their folds
+    pub max_parallel_evaluations: usize,
+}
+
+impl SearchConfig {
+    pub fn validate(&self, space: &ParameterSpace) -> Result<(), String> {
+        if self.max_parallel_evaluations == 0 {
+            return Err("max_parallel_evaluations must be at least 1".to_string());
+        }
+        if space.max_levels.is_empty()
+            || space.number_of_iterations.is_empty()
+            || space.learning_rates.is_empty()
+        {
+            return Err(
+                "max_levels, number_of_iterations and learning_rates need at least one value"
+                    .to_string(),
+            );
+        }
+        match self.strategy {
+            SearchStrategy::Random {
+                number_of_candidates,
+                ..
+            }
+            | SearchStrategy::SuccessiveHalving {
+                number_of_candidates,
+                ..
+            } if number_of_candidates == 0 => {
+                return Err("number_of_candidates must be at least 1".to_string());
+            }
+            _ => {}
+        }
+        if let SearchStrategy::SuccessiveHalving {
+            minimum_iterations,
+            reduction_factor,
+            ..
+        } = self.strategy
+        {
+            if reduction_factor < 2 {
+                return Err("the reduction factor must be at least 2".to_string());
+            }
+            let maximum_iterations = space.number_of_iterations.iter().max().copied();
+            if minimum_iterations == 0 || Some(minimum_iterations) > maximum_iterations {
+                return Err(
+                    "minimum_iterations must be from 1 to the largest number_of_iterations"
+                        .to_string(),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SearchResult {
+    pub candidate: Candidate,
+    pub mean_score: f64,
+    pub std_score: f64,
+}
+
+// Results are ranked best first, for successive halving only the last round of each candidate
+// is kept. The best candidate is refit on all of the data.
+pub struct HyperparameterSearch<M: GradientBoostedEnsemble> {
+    pub results: Vec<SearchResult>,
+    pub score_direction: ScoreDirection,
+    pub best_candidate: Candidate,
+    pub best_model: M,
+}
+
+impl<M: GradientBoostedEnsemble> HyperparameterSearch<M> {
+    pub fn write_results_csv<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        let (header, records) = self.results_table();
+        write_csv_records_to_writer(writer, &header, &records)
+    }
+
+    pub fn write_results_markdown<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        let (header, records) = self.results_table();
+        write_markdown_table(writer, &header, &records)
+    }
+
+    fn results_table(&self) -> (Vec<String>, Vec<Vec<String>>) {
+        let header = [
+            "rank",
+            "max_levels",
+            "number_of_iterations",
+            "learning_rate",
+            "mean_score",
+            "std_score",
+        ]
+        .iter()
+        .map(|column| column.to_string())
+        .collect();
+        let records = self
+            .results
+            .iter()
+            .enumerate()
+            .map(|(rank, result)| {
+                vec![
+                    (rank + 1).to_string(),
+                    result.candidate.max_levels.to_string(),
+                    result.candidate.number_of_iterations.to_string(),
+                    result.candidate.learning_rate.to_string(),
+                    format!("{:.4}", result.mean_score),
+                    format!("{:.4}", result.std_score),
+                ]
+            })
+            .collect();
+        (header, records)
+    }
+}
+
+pub fn hyperparameter_search<M: GradientBoostedEnsemble>(
+    data: &MultiTargetDataSet,
+    base_tree_config: TreeConfig,
+    space: &ParameterSpace,
+    config: &SearchConfig,
+) -> Result<HyperparameterSearch<M>, String> {
+    config.validate(space)?;
+    let thread_pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(config.max_parallel_evaluations)
+        .build()
+        .map_err(|error| format!("Failed to build the thread pool: {}", error))?;
+    let evaluate = |candidates: &[Candidate]| {
+        let evaluations: Vec<(SearchResult, ScoreDirection)> = thread_pool.install(|| {
+            candidates
+                .par_iter()
+                .map(|candidate| {
+                    let tree_config = TreeConfig {
+                        max_levels: candidate.max_levels,
+                        ..base_tree_config
+                    };
+                    let cross_validation_result = cross_validate_ensemble::<M>(
+                        data,
+                        tree_config,
+                        candidate.number_of_iterations,
+                        candidate.learning_rate,
+                        &config.cross_validation,
+                    )?;
+                    let result = SearchResult {
+                        candidate: *candidate,
+                        mean_score: cross_validation_result.mean_score,
+                        std_score: cross_validation_result.std_score,
+                    };
+                    Ok((result, cross_validation_result.score_direction))
+                })
+                .collect::<Result<_, String>>()
+        })?;
+        let score_direction = evaluations[0].1;
+        let mut results: Vec<SearchResult> =
+            evaluations.into_iter().map(|(result, _)| result).collect();
+        rank_results(&mut results, score_direction);
+        Ok::<_, String>((results, score_direction))
+    };
+
+    let (results, score_direction) = match config.strategy {
+        SearchStrategy::Grid => evaluate(&space.grid())?,
+        SearchStrategy::Random {
+            number_of_candidates,
+            seed,
+        } => evaluate(&draw_candidates(space.grid(), number_of_candidates, seed))?,
+        SearchStrategy::SuccessiveHalving {
+            number_of_candidates,
+            seed,
+            minimum_iterations,
+            reduction_factor,
+        } => {
+            let maximum_iterations = *space.number_of_iterations.iter().max().unwrap();
+            let budget_space = ParameterSpace {
+                number_of_iterations: vec![minimum_iterations],
+                ..space.clone()
+            };
+            let mut candidates = draw_candidates(budget_space.grid(), number_of_candidates, seed);
+            let mut eliminated = vec![];
+            loop {
+                let (mut round_results, score_direction) = evaluate(&candidates)?;
+                let budget = candidates[0].number_of_iterations;
+                if round_results.len() == 1 || budget >= maximum_iterations {
+                    round_results.extend(eliminated);
+                    break (round_results, score_direction);
+                }
+                let number_of_survivors = (round_results.len() / reduction_factor).max(1);
+                let next_budget = (budget * reduction_factor as u32).min(maximum_iterations);
+                candidates = round_results[..number_of_survivors]
+                    .iter()
+                    .map(|result| Candidate {
+                        number_of_iterations: next_budget,
+                        ..result.candidate
+                    })
+                    .collect();
+                // earlier rounds rank below later ones
+                let mut round_eliminated = round_results.split_off(number_of_survivors);
+                round_eliminated.extend(eliminated);
+                eliminated = round_eliminated;
+            }
+        }
+    };
+
+    let best_candidate = results[0].candidate;
+    let best_tree_config = TreeConfig {
+        max_levels: best_candidate.max_levels,
+        ..base_tree_config
+    };
+    let best_model = M::train(
+        data.clone(),
+        best_tree_config,
+        best_candidate.number_of_iterations,
+        best_candidate.learning_rate,
+    );
+    Ok(HyperparameterSearch {
+        results,
+        score_direction,
+        best_candidate,
+        best_model,
+    })
+}
+
+fn draw_candidates(
+    mut grid: Vec<Candidate>,
+    number_of_candidates: usize,
+    seed: u64,
+) -> Vec<Candidate> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    grid.shuffle(&mut rng);
+    grid.truncate(number_of_candidates);
+    grid
+}
+
+// Candidates with a NaN score, e.g from a diverging learning rate, rank last
+fn rank_results(results: &mut [SearchResult], score_direction: ScoreDirection) {
+    results.sort_by(|first, second| {
+        let ordering = first.mean_score.total_cmp(&second.mean_score);
+        let ordering = match score_direction {
+            ScoreDirection::HigherIsBetter => ordering.reverse(),
+            ScoreDirection::LowerIsBetter => ordering,
+        };
+        first
+            .mean_score
+            .is_nan()
+            .cmp(&second.mean_score.is_nan())
+            .then(ordering)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cross_validation::FoldStrategy;
+
+    #[test]
+    fn test_grid_and_ranking() {
+        let space = ParameterSpace {
+            max_levels: vec![2, 3],
+            number_of_iterations: vec![10],
+            learning_rates: vec![0.1, 0.3, 0.5],
+        };
+        let grid = space.grid();
+        assert_eq!(grid.len(), 6);
+        let drawn = draw_candidates(grid.clone(), 4, 2);
+        assert_eq!(drawn.len(), 4);
+        for (index, candidate) in drawn.iter().enumerate() {
+            assert!(grid.contains(candidate));
+            assert!(!drawn[index + 1..].contains(candidate));
+        }
+
+        let mut results: Vec<SearchResult> = [0.3, 0.1, 0.2]
+            .iter()
+            .zip(&grid)
+            .map(|(score, candidate)| SearchResult {
+                candidate: *candidate,
+                mean_score: *score,
+                std_score: 0.,
+            })
+            .collect();
+        rank_results(&mut results, ScoreDirection::LowerIsBetter);
+        assert_eq!(results[0].mean_score, 0.1);
+        rank_results(&mut results, ScoreDirection::HigherIsBetter);
+        assert_eq!(results[0].mean_score, 0.3);
+
+        results[0].mean_score = f64::NAN;
+        rank_results(&mut results, ScoreDirection::HigherIsBetter);
+        assert_eq!(results[0].mean_score, 0.2);
+        assert!(results[2].mean_score.is_nan());
+        rank_results(&mut results, ScoreDirection::LowerIsBetter);
+        assert_eq!(results[0].mean_score, 0.1);
+        assert!(results[2].mean_score.is_nan());
+    }
+
+    #[test]
+    fn test_validate() {
+        let space = ParameterSpace {
+            max_levels: vec![2],
+            number_of_iterations: vec![10, 20],
+            learning_rates: vec![0.1],
+        };
+        let config =
+            |minimum_iterations, reduction_factor, max_parallel_evaluations| SearchConfig {
+                strategy: SearchStrategy::SuccessiveHalving {
+                    number_of_candidates: 2,
+                    seed: 0,
+                    minimum_iterations,
+                    reduction_factor,
+                },
+                cross_validation: CrossValidationConfig {
+                    number_of_folds: 3,
+                    strategy: FoldStrategy::KFold { shuffle: false },
+                    seed: 0,
+                },
+                max_parallel_evaluations,
+            };
+        assert!(config(5, 2, 1).validate(&space).is_ok());
+        assert!(config(20, 3, 1).validate(&space).is_ok());
+        // reduction factors of 0 and 1
+        assert!(config(5, 0, 1).validate(&space).is_err());
+        assert!(config(5, 1, 1).validate(&space).is_err());
+        // minimum iterations of 0 and above the largest number of iterations
+        assert!(config(0, 2, 1).validate(&space).is_err());
+        assert!(config(21, 2, 1).validate(&space).is_err());
+        assert!(config(5, 2, 0).validate(&space).is_err());
+
+        let mut no_candidates = config(5, 2, 1);
+        no_candidates.strategy = SearchStrategy::Random {
+            number_of_candidates: 0,
+            seed: 0,
+        };
+        assert!(no_candidates.validate(&space).is_err());
+        no_candidates.strategy = SearchStrategy::SuccessiveHalving {
+            number_of_candidates: 0,
+            seed: 0,
+            minimum_iterations: 5,
+            reduction_factor: 2,
+        };
+        assert!(no_candidates.validate(&space).is_err());
+        let mut grid = config(5, 2, 1);
+        grid.strategy = SearchStrategy::Grid;
+        assert!(grid.validate(&space).is_ok());
+        for empty_space in [
+            ParameterSpace {
+                max_levels: vec![],
+                ..space.clone()
+            },
+            ParameterSpace {
+                number_of_iterations: vec![],
+                ..space.clone()
+            },
+            ParameterSpace {
+                learning_rates: vec![],
+                ..space.clone()
+            },
+        ]
+        .iter()
+        {
+            assert!(grid.validate(empty_space).is_err());
+        }
+    }
+}
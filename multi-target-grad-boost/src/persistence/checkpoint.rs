@@ -0,0 +1,137 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use multi_target_decision_tree::{leaf::Leaf, node::TreeNode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::boosting_ensemble::{
+    boosting_types::{BoostingEnsembleType, MultiClassObjective},
+    learning_rate_schedule::LearningRateSchedule,
+};
+
+// Bumped whenever a change to Checkpoint makes older files unreadable
+pub const CHECKPOINT_FORMAT_VERSION: u32 = 2;
+
+// The boosting loop writes a checkpoint to the directory every every_n_iterations iterations and
+// once it is done, see Checkpoint::file_path for the file names
+#[derive(Clone, Debug)]
+pub struct CheckpointConfig {
+    pub directory: String,
+    pub every_n_iterations: u32,
+}
+
+// The state of the boosting loop after completed_iterations of number_of_iterations. Boosting
+// draws no random numbers, the next trees only depend on the training data and this state, so
+// there is no RNG state to store. The training data itself is not stored.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint<T: Leaf> {
+    pub format_version: u32,
+    pub ensemble_type: BoostingEnsembleType,
+    pub number_of_iterations: u32,
+    pub completed_iterations: u32,
+    // The parts of the tree config the trees depend on, a resumed run must use the same
+    pub max_levels: u32,
+    pub number_of_classes: u32,
+    // None for RegressionBoost, class_weights and focal_gamma are resolved from it
+    pub objective: Option<MultiClassObjective>,
+    pub learning_rate_schedule: LearningRateSchedule,
+    // The learning rate of each tree so far
+    pub learning_rates: Vec<f64>,
+    pub initial_guess: Vec<f64>,
+    // The raw predictions of the trees so far for each training row
+    pub mutable_labels: Vec<Vec<f64>>,
+    pub class_weights: Option<Vec<f64>>,
+    pub focal_gamma: Option<f64>,
+    pub target_shrinkage: Option<Vec<f64>>,
+    pub trees: Vec<Box<TreeNode<T>>>,
+}
+
+impl<T: Leaf + Serialize + DeserializeOwned> Checkpoint<T> {
+    // e.g checkpoint_000020.json, so the files sort by the number of completed iterations
+    pub fn file_path(directory: &str, completed_iterations: u32) -> PathBuf {
+        Path::new(directory).join(format!("checkpoint_{:06}.json", completed_iterations))
+    }
+
+    // Written to a temporary file first, so a crash while writing leaves the previous checkpoints
+    // intact
+    pub fn save(&self, directory: &str) -> Result<PathBuf, Box<dyn Error>> {
+        fs::create_dir_all(directory)
+            .map_err(|error| format!("Failed to create {}: {}", directory, error))?;
+        let file_path = Self::file_path(directory, self.completed_iterations);
+        let temporary_path = file_path.with_extension("json.tmp");
+        let file = File::create(&temporary_path)
+            .map_err(|error| format!("Failed to create {}: {}", temporary_path.display(), error))?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        fs::rename(&temporary_path, &file_path)?;
+        Ok(file_path)
+    }
+
+    pub fn load(file_path: &str) -> Result<Self, Box<dyn Error>> {
+        let contents = read_checkpoint_file(file_path)?;
+        Ok(serde_json::from_slice(&contents)
+            .map_err(|error| format!("{}: {}", file_path, error))?)
+    }
+}
+
+// What a checkpoint file is a checkpoint of, without reading its trees into a Checkpoint
+#[derive(Deserialize)]
+pub struct CheckpointHeader {
+    pub ensemble_type: BoostingEnsembleType,
+    pub objective: Option<MultiClassObjective>,
+}
+
+pub fn read_checkpoint_header(file_path: &str) -> Result<CheckpointHeader, Box<dyn Error>> {
+    let contents = read_checkpoint_file(file_path)?;
+    Ok(serde_json::from_slice(&contents).map_err(|error| format!("{}: {}", file_path, error))?)
+}
+
+// The ensemble a checkpoint file belongs to
+pub fn read_checkpoint_ensemble_type(
+    file_path: &str,
+) -> Result<BoostingEnsembleType, Box<dyn Error>> {
+    Ok(read_checkpoint_header(file_path)?.ensemble_type)
+}
+
+// The contents of a checkpoint file of the current format version. The version is checked before
+// the rest, whose layout may differ in other versions.
+fn read_checkpoint_file(file_path: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    #[derive(Deserialize)]
+    struct VersionHeader {
+        format_version: u32,
+    }
+    let contents =
+        fs::read(file_path).map_err(|error| format!("Failed to open {}: {}", file_path, error))?;
+    let header: VersionHeader =
+        serde_json::from_slice(&contents).map_err(|error| format!("{}: {}", file_path, error))?;
+    if header.format_version != CHECKPOINT_FORMAT_VERSION {
+        return Err(format!(
+            "{}: checkpoint format version {} is not supported, expected {}",
+            file_path, header.format_version, CHECKPOINT_FORMAT_VERSION
+        )
+        .into());
+    }
+    Ok(contents)
+}
+
+// The checkpoint with the most completed iterations in the directory, None if there is none
+pub fn find_latest_checkpoint(directory: &str) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    let mut checkpoint_paths: Vec<PathBuf> = fs::read_dir(directory)
+        .map_err(|error| format!("Failed to read {}: {}", directory, error))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|file_name| file_name.to_str())
+                .is_some_and(|file_name| {
+                    file_name.starts_with("checkpoint_") && file_name.ends_with(".json")
+                })
+        })
+        .collect();
+    checkpoint_paths.sort();
+    Ok(checkpoint_paths.pop())
+}
@@ -0,0 +1,412 @@
+use std::{
+    error::Error,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+};
+
+use common::datasets::MultiTargetDataSet;
+use multi_target_decision_tree::{
+    decision_trees::{RegressionMultiTargetDecisionTree, TreeConfig},
+    feature_importance::FeatureImportance,
+    printer::TreeDump,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    boosting_ensemble::{
+        boosting_types::{
+            AMGBoostModel, BoostingEnsembleType, MultiClassBoostModel, MultiClassObjective,
+            RegressionBoostModel,
+        },
+        learning_rate_schedule::LearningRateSchedule,
+        GradientBoostedEnsemble,
+    },
+    checkpoint::{read_checkpoint_ensemble_type, CheckpointConfig},
+};
+
+// Bumped whenever a change to the model structs makes older files unreadable
+pub const SAVED_MODEL_FORMAT_VERSION: u32 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ModelType {
+    Tree,
+    RegressionBoost,
+    MultiClassBoost,
+    AmgBoost,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum Model {
+    Tree(RegressionMultiTargetDecisionTree),
+    RegressionBoost(RegressionBoostModel),
+    MultiClassBoost(MultiClassBoostModel),
+    AMGBoost(AMGBoostModel),
+}
+
+// How the boosting ensembles are trained, a single tree ignores all of it
+#[derive(Clone, Debug)]
+pub struct BoostingOptions {
+    pub number_of_iterations: u32,
+    pub learning_rate_schedule: LearningRateSchedule,
+    // Only used by the multiclass ensembles
+    pub objective: MultiClassObjective,
+    // Only used by RegressionBoost
+    pub target_shrinkage: Option<Vec<f64>>,
+}
+
+impl BoostingOptions {
+    // A constant learning rate and the defaults for the rest
+    pub fn new(number_of_iterations: u32, learning_rate: f64) -> Self {
+        Self {
+            number_of_iterations,
+            learning_rate_schedule: LearningRateSchedule::Constant(learning_rate),
+            objective: MultiClassObjective::default(),
+            target_shrinkage: None,
+        }
+    }
+}
+
+impl ModelType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ModelType::Tree => "tree",
+            ModelType::RegressionBoost => "regression-boost",
+            ModelType::MultiClassBoost => "multi-class-boost",
+            ModelType::AmgBoost => "amg-boost",
+        }
+    }
+}
+
+impl Model {
+    // The number of iterations and the learning rate are ignored for a tree
+    pub fn train(
+        model_type: ModelType,
+        data: MultiTargetDataSet,
+        tree_config: TreeConfig,
+        number_of_iterations: u32,
+        learning_rate: f64,
+    ) -> Self {
+        Self::train_with_options(
+            model_type,
+            data,
+            tree_config,
+            BoostingOptions::new(number_of_iterations, learning_rate),
+        )
+    }
+
+    pub fn train_with_options(
+        model_type: ModelType,
+        data: MultiTargetDataSet,
+        tree_config: TreeConfig,
+        options: BoostingOptions,
+    ) -> Self {
+        let BoostingOptions {
+            number_of_iterations,
+            learning_rate_schedule,
+            objective,
+            target_shrinkage,
+        } = options;
+        match model_type {
+            ModelType::Tree => {
+                Model::Tree(RegressionMultiTargetDecisionTree::new(data, tree_config))
+            }
+            ModelType::RegressionBoost => {
+                Model::RegressionBoost(RegressionBoostModel::train_with_target_shrinkage(
+                    data,
+                    tree_config,
+                    number_of_iterations,
+                    &learning_rate_schedule,
+                    target_shrinkage,
+                ))
+            }
+            ModelType::MultiClassBoost => {
+                Model::MultiClassBoost(MultiClassBoostModel::train_with_objective(
+                    data,
+                    tree_config,
+                    number_of_iterations,
+                    &learning_rate_schedule,
+                    objective,
+                ))
+            }
+            ModelType::AmgBoost => Model::AMGBoost(AMGBoostModel::train_with_objective(
+                data,
+                tree_config,
+                number_of_iterations,
+                &learning_rate_schedule,
+                objective,
+            )),
+        }
+    }
+
+    // As train_with_options, writing checkpoints. Single trees have no checkpoints.
+    pub fn train_with_checkpoints(
+        model_type: ModelType,
+        data: MultiTargetDataSet,
+        tree_config: TreeConfig,
+        options: BoostingOptions,
+        checkpoint_config: &CheckpointConfig,
+    ) -> Result<Self, Box<dyn Error>> {
+        let BoostingOptions {
+            number_of_iterations,
+            learning_rate_schedule,
+            objective,
+            target_shrinkage,
+        } = options;
+        Ok(match model_type {
+            ModelType::Tree => return Err("a single tree is not trained in iterations".into()),
+            ModelType::RegressionBoost => {
+                Model::RegressionBoost(RegressionBoostModel::train_with_checkpoints(
+                    data,
+                    tree_config,
+                    number_of_iterations,
+                    &learning_rate_schedule,
+                    target_shrinkage,
+                    checkpoint_config,
+                )?)
+            }
+            ModelType::MultiClassBoost => {
+                Model::MultiClassBoost(MultiClassBoostModel::train_with_checkpoints(
+                    data,
+                    tree_config,
+                    number_of_iterations,
+                    &learning_rate_schedule,
+                    objective,
+                    checkpoint_config,
+                )?)
+            }
+            ModelType::AmgBoost => Model::AMGBoost(AMGBoostModel::train_with_checkpoints(
+                data,
+                tree_config,
+                number_of_iterations,
+                &learning_rate_schedule,
+                objective,
+                checkpoint_config,
+            )?),
+        })
+    }
+
+    // Finishes the run of a checkpoint of the given model type, see resume_from of the ensembles
+    pub fn resume_from(
+        model_type: ModelType,
+        checkpoint_path: &str,
+        data: MultiTargetDataSet,
+        tree_config: TreeConfig,
+        checkpoint_config: Option<&CheckpointConfig>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let checkpoint_model_type = match read_checkpoint_ensemble_type(checkpoint_path)? {
+            BoostingEnsembleType::RegressionBoost => ModelType::RegressionBoost,
+            BoostingEnsembleType::MultiClassBoost => ModelType::MultiClassBoost,
+            BoostingEnsembleType::AMGBoost => ModelType::AmgBoost,
+        };
+        if checkpoint_model_type != model_type {
+            return Err(format!(
+                "{} is a checkpoint of {}, not {}",
+                checkpoint_path,
+                checkpoint_model_type.name(),
+                model_type.name()
+            )
+            .into());
+        }
+        Ok(match model_type {
+            ModelType::Tree => unreachable!(),
+            ModelType::RegressionBoost => {
+                Model::RegressionBoost(RegressionBoostModel::resume_from(
+                    checkpoint_path,
+                    data,
+                    tree_config,
+                    checkpoint_config,
+                )?)
+            }
+            ModelType::MultiClassBoost => {
+                Model::MultiClassBoost(MultiClassBoostModel::resume_from(
+                    checkpoint_path,
+                    data,
+                    tree_config,
+                    checkpoint_config,
+                )?)
+            }
+            ModelType::AmgBoost => Model::AMGBoost(AMGBoostModel::resume_from(
+                checkpoint_path,
+                data,
+                tree_config,
+                checkpoint_config,
+            )?),
+        })
+    }
+
+    // Appends trees to a boosting ensemble, e.g to fine-tune a saved model on fresh data, see
+    // GradientBoostedEnsemble::continue_training. The objective is only used by the multiclass
+    // ensembles and should be the one they were trained with, see SavedModel::objective.
+    pub fn continue_training(
+        &mut self,
+        data: MultiTargetDataSet,
+        tree_config: TreeConfig,
+        extra_iterations: u32,
+        learning_rate: Option<f64>,
+        objective: MultiClassObjective,
+    ) -> Result<(), Box<dyn Error>> {
+        if learning_rate.is_none() && self.number_of_trees() == 0 {
+            return Err("an ensemble without trees needs a learning rate to continue".into());
+        }
+        if let Some(learning_rate) = learning_rate {
+            LearningRateSchedule::Constant(learning_rate).validate()?;
+        }
+        match self {
+            Model::Tree(_) => return Err("a single tree cannot be trained further".into()),
+            Model::RegressionBoost(model) => {
+                model.continue_training(data, tree_config, extra_iterations, learning_rate)
+            }
+            Model::MultiClassBoost(model) => model.continue_training_with_objective(
+                data,
+                tree_config,
+                extra_iterations,
+                learning_rate,
+                objective,
+            ),
+            Model::AMGBoost(model) => model.continue_training_with_objective(
+                data,
+                tree_config,
+                extra_iterations,
+                learning_rate,
+                objective,
+            ),
+        }
+        Ok(())
+    }
+
+    pub fn model_type(&self) -> ModelType {
+        match self {
+            Model::Tree(_) => ModelType::Tree,
+            Model::RegressionBoost(_) => ModelType::RegressionBoost,
+            Model::MultiClassBoost(_) => ModelType::MultiClassBoost,
+            Model::AMGBoost(_) => ModelType::AmgBoost,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        self.model_type().name()
+    }
+
+    // The multiclass ensembles predict one-hot vectors
+    pub fn is_classifier(&self) -> bool {
+        matches!(self, Model::MultiClassBoost(_) | Model::AMGBoost(_))
+    }
+
+    pub fn number_of_trees(&self) -> usize {
+        match self {
+            Model::Tree(_) => 1,
+            Model::RegressionBoost(model) => model.trees.len(),
+            Model::MultiClassBoost(model) => model.trees.len(),
+            Model::AMGBoost(model) => model.trees.len(),
+        }
+    }
+
+    pub fn predict_batch(&self, feature_rows: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        match self {
+            Model::Tree(tree) => tree.predict_batch(feature_rows),
+            Model::RegressionBoost(model) => model.predict_batch(feature_rows),
+            Model::MultiClassBoost(model) => model.predict_batch(feature_rows),
+            Model::AMGBoost(model) => model.predict_batch(feature_rows),
+        }
+    }
+
+    pub fn feature_importance(&self, number_of_features: usize) -> FeatureImportance {
+        match self {
+            Model::Tree(tree) => FeatureImportance::from_tree(&tree.root, number_of_features),
+            Model::RegressionBoost(model) => model.feature_importance(number_of_features),
+            Model::MultiClassBoost(model) => model.feature_importance(number_of_features),
+            Model::AMGBoost(model) => model.feature_importance(number_of_features),
+        }
+    }
+
+    pub fn write_text<W: Write>(&self, writer: &mut W, tree_dump: &TreeDump) -> io::Result<()> {
+        match self {
+            Model::Tree(tree) => tree_dump.write_text(writer, &tree.root),
+            Model::RegressionBoost(model) => tree_dump.write_text_for_trees(writer, &model.trees),
+            Model::MultiClassBoost(model) => tree_dump.write_text_for_trees(writer, &model.trees),
+            Model::AMGBoost(model) => tree_dump.write_text_for_trees(writer, &model.trees),
+        }
+    }
+
+    pub fn write_json<W: Write>(
+        &self,
+        writer: W,
+        tree_dump: &TreeDump,
+    ) -> Result<(), Box<dyn Error>> {
+        match self {
+            Model::Tree(tree) => tree_dump.write_json(writer, &tree.root),
+            Model::RegressionBoost(model) => tree_dump.write_json_for_trees(writer, &model.trees),
+            Model::MultiClassBoost(model) => tree_dump.write_json_for_trees(writer, &model.trees),
+            Model::AMGBoost(model) => tree_dump.write_json_for_trees(writer, &model.trees),
+        }
+    }
+}
+
+// A model with the feature and target names it was trained with, stored as JSON.
+// Tree models keep their leaves' training rows, so they are much larger than the ensembles.
+#[derive(Serialize, Deserialize)]
+pub struct SavedModel {
+    pub format_version: u32,
+    pub feature_names: Vec<String>,
+    pub target_names: Vec<String>,
+    pub model: Model,
+    // What a multiclass ensemble was trained with, so continued training keeps it
+    pub objective: MultiClassObjective,
+}
+
+impl SavedModel {
+    // With the default objective, see with_objective
+    pub fn new(model: Model, feature_names: Vec<String>, target_names: Vec<String>) -> Self {
+        Self {
+            format_version: SAVED_MODEL_FORMAT_VERSION,
+            feature_names,
+            target_names,
+            model,
+            objective: MultiClassObjective::default(),
+        }
+    }
+
+    pub fn with_objective(self, objective: MultiClassObjective) -> Self {
+        Self { objective, ..self }
+    }
+
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    // The version is checked before the rest, whose layout may differ in other versions
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, Box<dyn Error>> {
+        #[derive(Deserialize)]
+        struct SavedModelHeader {
+            format_version: u32,
+        }
+        let mut contents = vec![];
+        reader.read_to_end(&mut contents)?;
+        let header: SavedModelHeader = serde_json::from_slice(&contents)?;
+        if header.format_version != SAVED_MODEL_FORMAT_VERSION {
+            return Err(format!(
+                "saved model format version {} is not supported, expected {}",
+                header.format_version, SAVED_MODEL_FORMAT_VERSION
+            )
+            .into());
+        }
+        Ok(serde_json::from_slice(&contents)?)
+    }
+
+    pub fn save(&self, file_path: &str) -> Result<(), Box<dyn Error>> {
+        let file = File::create(file_path)
+            .map_err(|error| format!("Failed to create {}: {}", file_path, error))?;
+        let mut writer = BufWriter::new(file);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn load(file_path: &str) -> Result<Self, Box<dyn Error>> {
+        let file = File::open(file_path)
+            .map_err(|error| format!("Failed to open {}: {}", file_path, error))?;
+        Self::read(BufReader::new(file)).map_err(|error| format!("{}: {}", file_path, error).into())
+    }
+}
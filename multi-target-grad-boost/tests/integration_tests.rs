@@ -1,14 +1,53 @@
 use std::time::Instant;
 
-use common::data_reader::{read_csv_data_multi_target, read_csv_data_one_hot_multi_target};
+use common::{
+    data_reader::{
+        get_feature_names, read_csv_data_multi_target, read_csv_data_one_hot_multi_target,
+    },
+    data_writer::write_sparse_binary_matrix_as_libsvm,
+    metrics::classification::ClassificationReport,
+};
 use multi_target_decision_tree::{
-    decision_trees::TreeConfig,
+    decision_trees::{RegressionMultiTargetDecisionTree, TreeConfig},
+    dot_export::{write_dot, DotExportConfig},
+    leaf_encoder::LeafOneHotEncoder,
+    printer::TreeDump,
     split_finder::{SplitFinder, SplitMetric},
+    tree_shap::ShapValues,
 };
 use multi_target_grad_boost::boosting_ensemble::{
-    boosting_types::{AMGBoostModel, MultiClassBoostModel, RegressionBoostModel},
+    boosting_types::{
+        AMGBoostModel, BatchPredictionStrategy, ClassWeight, MultiClassBoostModel,
+        MultiClassObjective, RegressionBoostModel, ScoreDirection,
+    },
+    common_multi_class_boosting_functions::predict_helper_functions::get_binary_prediction,
+    learning_rate_schedule::LearningRateSchedule,
     GradientBoostedEnsemble,
 };
+use multi_target_grad_boost::checkpoint::{
+    find_latest_checkpoint, read_checkpoint_header, CheckpointConfig,
+};
+use multi_target_grad_boost::cross_validation::{
+    cross_validate_ensemble, cross_validate_tree, CrossValidationConfig, FoldStrategy,
+};
+use multi_target_grad_boost::distillation::{
+    distill_ensemble, evaluate_distillation, DistillationConfig,
+};
+use multi_target_grad_boost::experiment::{
+    run_experiment, write_results_csv, ExperimentConfig, Metric,
+};
+use multi_target_grad_boost::hyperparameter_search::{
+    hyperparameter_search, ParameterSpace, SearchConfig, SearchStrategy,
+};
+use multi_target_grad_boost::partial_dependence::{
+    calculate_partial_dependence_for_ensemble, PartialDependenceConfig,
+};
+use multi_target_grad_boost::permutation_importance::{
+    calculate_permutation_importance_for_ensemble, calculate_permutation_importance_for_tree,
+    PermutationImportanceConfig,
+};
+use multi_target_grad_boost::rule_fit::{RuleFitConfig, RuleFitModel};
+use multi_target_grad_boost::saved_model::{BoostingOptions, Model, ModelType, SavedModel};
 
 #[test]
 fn test_mtgbdt_single_threaded() {
@@ -199,3 +238,1328 @@ fn test_amgboost_multi_threaded_for_mcc_mnist() {
     let accuracy = grad_boost_ensemble.calculate_score(&test_set);
     println!("{:?}", accuracy)
 }
+
+#[test]
+fn test_predict_batch_matches_predict() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
+
+    let number_of_classes = true_data.labels[0].len() as u32;
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes,
+        max_levels: 3,
+    };
+
+    let regression_ensemble = RegressionBoostModel::train(true_data.clone(), tree_config, 20, 0.1);
+    let multi_class_ensemble = MultiClassBoostModel::train(true_data.clone(), tree_config, 20, 0.1);
+    let amg_ensemble = AMGBoostModel::train(true_data, tree_config, 20, 0.1);
+
+    let test_set = read_csv_data_one_hot_multi_target("./../common/data-files/iris_test.csv", 3);
+    let rows = &test_set.feature_rows;
+
+    let regression_predictions = regression_ensemble.predict_batch(rows);
+    let regression_split_tree_predictions = regression_ensemble.predict_batch_with_strategy(
+        rows,
+        BatchPredictionStrategy::ParallelRowsAndTrees { trees_per_chunk: 3 },
+    );
+    let regression_row_predictions: Vec<Vec<f64>> = rows
+        .iter()
+        .map(|row| regression_ensemble.predict(row))
+        .collect();
+    assert_eq!(regression_predictions, regression_row_predictions);
+    for (split_tree_prediction, prediction) in regression_split_tree_predictions
+        .iter()
+        .zip(&regression_row_predictions)
+    {
+        for (split_value, value) in split_tree_prediction.iter().zip(prediction) {
+            assert!((split_value - value).abs() < 1e-10);
+        }
+    }
+    assert_eq!(
+        multi_class_ensemble.predict_batch(rows),
+        rows.iter()
+            .map(|row| multi_class_ensemble.predict(row))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        amg_ensemble.predict_batch(rows),
+        rows.iter()
+            .map(|row| amg_ensemble.predict(row))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_staged_and_truncated_predictions() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
+
+    let number_of_classes = true_data.labels[0].len() as u32;
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes,
+        max_levels: 3,
+    };
+
+    let regression_ensemble = RegressionBoostModel::train(true_data.clone(), tree_config, 30, 0.1);
+    let amg_ensemble = AMGBoostModel::train(true_data, tree_config, 30, 0.1);
+
+    let test_set = read_csv_data_one_hot_multi_target("./../common/data-files/iris_test.csv", 3);
+    let rows = &test_set.feature_rows;
+
+    let staged_predictions: Vec<_> = regression_ensemble.staged_predict(rows).collect();
+    assert_eq!(staged_predictions.len(), 30);
+    assert_eq!(
+        staged_predictions[29],
+        regression_ensemble.predict_batch(rows)
+    );
+
+    let truncated_ensemble = regression_ensemble.truncate(10);
+    assert_eq!(truncated_ensemble.trees.len(), 10);
+    for (i, row) in rows.iter().enumerate() {
+        let prediction_with_ten_trees = regression_ensemble.predict_with_n_trees(row, 10);
+        assert_eq!(truncated_ensemble.predict(row), prediction_with_ten_trees);
+        for (staged_value, value) in staged_predictions[9][i]
+            .iter()
+            .zip(&prediction_with_ten_trees)
+        {
+            assert!((staged_value - value).abs() < 1e-10);
+        }
+    }
+
+    let amg_staged_predictions: Vec<_> = amg_ensemble.staged_predict(rows).step_by(10).collect();
+    assert_eq!(amg_staged_predictions.len(), 3);
+    assert_eq!(
+        amg_staged_predictions[1][0],
+        amg_ensemble.truncate(11).predict(&rows[0])
+    );
+}
+
+#[test]
+fn test_apply_leaf_ids_as_one_hot_features() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
+
+    let number_of_classes = true_data.labels[0].len() as u32;
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes,
+        max_levels: 3,
+    };
+
+    let grad_boost_ensemble = MultiClassBoostModel::train(true_data.clone(), tree_config, 10, 0.1);
+    let leaf_ids = grad_boost_ensemble.apply(&true_data.feature_rows);
+    assert_eq!(leaf_ids.len(), true_data.feature_rows.len());
+    assert!(leaf_ids.iter().all(|row_leaf_ids| row_leaf_ids.len() == 10));
+    for (tree, tree_leaf_id) in grad_boost_ensemble.trees.iter().zip(&leaf_ids[0]) {
+        assert!(tree.leaf_ids().contains(tree_leaf_id));
+    }
+
+    let encoder = LeafOneHotEncoder::new(&grad_boost_ensemble.trees);
+    let one_hot_leaves = encoder.transform(&leaf_ids);
+    assert!(one_hot_leaves.rows.iter().all(|row| row.len() == 10));
+
+    let mut libsvm_output = vec![];
+    write_sparse_binary_matrix_as_libsvm(&mut libsvm_output, &one_hot_leaves, &true_data.labels)
+        .unwrap();
+    let libsvm_string = String::from_utf8(libsvm_output).unwrap();
+    assert_eq!(libsvm_string.lines().count(), true_data.labels.len());
+}
+
+#[test]
+fn test_ensemble_feature_importance_for_wine() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/wine_train.csv", 3);
+    let number_of_features = true_data.feature_columns.len();
+
+    let number_of_classes = true_data.labels[0].len() as u32;
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes,
+        max_levels: 2,
+    };
+
+    let grad_boost_ensemble = AMGBoostModel::train(true_data, tree_config, 10, 0.1);
+    let feature_importance = grad_boost_ensemble.feature_importance(number_of_features);
+    let total_split_count: usize = feature_importance.split_count.iter().sum();
+    let number_of_splits: usize = grad_boost_ensemble
+        .trees
+        .iter()
+        .map(|tree| tree.leaf_ids().len() - 1)
+        .sum();
+    assert_eq!(total_split_count, number_of_splits);
+
+    let feature_names = get_feature_names("./../common/data-files/wine_train.csv");
+    let named_feature_importances = feature_importance.with_feature_names(&feature_names);
+    println!("{:?}", named_feature_importances[0]);
+    assert!(named_feature_importances[0].total_gain > 0.);
+}
+
+#[test]
+fn test_permutation_importance_on_held_out_wine() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/wine_train.csv", 3);
+    let test_set = read_csv_data_one_hot_multi_target("./../common/data-files/wine_test.csv", 3);
+    let number_of_features = test_set.feature_columns.len();
+
+    let number_of_classes = true_data.labels[0].len() as u32;
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes,
+        max_levels: 3,
+    };
+    let config = PermutationImportanceConfig {
+        number_of_repeats: 3,
+        seed: 1,
+    };
+
+    let multi_class_ensemble = MultiClassBoostModel::train(true_data.clone(), tree_config, 10, 0.1);
+    let ensemble_importance =
+        calculate_permutation_importance_for_ensemble(&multi_class_ensemble, &test_set, config);
+    assert_eq!(
+        ensemble_importance.baseline_score,
+        multi_class_ensemble.calculate_score(&test_set)
+    );
+    assert_eq!(
+        ensemble_importance.importances_mean.len(),
+        number_of_features
+    );
+    assert_eq!(
+        ensemble_importance.importances_mean_per_target[0].len(),
+        number_of_classes as usize
+    );
+    assert!(ensemble_importance
+        .importances_mean
+        .iter()
+        .any(|importance| *importance > 0.));
+
+    let tree = RegressionMultiTargetDecisionTree::new(true_data, tree_config);
+    let tree_importance = calculate_permutation_importance_for_tree(&tree, &test_set, config);
+    assert_eq!(tree_importance.importances_std.len(), number_of_features);
+    assert!(tree_importance
+        .importances_mean
+        .iter()
+        .any(|importance| *importance > 0.));
+}
+
+#[test]
+fn test_ensemble_shap_values_sum_to_predictions() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
+
+    let number_of_classes = true_data.labels[0].len() as u32;
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes,
+        max_levels: 3,
+    };
+
+    let regression_ensemble = RegressionBoostModel::train(true_data.clone(), tree_config, 10, 0.1);
+    let amg_ensemble = AMGBoostModel::train(true_data, tree_config, 10, 0.1);
+
+    let test_set = read_csv_data_one_hot_multi_target("./../common/data-files/iris_test.csv", 3);
+    let sum_shap_values = |shap_values: &ShapValues| {
+        let mut sum = shap_values.expected_value.clone();
+        for feature_values in &shap_values.values {
+            for (total, value) in sum.iter_mut().zip(feature_values) {
+                *total += value;
+            }
+        }
+        sum
+    };
+    for row in &test_set.feature_rows {
+        let prediction = regression_ensemble.predict(row);
+        let shap_sum = sum_shap_values(&regression_ensemble.shap_values(row));
+        for (shap_value, value) in shap_sum.iter().zip(&prediction) {
+            assert!((shap_value - value).abs() < 1e-10);
+        }
+
+        let amg_shap_values = amg_ensemble.shap_values(row);
+        assert_eq!(
+            get_binary_prediction(&sum_shap_values(&amg_shap_values)),
+            amg_ensemble.predict(row)
+        );
+        let amg_interaction_values = amg_ensemble.shap_interaction_values(row);
+        for (feature, interactions) in amg_interaction_values.values.iter().enumerate() {
+            for class in 0..number_of_classes as usize {
+                let interaction_sum: f64 = interactions.iter().map(|values| values[class]).sum();
+                assert!((interaction_sum - amg_shap_values.values[feature][class]).abs() < 1e-10);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_partial_dependence_for_regression_ensemble() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
+
+    let number_of_classes = true_data.labels[0].len() as u32;
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes,
+        max_levels: 3,
+    };
+
+    let regression_ensemble = RegressionBoostModel::train(true_data.clone(), tree_config, 10, 0.1);
+    let config = PartialDependenceConfig::default();
+    let partial_dependence = calculate_partial_dependence_for_ensemble(
+        &regression_ensemble,
+        &true_data,
+        &true_data.feature_rows,
+        &[2, 3],
+        config,
+    );
+    assert!(partial_dependence.grid_points.len() <= config.grid_resolution.pow(2));
+    assert_eq!(
+        partial_dependence.individual.len(),
+        true_data.feature_rows.len()
+    );
+    for (grid_index, average) in partial_dependence.average.iter().enumerate() {
+        assert_eq!(average.len(), number_of_classes as usize);
+        let mut row = true_data.feature_rows[0].clone();
+        row[2] = partial_dependence.grid_points[grid_index][0];
+        row[3] = partial_dependence.grid_points[grid_index][1];
+        assert_eq!(
+            partial_dependence.individual[0][grid_index],
+            regression_ensemble.predict(&row)
+        );
+    }
+}
+
+#[test]
+fn test_ensemble_decision_path_contributions_sum_to_prediction() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
+
+    let number_of_classes = true_data.labels[0].len() as u32;
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes,
+        max_levels: 3,
+    };
+
+    let regression_ensemble = RegressionBoostModel::train(true_data.clone(), tree_config, 5, 0.1);
+    let row = &true_data.feature_rows[60];
+    let decision_path = regression_ensemble.decision_path(row);
+    assert_eq!(decision_path.trees.len(), 5);
+
+    let mut sum = decision_path.initial_guess.clone();
+    for (tree_path, leaf_id) in decision_path
+        .trees
+        .iter()
+        .zip(&regression_ensemble.apply(std::slice::from_ref(row))[0])
+    {
+        assert_eq!(tree_path.path.leaf_id, *leaf_id);
+        for (total, contribution) in sum.iter_mut().zip(&tree_path.contribution) {
+            *total += contribution;
+        }
+    }
+    for (total, value) in sum.iter().zip(regression_ensemble.predict(row)) {
+        assert!((total - value).abs() < 1e-10);
+    }
+
+    let feature_names = get_feature_names("./../common/data-files/iris.csv");
+    let explanation = decision_path.to_string(&feature_names);
+    println!("{}", explanation);
+    assert!(explanation.starts_with("Initial guess"));
+}
+
+#[test]
+fn test_write_dot_for_individual_ensemble_trees() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
+
+    let number_of_classes = true_data.labels[0].len() as u32;
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes,
+        max_levels: 3,
+    };
+
+    let multi_class_ensemble = MultiClassBoostModel::train(true_data.clone(), tree_config, 3, 0.1);
+    let amg_ensemble = AMGBoostModel::train(true_data, tree_config, 3, 0.1);
+    let feature_names = get_feature_names("./../common/data-files/iris.csv");
+    let config = DotExportConfig {
+        feature_names: &feature_names,
+        target_names: &[],
+        max_depth: Some(2),
+    };
+
+    let mut multi_class_output = vec![];
+    write_dot(
+        &mut multi_class_output,
+        &multi_class_ensemble.trees[1],
+        &config,
+    )
+    .unwrap();
+    let multi_class_dot = String::from_utf8(multi_class_output).unwrap();
+    assert!(multi_class_dot.contains("output ["));
+
+    let mut amg_output = vec![];
+    let untruncated_config = DotExportConfig {
+        max_depth: None,
+        ..config
+    };
+    write_dot(&mut amg_output, &amg_ensemble.trees[2], &untruncated_config).unwrap();
+    let amg_dot = String::from_utf8(amg_output).unwrap();
+    assert!(amg_dot.contains("max value"));
+}
+
+#[test]
+fn test_tree_dump_for_regression_ensemble() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
+
+    let number_of_classes = true_data.labels[0].len() as u32;
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes,
+        max_levels: 2,
+    };
+
+    let regression_ensemble = RegressionBoostModel::train(true_data, tree_config, 4, 0.1);
+    let feature_names = get_feature_names("./../common/data-files/iris.csv");
+    let tree_dump = TreeDump {
+        feature_names: &feature_names,
+        target_names: &[],
+    };
+
+    let mut text_output = vec![];
+    tree_dump
+        .write_text_for_trees(&mut text_output, &regression_ensemble.trees)
+        .unwrap();
+    let text = String::from_utf8(text_output).unwrap();
+    assert_eq!(
+        text.lines()
+            .filter(|line| line.starts_with("Tree "))
+            .count(),
+        4
+    );
+
+    let mut json_output = vec![];
+    tree_dump
+        .write_json_for_trees(&mut json_output, &regression_ensemble.trees)
+        .unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&json_output).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), 4);
+}
+
+#[test]
+fn test_rule_fit_on_ensemble_rules() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
+
+    let number_of_classes = true_data.labels[0].len() as u32;
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes,
+        max_levels: 2,
+    };
+
+    let regression_ensemble = RegressionBoostModel::train(true_data.clone(), tree_config, 10, 0.1);
+    let rules = regression_ensemble.extract_rules();
+    let number_of_leaves: usize = regression_ensemble
+        .trees
+        .iter()
+        .map(|tree| tree.leaf_ids().len())
+        .sum();
+    assert_eq!(rules.len(), number_of_leaves);
+    assert!(rules.iter().all(|rule| rule.tree_index < 10));
+
+    let rule_fit = RuleFitModel::fit(rules, &true_data, RuleFitConfig::default());
+    assert!(!rule_fit.rules.is_empty());
+    let predictions = rule_fit.predict_batch(&true_data.feature_rows);
+    let mean_squared_error: f64 = predictions
+        .iter()
+        .zip(&true_data.labels)
+        .map(|(prediction, label)| {
+            prediction
+                .iter()
+                .zip(label)
+                .map(|(p, l)| (p - l) * (p - l))
+                .sum::<f64>()
+        })
+        .sum::<f64>()
+        / true_data.labels.len() as f64;
+    // predicting the class frequencies everywhere gives 2/3
+    assert!(mean_squared_error < 0.3);
+
+    let feature_names = get_feature_names("./../common/data-files/iris.csv");
+    let mut csv_output = vec![];
+    rule_fit
+        .write_csv(&mut csv_output, &feature_names, &[])
+        .unwrap();
+    let csv_string = String::from_utf8(csv_output).unwrap();
+    assert_eq!(csv_string.lines().count(), rule_fit.rules.len() + 2);
+}
+
+#[test]
+fn test_distill_multi_class_ensemble_into_single_tree() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/wine_train.csv", 3);
+    let test_set = read_csv_data_one_hot_multi_target("./../common/data-files/wine_test.csv", 3);
+
+    let number_of_classes = true_data.labels[0].len() as u32;
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes,
+        max_levels: 3,
+    };
+
+    let teacher = MultiClassBoostModel::train(true_data.clone(), tree_config, 20, 0.1);
+    let config = DistillationConfig {
+        tree_config: TreeConfig {
+            max_levels: 4,
+            ..tree_config
+        },
+        number_of_synthetic_samples: 200,
+        seed: 5,
+    };
+    let student = distill_ensemble(&teacher, &true_data, config);
+    let report = evaluate_distillation(&teacher, &student, &test_set);
+    println!("{:?}", report);
+    assert_eq!(report.teacher_score, teacher.calculate_score(&test_set));
+    assert!(report.fidelity_agreement > 0.7);
+    assert!(report.student_score > 0.6);
+}
+
+#[test]
+fn test_regression_report_for_ensemble_and_tree() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
+
+    let number_of_classes = true_data.labels[0].len() as u32;
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes,
+        max_levels: 3,
+    };
+
+    let regression_ensemble = RegressionBoostModel::train(true_data.clone(), tree_config, 20, 0.1);
+    let ensemble_report = regression_ensemble.regression_report(&true_data);
+    println!("{}", ensemble_report.to_string(&[]));
+    assert!(
+        (ensemble_report.overall_mean_squared_error
+            - regression_ensemble.calculate_score(&true_data))
+        .abs()
+            < 1e-10
+    );
+    assert_eq!(ensemble_report.r_squared.len(), number_of_classes as usize);
+    assert!(ensemble_report.average_relative_root_mean_squared_error < 1.);
+    assert!(ensemble_report.average_correlation_coefficient > 0.8);
+
+    let tree = RegressionMultiTargetDecisionTree::new(true_data.clone(), tree_config);
+    let tree_report = tree.regression_report(&true_data);
+    for target in 0..number_of_classes as usize {
+        assert!(tree_report.r_squared[target] > 0.);
+        assert!(
+            tree_report.mean_absolute_error[target] <= tree_report.root_mean_squared_error[target]
+        );
+    }
+}
+
+#[test]
+fn test_classification_report_for_multi_class_ensemble() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/wine_train.csv", 3);
+    let test_set = read_csv_data_one_hot_multi_target("./../common/data-files/wine_test.csv", 3);
+
+    let number_of_classes = true_data.labels[0].len() as u32;
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes,
+        max_levels: 3,
+    };
+
+    let multi_class_ensemble = MultiClassBoostModel::train(true_data, tree_config, 20, 0.1);
+    let report = multi_class_ensemble.classification_report(&test_set);
+    println!("{}", report.to_string(&[]));
+    assert_eq!(
+        report.accuracy,
+        multi_class_ensemble.calculate_score(&test_set)
+    );
+    assert_eq!(
+        report.confusion_matrix.counts.len(),
+        number_of_classes as usize
+    );
+    assert!(report.balanced_accuracy > 0.7);
+}
+
+#[test]
+fn test_stratified_cross_validation_for_iris() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
+
+    let number_of_classes = true_data.labels[0].len() as u32;
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes,
+        max_levels: 3,
+    };
+    let config = CrossValidationConfig {
+        number_of_folds: 5,
+        strategy: FoldStrategy::Stratified,
+        seed: 11,
+    };
+
+    let result =
+        cross_validate_ensemble::<MultiClassBoostModel>(&true_data, tree_config, 10, 0.1, &config)
+            .unwrap();
+    println!("{} +- {}", result.mean_score, result.std_score);
+    assert_eq!(result.folds.len(), 5);
+    assert_eq!(result.score_direction, ScoreDirection::HigherIsBetter);
+    assert!(result.mean_score > 0.85);
+    let out_of_fold_report =
+        ClassificationReport::new(&result.out_of_fold_predictions, &true_data.labels);
+    assert!((out_of_fold_report.accuracy - result.mean_score).abs() < 1e-10);
+
+    let tree_result = cross_validate_tree(&true_data, tree_config, &config).unwrap();
+    assert_eq!(
+        tree_result.out_of_fold_predictions.len(),
+        true_data.labels.len()
+    );
+    assert!(
+        tree_result
+            .out_of_fold_regression_report
+            .average_correlation_coefficient
+            > 0.8
+    );
+}
+
+#[test]
+fn test_successive_halving_search_for_wine() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/wine_train.csv", 3);
+
+    let number_of_classes = true_data.labels[0].len() as u32;
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes,
+        max_levels: 3,
+    };
+    let space = ParameterSpace {
+        max_levels: vec![2, 3],
+        number_of_iterations: vec![20],
+        learning_rates: vec![0.05, 0.1, 0.3],
+    };
+    let config = SearchConfig {
+        strategy: SearchStrategy::SuccessiveHalving {
+            number_of_candidates: 4,
+            seed: 3,
+            minimum_iterations: 5,
+            reduction_factor: 2,
+        },
+        cross_validation: CrossValidationConfig {
+            number_of_folds: 3,
+            strategy: FoldStrategy::Stratified,
+            seed: 3,
+        },
+        max_parallel_evaluations: 2,
+    };
+
+    let search =
+        hyperparameter_search::<MultiClassBoostModel>(&true_data, tree_config, &space, &config)
+            .unwrap();
+    let mut markdown_output = vec![];
+    search.write_results_markdown(&mut markdown_output).unwrap();
+    println!("{}", String::from_utf8(markdown_output).unwrap());
+    assert_eq!(search.results.len(), 4);
+    // rounds of 5, 10 and 20 iterations
+    assert_eq!(search.best_candidate.number_of_iterations, 20);
+    assert_eq!(search.results[0].candidate, search.best_candidate);
+    assert_eq!(search.best_model.trees.len(), 20);
+}
+
+#[test]
+fn test_saved_model_round_trip() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
+    let test_set = read_csv_data_one_hot_multi_target("./../common/data-files/iris_test.csv", 3);
+
+    let number_of_classes = true_data.labels[0].len() as u32;
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes,
+        max_levels: 3,
+    };
+
+    let amg_ensemble = AMGBoostModel::train(true_data.clone(), tree_config, 10, 0.1);
+    let expected_predictions = amg_ensemble.predict_batch(&test_set.feature_rows);
+    let feature_names = get_feature_names("./../common/data-files/iris.csv")[..4].to_vec();
+    let saved_model = SavedModel::new(Model::AMGBoost(amg_ensemble), feature_names, vec![]);
+    let mut output = vec![];
+    saved_model.write(&mut output).unwrap();
+
+    let loaded_model = SavedModel::read(&output[..]).unwrap();
+    assert_eq!(loaded_model.feature_names, saved_model.feature_names);
+    assert_eq!(loaded_model.model.type_name(), "amg-boost");
+    assert_eq!(
+        loaded_model.model.predict_batch(&test_set.feature_rows),
+        expected_predictions
+    );
+
+    let tree = RegressionMultiTargetDecisionTree::new(true_data.clone(), tree_config);
+    let expected_tree_predictions = tree.predict_batch(&test_set.feature_rows);
+    let mut tree_output = vec![];
+    SavedModel::new(Model::Tree(tree), vec![], vec![])
+        .write(&mut tree_output)
+        .unwrap();
+    let loaded_tree = SavedModel::read(&tree_output[..]).unwrap();
+    assert_eq!(
+        loaded_tree.model.predict_batch(&test_set.feature_rows),
+        expected_tree_predictions
+    );
+
+    let mut wrong_version: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    wrong_version["format_version"] = serde_json::json!(0);
+    assert!(SavedModel::read(wrong_version.to_string().as_bytes()).is_err());
+    // a file of another version is rejected for its version, not its layout
+    wrong_version["model"] = serde_json::json!({ "UnknownModel": [] });
+    let error = SavedModel::read(wrong_version.to_string().as_bytes())
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("format version 0"));
+
+    // continued training of a loaded multiclass model keeps the objective it was saved with
+    let objective = MultiClassObjective {
+        class_weight: ClassWeight::Balanced,
+        focal_gamma: Some(2.),
+    };
+    let multi_class_ensemble = MultiClassBoostModel::train_with_objective(
+        true_data.clone(),
+        tree_config,
+        5,
+        &LearningRateSchedule::Constant(0.1),
+        objective.clone(),
+    );
+    let mut expected_ensemble = multi_class_ensemble.truncate(5);
+    expected_ensemble.continue_training_with_objective(
+        true_data.clone(),
+        tree_config,
+        3,
+        None,
+        objective.clone(),
+    );
+    let mut objective_output = vec![];
+    SavedModel::new(Model::MultiClassBoost(multi_class_ensemble), vec![], vec![])
+        .with_objective(objective.clone())
+        .write(&mut objective_output)
+        .unwrap();
+    let mut loaded_model = SavedModel::read(&objective_output[..]).unwrap();
+    assert_eq!(loaded_model.objective, objective);
+    loaded_model
+        .model
+        .continue_training(
+            true_data,
+            tree_config,
+            3,
+            None,
+            loaded_model.objective.clone(),
+        )
+        .unwrap();
+    assert_eq!(
+        loaded_model.model.predict_batch(&test_set.feature_rows),
+        expected_ensemble.predict_batch(&test_set.feature_rows)
+    );
+}
+
+#[test]
+fn test_experiment_runs_every_combination() {
+    let config = ExperimentConfig::from_toml(
+        r#"
+        seeds = [1, 2]
+        metrics = ["accuracy", "mean_squared_error"]
+
+        [[datasets]]
+        name = "iris"
+        train = "./../common/data-files/iris.csv"
+        test_fraction = 0.3
+        labels = { type = "class_column", number_of_classes = 3 }
+
+        [[models]]
+        model_type = "tree"
+        max_levels = [2]
+
+        [[models]]
+        model_type = "multi-class-boost"
+        max_levels = [2]
+        number_of_iterations = [5]
+        learning_rates = [0.1, 0.5]
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        config.metrics,
+        vec![Metric::Accuracy, Metric::MeanSquaredError]
+    );
+
+    let results = run_experiment(&config).unwrap();
+    assert_eq!(results.len(), 6);
+    assert_eq!(results[0].number_of_iterations, None);
+    assert_eq!(results[2].learning_rate, Some(0.5));
+    assert_eq!(results[3].seed, 2);
+    for result in &results {
+        assert!(result.metric_values[0] > 0.5);
+        assert!(result.training_seconds >= 0.);
+        assert!(result.model_bytes > 0);
+    }
+
+    let mut output = vec![];
+    write_results_csv(&mut output, &config.metrics, &results).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(output.lines().count(), 7);
+    assert!(output
+        .lines()
+        .next()
+        .unwrap()
+        .ends_with("accuracy,mean_squared_error"));
+
+    let mut missing_file = config.clone();
+    missing_file.datasets[0].train = "missing.csv".to_string();
+    assert!(run_experiment(&missing_file).is_err());
+}
+
+#[test]
+fn test_integer_weights_match_repeated_rows() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/iris.csv", 3);
+    let test_set = read_csv_data_one_hot_multi_target("./../common/data-files/iris_test.csv", 3);
+
+    let weights: Vec<f64> = (0..true_data.number_of_rows())
+        .map(|row| (row % 3 + 1) as f64)
+        .collect();
+    let mut repeated_rows = vec![];
+    for (row, weight) in weights.iter().enumerate() {
+        (0..*weight as usize).for_each(|_| repeated_rows.push(row));
+    }
+    let repeated_data = true_data.subset(&repeated_rows);
+    let weighted_data = true_data.with_weights(weights);
+
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes: 3,
+        max_levels: 3,
+    };
+
+    let weighted_ensemble = RegressionBoostModel::train(weighted_data, tree_config, 10, 0.1);
+    let repeated_ensemble = RegressionBoostModel::train(repeated_data, tree_config, 10, 0.1);
+    let weighted_predictions = weighted_ensemble.predict_batch(&test_set.feature_rows);
+    let repeated_predictions = repeated_ensemble.predict_batch(&test_set.feature_rows);
+    for (weighted, repeated) in weighted_predictions.iter().zip(&repeated_predictions) {
+        for (weighted_value, repeated_value) in weighted.iter().zip(repeated) {
+            assert!((weighted_value - repeated_value).abs() < 1e-9);
+        }
+    }
+
+    // zero weights leave the rows out of the score
+    let test_weights: Vec<f64> = (0..test_set.number_of_rows())
+        .map(|row| (row % 2) as f64)
+        .collect();
+    let odd_rows: Vec<usize> = (1..test_set.number_of_rows()).step_by(2).collect();
+    let weighted_test_set = test_set.clone().with_weights(test_weights);
+    assert!(
+        (weighted_ensemble.calculate_score(&weighted_test_set)
+            - weighted_ensemble.calculate_score(&test_set.subset(&odd_rows)))
+        .abs()
+            < 1e-12
+    );
+}
+
+#[test]
+fn test_class_weights_and_focal_loss_for_imbalanced_wine() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/wine_train.csv", 3);
+    let test_set = read_csv_data_one_hot_multi_target("./../common/data-files/wine_test.csv", 3);
+    // keep every fourth row of the last class
+    let mut minority_rows = 0;
+    let kept_rows: Vec<usize> = (0..true_data.number_of_rows())
+        .filter(|row| {
+            if true_data.labels[*row][2] != 1. {
+                return true;
+            }
+            minority_rows += 1;
+            minority_rows % 4 == 0
+        })
+        .collect();
+    let imbalanced_data = true_data.subset(&kept_rows);
+
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes: 3,
+        max_levels: 3,
+    };
+    let train = |objective: MultiClassObjective| {
+        MultiClassBoostModel::train_with_objective(
+            imbalanced_data.clone(),
+            tree_config,
+            20,
+            &LearningRateSchedule::Constant(0.1),
+            objective,
+        )
+    };
+
+    let uniform_ensemble = train(MultiClassObjective::default());
+    let uniform_predictions = uniform_ensemble.predict_batch(&test_set.feature_rows);
+    // scaling every class by the same weight changes nothing
+    let doubled_ensemble = train(MultiClassObjective {
+        class_weight: ClassWeight::Explicit(vec![2., 2., 2.]),
+        focal_gamma: None,
+    });
+    assert_eq!(
+        doubled_ensemble.predict_batch(&test_set.feature_rows),
+        uniform_predictions
+    );
+    // gamma 0 is the softmax loss
+    let zero_gamma_ensemble = train(MultiClassObjective {
+        class_weight: ClassWeight::Uniform,
+        focal_gamma: Some(0.),
+    });
+    assert_eq!(
+        zero_gamma_ensemble.predict_batch(&test_set.feature_rows),
+        uniform_predictions
+    );
+
+    let uniform_report = uniform_ensemble.classification_report(&test_set);
+    let balanced_report = train(MultiClassObjective {
+        class_weight: ClassWeight::Balanced,
+        focal_gamma: None,
+    })
+    .classification_report(&test_set);
+    let focal_report = train(MultiClassObjective {
+        class_weight: ClassWeight::Balanced,
+        focal_gamma: Some(2.),
+    })
+    .classification_report(&test_set);
+    println!(
+        "minority recall: uniform {:.4}, balanced {:.4}, balanced focal {:.4}",
+        uniform_report.recall[2], balanced_report.recall[2], focal_report.recall[2]
+    );
+    assert!(balanced_report.recall[2] >= uniform_report.recall[2]);
+    assert!(focal_report.balanced_accuracy > 0.7);
+
+    let amg_ensemble = AMGBoostModel::train_with_objective(
+        imbalanced_data.clone(),
+        tree_config,
+        20,
+        &LearningRateSchedule::Constant(0.1),
+        MultiClassObjective {
+            class_weight: ClassWeight::Balanced,
+            focal_gamma: Some(1.),
+        },
+    );
+    assert!(
+        amg_ensemble
+            .classification_report(&test_set)
+            .balanced_accuracy
+            > 0.7
+    );
+}
+
+fn assert_predictions_close(actual: &[Vec<f64>], expected: &[Vec<f64>]) {
+    for (actual_row, expected_row) in actual.iter().zip(expected) {
+        for (actual_value, expected_value) in actual_row.iter().zip(expected_row) {
+            assert!((actual_value - expected_value).abs() < 1e-9);
+        }
+    }
+}
+
+#[test]
+fn test_continue_training_matches_longer_training() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/wine_train.csv", 3);
+    let test_set = read_csv_data_one_hot_multi_target("./../common/data-files/wine_test.csv", 3);
+
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes: 3,
+        max_levels: 3,
+    };
+
+    let mut regression_ensemble =
+        RegressionBoostModel::train(true_data.clone(), tree_config, 10, 0.1);
+    regression_ensemble.continue_training(true_data.clone(), tree_config, 10, None);
+    let longer_regression_ensemble =
+        RegressionBoostModel::train(true_data.clone(), tree_config, 20, 0.1);
+    assert_eq!(regression_ensemble.trees.len(), 20);
+    assert_eq!(
+        regression_ensemble.predict_batch(&test_set.feature_rows),
+        longer_regression_ensemble.predict_batch(&test_set.feature_rows)
+    );
+
+    let mut multi_class_ensemble =
+        MultiClassBoostModel::train(true_data.clone(), tree_config, 5, 0.1);
+    multi_class_ensemble.continue_training(true_data.clone(), tree_config, 5, None);
+    assert_eq!(
+        multi_class_ensemble.predict_batch(&test_set.feature_rows),
+        MultiClassBoostModel::train(true_data.clone(), tree_config, 10, 0.1)
+            .predict_batch(&test_set.feature_rows)
+    );
+
+    let mut amg_ensemble = AMGBoostModel::train(true_data.clone(), tree_config, 5, 0.1);
+    amg_ensemble.continue_training(true_data.clone(), tree_config, 5, None);
+    assert_eq!(
+        amg_ensemble.predict_batch(&test_set.feature_rows),
+        AMGBoostModel::train(true_data.clone(), tree_config, 10, 0.1)
+            .predict_batch(&test_set.feature_rows)
+    );
+
+    // the new trees of an ensemble without trees are those of one trained with their learning rate
+    let mut initial_guess_only =
+        RegressionBoostModel::train(true_data.clone(), tree_config, 0, 0.1);
+    initial_guess_only.continue_training(true_data.clone(), tree_config, 5, Some(0.3));
+    assert_eq!(initial_guess_only.learning_rates, vec![0.3; 5]);
+    assert_predictions_close(
+        &initial_guess_only.predict_batch(&test_set.feature_rows),
+        &RegressionBoostModel::train(true_data.clone(), tree_config, 5, 0.3)
+            .predict_batch(&test_set.feature_rows),
+    );
+
+    let mut saved_tree = Model::train(ModelType::Tree, true_data.clone(), tree_config, 0, 0.1);
+    assert!(saved_tree
+        .continue_training(
+            true_data,
+            tree_config,
+            5,
+            None,
+            MultiClassObjective::default()
+        )
+        .is_err());
+}
+
+#[test]
+fn test_resume_from_checkpoint_matches_uninterrupted_run() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/wine_train.csv", 3);
+    let test_set = read_csv_data_one_hot_multi_target("./../common/data-files/wine_test.csv", 3);
+    let directory = std::env::temp_dir().join(format!("checkpoints_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    let checkpoint_config = CheckpointConfig {
+        directory: directory.to_str().unwrap().to_string(),
+        every_n_iterations: 3,
+    };
+    let checkpoint_path = |completed_iterations: u32| {
+        directory
+            .join(format!("checkpoint_{:06}.json", completed_iterations))
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes: 3,
+        max_levels: 3,
+    };
+
+    let uninterrupted_ensemble =
+        RegressionBoostModel::train(true_data.clone(), tree_config, 10, 0.1);
+    let expected_predictions = uninterrupted_ensemble.predict_batch(&test_set.feature_rows);
+    let checkpointed_ensemble = RegressionBoostModel::train_with_checkpoints(
+        true_data.clone(),
+        tree_config,
+        10,
+        &LearningRateSchedule::Constant(0.1),
+        None,
+        &checkpoint_config,
+    )
+    .unwrap();
+    assert_eq!(
+        checkpointed_ensemble.predict_batch(&test_set.feature_rows),
+        expected_predictions
+    );
+    for completed_iterations in &[3, 6, 9, 10] {
+        assert!(std::path::Path::new(&checkpoint_path(*completed_iterations)).exists());
+    }
+    assert_eq!(
+        find_latest_checkpoint(&checkpoint_config.directory)
+            .unwrap()
+            .unwrap()
+            .to_str()
+            .unwrap(),
+        checkpoint_path(10)
+    );
+
+    let resumed_ensemble = RegressionBoostModel::resume_from(
+        &checkpoint_path(6),
+        true_data.clone(),
+        tree_config,
+        None,
+    )
+    .unwrap();
+    assert_eq!(resumed_ensemble.trees.len(), 10);
+    assert_eq!(
+        resumed_ensemble.predict_batch(&test_set.feature_rows),
+        expected_predictions
+    );
+    assert!(Model::resume_from(
+        ModelType::AmgBoost,
+        &checkpoint_path(6),
+        true_data.clone(),
+        tree_config,
+        None
+    )
+    .is_err());
+    // the checkpoint was written with max_levels 3
+    let deeper_tree_config = TreeConfig {
+        max_levels: 4,
+        ..tree_config
+    };
+    assert!(RegressionBoostModel::resume_from(
+        &checkpoint_path(6),
+        true_data.clone(),
+        deeper_tree_config,
+        None
+    )
+    .is_err());
+    let invalid_schedule = LearningRateSchedule::StepDecay {
+        initial_rate: 0.1,
+        factor: 0.5,
+        step_size: 0,
+    };
+    assert!(RegressionBoostModel::train_with_checkpoints(
+        true_data.clone(),
+        tree_config,
+        10,
+        &invalid_schedule,
+        None,
+        &checkpoint_config,
+    )
+    .is_err());
+
+    // the class weights and focal gamma are restored from the checkpoint
+    let objective = MultiClassObjective {
+        class_weight: ClassWeight::Balanced,
+        focal_gamma: Some(2.),
+    };
+    let uninterrupted_ensemble = MultiClassBoostModel::train_with_objective(
+        true_data.clone(),
+        tree_config,
+        8,
+        &LearningRateSchedule::Constant(0.1),
+        objective.clone(),
+    );
+    MultiClassBoostModel::train_with_checkpoints(
+        true_data.clone(),
+        tree_config,
+        8,
+        &LearningRateSchedule::Constant(0.1),
+        objective.clone(),
+        &checkpoint_config,
+    )
+    .unwrap();
+    assert_eq!(
+        read_checkpoint_header(&checkpoint_path(3))
+            .unwrap()
+            .objective,
+        Some(objective)
+    );
+    let resumed_ensemble = MultiClassBoostModel::resume_from(
+        &checkpoint_path(3),
+        true_data.clone(),
+        tree_config,
+        None,
+    )
+    .unwrap();
+    assert_eq!(
+        resumed_ensemble.predict_batch(&test_set.feature_rows),
+        uninterrupted_ensemble.predict_batch(&test_set.feature_rows)
+    );
+
+    let uninterrupted_ensemble = AMGBoostModel::train(true_data.clone(), tree_config, 8, 0.1);
+    AMGBoostModel::train_with_checkpoints(
+        true_data.clone(),
+        tree_config,
+        8,
+        &LearningRateSchedule::Constant(0.1),
+        MultiClassObjective::default(),
+        &checkpoint_config,
+    )
+    .unwrap();
+    let resumed_ensemble =
+        AMGBoostModel::resume_from(&checkpoint_path(6), true_data, tree_config, None).unwrap();
+    assert_eq!(
+        resumed_ensemble.predict_batch(&test_set.feature_rows),
+        uninterrupted_ensemble.predict_batch(&test_set.feature_rows)
+    );
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_learning_rate_schedules_and_target_shrinkage() {
+    let true_data = read_csv_data_one_hot_multi_target("./../common/data-files/wine_train.csv", 3);
+    let test_set = read_csv_data_one_hot_multi_target("./../common/data-files/wine_test.csv", 3);
+
+    let split_finder = SplitFinder::new(SplitMetric::Variance);
+    let tree_config = TreeConfig {
+        split_finder,
+        use_multi_threading: false,
+        number_of_classes: 3,
+        max_levels: 3,
+    };
+
+    let constant_ensemble = RegressionBoostModel::train_with_schedule(
+        true_data.clone(),
+        tree_config,
+        8,
+        &LearningRateSchedule::Constant(0.1),
+    );
+    assert_eq!(
+        constant_ensemble.predict_batch(&test_set.feature_rows),
+        RegressionBoostModel::train(true_data.clone(), tree_config, 8, 0.1)
+            .predict_batch(&test_set.feature_rows)
+    );
+
+    // every tree keeps the rate of its iteration, and all the ways of predicting use it
+    let schedule = LearningRateSchedule::Warmup {
+        warmup_iterations: 3,
+        schedule: Box::new(LearningRateSchedule::Cosine {
+            initial_rate: 0.3,
+            minimum_rate: 0.05,
+        }),
+    };
+    let target_shrinkage = vec![1., 0.5, 0.];
+    let ensemble = RegressionBoostModel::train_with_target_shrinkage(
+        true_data.clone(),
+        tree_config,
+        12,
+        &schedule,
+        Some(target_shrinkage),
+    );
+    assert_eq!(ensemble.learning_rates, schedule.learning_rates(0, 12, 12));
+    let predictions = ensemble.predict_batch(&test_set.feature_rows);
+    assert_predictions_close(
+        &ensemble.predict_batch_with_strategy(
+            &test_set.feature_rows,
+            BatchPredictionStrategy::ParallelRowsAndTrees { trees_per_chunk: 5 },
+        ),
+        &predictions,
+    );
+    assert_predictions_close(
+        ensemble
+            .staged_predict(&test_set.feature_rows)
+            .last()
+            .as_ref()
+            .unwrap(),
+        &predictions,
+    );
+    assert_predictions_close(
+        &ensemble.truncate(7).predict_batch(&test_set.feature_rows),
+        &test_set
+            .feature_rows
+            .iter()
+            .map(|row| ensemble.predict_with_n_trees(row, 7))
+            .collect::<Vec<_>>(),
+    );
+    for (row, prediction) in test_set.feature_rows.iter().zip(&predictions) {
+        // a target with a shrinkage of 0 keeps the initial guess
+        assert_eq!(prediction[2], ensemble.initial_guess[2]);
+        let decision_path = ensemble.decision_path(row);
+        let mut sum = decision_path.initial_guess.clone();
+        let shap_values = ensemble.shap_values(row);
+        let mut shap_sum = shap_values.expected_value.clone();
+        for tree_path in &decision_path.trees {
+            sum.iter_mut()
+                .zip(&tree_path.contribution)
+                .for_each(|(total, contribution)| *total += contribution);
+        }
+        for feature_values in &shap_values.values {
+            shap_sum
+                .iter_mut()
+                .zip(feature_values)
+                .for_each(|(total, value)| *total += value);
+        }
+        assert_predictions_close(&[sum, shap_sum], &[prediction.clone(), prediction.clone()]);
+    }
+
+    // the schedule and the shrinkage are part of checkpoints and saved models
+    let directory =
+        std::env::temp_dir().join(format!("schedule_checkpoints_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+    let checkpoint_config = CheckpointConfig {
+        directory: directory.to_str().unwrap().to_string(),
+        every_n_iterations: 5,
+    };
+    Model::train_with_checkpoints(
+        ModelType::RegressionBoost,
+        true_data.clone(),
+        tree_config,
+        BoostingOptions {
+            number_of_iterations: 12,
+            learning_rate_schedule: schedule.clone(),
+            objective: MultiClassObjective::default(),
+            target_shrinkage: Some(vec![1., 0.5, 0.]),
+        },
+        &checkpoint_config,
+    )
+    .unwrap();
+    let resumed_model = Model::resume_from(
+        ModelType::RegressionBoost,
+        directory.join("checkpoint_000005.json").to_str().unwrap(),
+        true_data.clone(),
+        tree_config,
+        None,
+    )
+    .unwrap();
+    std::fs::remove_dir_all(&directory).unwrap();
+    let mut output = vec![];
+    SavedModel::new(resumed_model, vec![], vec![])
+        .write(&mut output)
+        .unwrap();
+    let loaded_model = SavedModel::read(&output[..]).unwrap();
+    assert_eq!(
+        loaded_model.model.predict_batch(&test_set.feature_rows),
+        predictions
+    );
+
+    // continued trees carry on with the last rate and keep the target shrinkage
+    let mut continued_ensemble = ensemble.truncate(10);
+    continued_ensemble.continue_training(true_data, tree_config, 2, None);
+    assert_eq!(
+        continued_ensemble.learning_rates[10..],
+        [ensemble.learning_rates[9]; 2]
+    );
+    assert!(continued_ensemble
+        .predict_batch(&test_set.feature_rows)
+        .iter()
+        .all(|prediction| prediction[2] == ensemble.initial_guess[2]));
+}